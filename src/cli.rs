@@ -1,5 +1,10 @@
 use clap::{Parser, Subcommand, ValueEnum};
-use std::path::PathBuf;
+use std::{ffi::OsString, path::PathBuf};
+
+use crate::{
+    size::parse_size,
+    template::{parse_template, Template},
+};
 
 #[derive(Debug, Clone, Parser)]
 /// A program to interact with the XDG Trash.{n}{n}
@@ -17,6 +22,8 @@ use std::path::PathBuf;
 /// use one of these commands:{n}
 /// trash-put -- -foo{n}
 /// trash-put ./-foo{n}{n}
+/// Set the TRASH_DIR env var to a trash directory to only use that one, for every command{n}
+/// (like put --trash-dir). It is created if it doesn't exist.{n}{n}
 /// You can adjust log verbosity by adjusting the RUST_LOG env var to any of the following:{n}
 ///     - trace{n}
 ///     - debug{n}
@@ -26,6 +33,55 @@ use std::path::PathBuf;
 pub struct RootArgs {
     #[command(subcommand)]
     pub subcommand: SubCmd,
+
+    /// Pretend that the current time is this (format example: 2024-01-24T16:27:00).{n}
+    /// Can also be set through the TRASH_NOW env var
+    #[arg(long, global = true, hide = true)]
+    pub now: Option<chrono::NaiveDateTime>,
+
+    /// Log all internal diagnostics, including where each message originated from
+    #[arg(long, global = true, hide = true)]
+    pub debug_internal: bool,
+
+    /// Answer yes to every question instead of asking, for use in scripts.{n}
+    /// Questions that can't be answered with yes (like choosing between files) fail instead.{n}
+    /// Can also be set through the TRASH_ASSUME_YES env var
+    #[arg(short, long, global = true)]
+    pub yes: bool,
+
+    /// Only print what put, restore, remove, empty or remove-orphaned would change{n}
+    /// in the trash and in the filesystem, without changing anything
+    #[arg(long, global = true)]
+    pub simulate: bool,
+
+    /// Show deletion dates like "2 days ago" (can also be enabled in the config)
+    #[arg(long, global = true)]
+    pub relative_dates: bool,
+
+    /// Show deletion dates in this strftime format (example: '%d.%m.%Y %H:%M'),{n}
+    /// overrides the date_format from the config
+    #[arg(long, global = true)]
+    pub date_format: Option<String>,
+
+    /// Show sizes in blocks of this size like du does (examples: 1K, M, 1MB),{n}
+    /// or `human-readable` (the default) / `si` for units in powers of 1024 / 1000.{n}
+    /// Overrides the block_size from the config
+    #[arg(long, global = true, value_name = "SIZE")]
+    pub block_size: Option<String>,
+
+    /// Use the trash of the user with this uid instead of your own (only for root).{n}
+    /// Meant for cleaning up with list, empty and remove-orphaned
+    #[arg(long, global = true)]
+    pub uid: Option<u32>,
+
+    /// Under sudo or a setuid binary, use the trash of the user who started the program{n}
+    /// instead of the one it runs as (root's trash by default)
+    #[arg(long, global = true, conflicts_with = "uid")]
+    pub invoking_user: bool,
+
+    /// Home dir of the --uid user, looked up in the user database by default
+    #[arg(long, global = true, requires = "uid")]
+    pub home: Option<PathBuf>,
 }
 
 #[derive(Debug, Clone, Subcommand)]
@@ -37,6 +93,36 @@ pub enum SubCmd {
     RemoveOrphaned(RemoveOrphanedArgs),
     Restore(RestoreArgs),
     Remove(RemoveArgs),
+    Batch(BatchArgs),
+    Info(InfoArgs),
+    Versions(VersionsArgs),
+    Diff(DiffArgs),
+    Cat(CatArgs),
+    Open(OpenArgs),
+    Log(LogArgs),
+    /// Undo the last puts and restores recorded in the journal (see `log`).{n}
+    /// Removing and emptying can't be undone
+    Undo(UndoArgs),
+    /// Redo what was undone with `undo`, until something new is done
+    Redo(UndoArgs),
+    Index(IndexArgs),
+    Search(SearchArgs),
+    Stats(StatsArgs),
+    Export(ExportArgs),
+    Doctor(DoctorArgs),
+    Verify(VerifyArgs),
+    Watch(WatchArgs),
+    ImportTrashCli(ImportTrashCliArgs),
+    Repair(RepairArgs),
+    Snapshot(SnapshotArgs),
+    Import(ImportArgs),
+    GenerateMan(GenerateManArgs),
+    GenerateShim(GenerateShimArgs),
+    Version(VersionArgs),
+    #[command(name = "__complete", hide = true)]
+    Complete(CompleteArgs),
+    #[command(name = "__helper", hide = true)]
+    Helper(HelperArgs),
 }
 
 #[derive(Debug, Clone, Parser)]
@@ -45,7 +131,8 @@ pub struct PutArgs {
     /// One or more files to trash
     pub files: Vec<PathBuf>,
 
-    /// Continue on errors (errors will still be logged to stderr)
+    /// Continue on errors (errors will still be logged to stderr).{n}
+    /// Exits with 4 if only some of the files failed
     #[arg(short, long)]
     pub force: bool,
 
@@ -60,15 +147,64 @@ pub struct PutArgs {
     /// Does nothing, exists for compatibility with rm
     #[arg(short, long)]
     pub directory: bool,
+
+    /// Use this trash directory (containing files/ and info/) instead of the trash of each file's device.{n}
+    /// It is created if it doesn't exist. Files on other devices are copied into it (which can be slow)
+    #[arg(long)]
+    pub trash_dir: Option<PathBuf>,
+
+    /// Always use the home trash instead of creating trash directories on other drives.{n}
+    /// Files on other devices are copied into it (which can be slow)
+    #[arg(long, conflicts_with = "trash_dir")]
+    pub home_only: bool,
+
+    /// Trash either all files or none of them.{n}
+    /// If one file can't be trashed, the ones that already were are moved back
+    #[arg(short, long, conflicts_with = "force")]
+    pub atomic: bool,
+
+    /// Name the entries NAME inside the trash instead of using the file name.{n}
+    /// A number is appended if the name is taken, so this works with several files
+    #[arg(long = "as", value_name = "NAME")]
+    pub name: Option<OsString>,
+
+    /// Print which trash each file was put into and its name in there
+    #[arg(short, long)]
+    pub verbose: bool,
+
+    /// Store regular files zstd compressed, restoring them decompresses them again.{n}
+    /// Other trash implementations restore these files compressed
+    #[arg(short = 'z', long)]
+    pub compress: bool,
+
+    /// Encrypt regular files, restoring them decrypts them again.{n}
+    /// The key is read from the key file in the config or from the keyring
+    #[arg(long)]
+    pub encrypt: bool,
+
+    /// Record a SHA-256 of regular files, restore and remove warn if the trashed file{n}
+    /// doesn't match it anymore
+    #[arg(long)]
+    pub checksum: bool,
+
+    /// Ask before trashing files that running programs have open (or work in),{n}
+    /// they keep using the file in the trash. Only processes of your own user are seen
+    #[arg(long)]
+    pub check_open: bool,
 }
 
-/// List trashed files
+/// List trashed files, exits with 3 if there are none
 #[derive(Debug, Clone, Parser)]
 pub struct ListArgs {
-    /// Just output columnns seperated by \t (for easy parsing) (2>/dev/null to ignore erros / warnings)
+    /// Just output columnns seperated by \t (for easy parsing) (2>/dev/null to ignore erros / warnings).{n}
+    /// The default if stdout is not a terminal
     #[arg(short, long)]
     pub simple: bool,
 
+    /// Print the table even if stdout is not a terminal
+    #[arg(long, conflicts_with = "simple")]
+    pub pretty: bool,
+
     /// Also display the trash location where each file resides
     #[arg(short, long)]
     pub trash_location: bool,
@@ -80,14 +216,61 @@ pub struct ListArgs {
     /// Sort by this value
     #[arg(long, value_enum, default_value_t = Sorting::OriginalPath)]
     pub sort: Sorting,
+
+    /// How to print the entries
+    #[arg(short, long, value_enum, default_value_t = ListOutput::Table)]
+    pub output: ListOutput,
+
+    /// Only show this many entries (after sorting).{n}
+    /// Example: `--sort deleted-at --reverse --limit 50` shows the 50 most recently deleted files
+    #[arg(short, long)]
+    pub limit: Option<usize>,
+
+    /// Skip this many entries (after sorting), use with --limit to page through the trash
+    #[arg(long, default_value_t = 0)]
+    pub offset: usize,
+
+    /// Print a seperate table for each group of entries
+    #[arg(short, long, value_enum, conflicts_with = "simple")]
+    pub group_by: Option<Grouping>,
+
+    /// Also show whether something exists at the original location of each file,{n}
+    /// restoring those files needs a new name or overwriting
+    #[arg(short, long)]
+    pub exists: bool,
+
+    /// Only print the number of entries, with `--group-by trash` one line per trash{n}
+    /// (the count, a tab and the trash location)
+    #[arg(short, long, conflicts_with_all = ["limit", "offset", "exists", "simple"])]
+    pub count: bool,
+
+    /// Print every entry with a template instead, like `{id}\t{deleted_at:%Y-%m-%d}\t{path}`.{n}
+    /// Fields: id, deleted_at (takes a strftime format after a :), path, name, trash,{n}
+    /// trash_filename, size (in bytes) and exists. Use {{ and }} for literal braces
+    #[arg(
+        short,
+        long,
+        value_parser = parse_template,
+        conflicts_with_all = ["simple", "pretty", "output", "group_by", "count", "trash_location", "exists"]
+    )]
+    pub format: Option<Template>,
 }
 
 /// List available trashcans on the system
 #[derive(Debug, Clone, Parser)]
 pub struct ListTrashesArgs {
-    /// Just output columnns seperated by \t (for easy parsing) (2>/dev/null to ignore erros / warnings)
+    /// Just output columnns seperated by \t (for easy parsing) (2>/dev/null to ignore erros / warnings).{n}
+    /// The default if stdout is not a terminal
     #[arg(short, long)]
     pub simple: bool,
+
+    /// Print the table even if stdout is not a terminal
+    #[arg(long, conflicts_with = "simple")]
+    pub pretty: bool,
+
+    /// How to print the trashes
+    #[arg(short, long, value_enum, default_value_t = TableOutput::Table, conflicts_with = "simple")]
+    pub output: TableOutput,
 }
 
 /// Empty the trash
@@ -101,27 +284,421 @@ pub struct EmptyArgs {
     #[arg(short = 'B', long)]
     pub before_datetime: Option<chrono::NaiveDateTime>,
 
+    /// Only delete files whose original path matches this glob (example: '~/Downloads/**').{n}
+    /// Relative patterns are relative to the current directory
+    #[arg(short, long)]
+    pub pattern: Option<String>,
+
+    /// Only delete files that were originally located inside this directory
+    #[arg(short, long)]
+    pub under: Option<PathBuf>,
+
     /// Dry run. Don't delete anything, just print.
     #[arg(short, long)]
     pub dry_run: bool,
+
+    /// Overwrite the contents of files before deleting them.{n}
+    /// This does NOT reliably destroy the data on copy-on-write filesystems (btrfs, zfs) and SSDs
+    #[arg(long)]
+    pub shred: bool,
+
+    /// Only print a summary as JSON (`{"removed": 132, "freed": 4509715660}`)
+    #[arg(long)]
+    pub json: bool,
+
+    /// Also show a desktop notification with the summary if anything was removed,{n}
+    /// for runs from a timer or cron job
+    #[arg(long)]
+    pub notify: bool,
+
+    /// Only delete what the retention policy of the config file says should go:{n}
+    /// entries older than retention.max_age and the oldest entries of trashes bigger than retention.max_size
+    #[arg(long, conflicts_with_all = ["before_date", "before_datetime", "pattern"])]
+    pub policy: bool,
+
+    /// With --policy, also delete the N largest entries of every trash that is still bigger{n}
+    /// than retention.max_size or the usage warning allow after the policy was applied
+    #[arg(long, value_name = "N", requires = "policy")]
+    pub emergency_purge: Option<usize>,
 }
 
 /// Remove orphaned trashinfo files
 #[derive(Debug, Clone, Parser)]
 pub struct RemoveOrphanedArgs {}
 
-/// Restore a file from the trash
+/// Restore a file from the trash, exits with 3 if no file matches
 #[derive(Debug, Clone, Parser)]
 pub struct RestoreArgs {
     /// The ID of a file or it's original
-    pub id_or_path: String,
+    #[arg(required_unless_present = "stdin", conflicts_with = "stdin")]
+    pub id_or_path: Option<String>,
+
+    /// Read the IDs or original paths from stdin instead, one per line.{n}
+    /// Lines of `list --simple` can be passed as they are. Files that are in the way
+    /// are only overwritten with --yes, as stdin can't be asked
+    #[arg(long)]
+    pub stdin: bool,
+
+    /// With --stdin, the IDs or paths are separated by NUL instead of newlines
+    #[arg(short = '0', long, requires = "stdin")]
+    pub null: bool,
+
+    /// If several files match, restore the one that was trashed last instead of asking
+    #[arg(short, long)]
+    pub latest: bool,
 }
 
-/// Permanently remove a file from the trash
+/// Permanently remove a file from the trash, exits with 3 if no file matches
 #[derive(Debug, Clone, Parser)]
 pub struct RemoveArgs {
     /// The ID of a file or it's original
+    #[arg(required_unless_present = "stdin", conflicts_with = "stdin")]
+    pub id_or_path: Option<String>,
+
+    /// Read the IDs or original paths from stdin instead, one per line.{n}
+    /// Lines of `list --simple` can be passed as they are. A path that was trashed several
+    /// times fails, as there is no one to ask which one is meant
+    #[arg(long)]
+    pub stdin: bool,
+
+    /// With --stdin, the IDs or paths are separated by NUL instead of newlines
+    #[arg(short = '0', long, requires = "stdin")]
+    pub null: bool,
+
+    /// Overwrite the contents of the file before deleting it.{n}
+    /// This does NOT reliably destroy the data on copy-on-write filesystems (btrfs, zfs) and SSDs
+    #[arg(long)]
+    pub shred: bool,
+
+    /// Dry run. Don't delete anything, just print the files that would be deleted.
+    #[arg(short, long)]
+    pub dry_run: bool,
+}
+
+/// Restore and remove many files at once as described by JSON on stdin, for frontends.{n}
+/// Example: {"operations": [{"op": "restore", "id": "e93c362f7a", "on_conflict": "overwrite"},{n}
+///     {"op": "remove", "id": "/home/user/file.zip", "latest": true, "shred": true}]}{n}
+/// `id` is an ID, the start of one or an original path. A restore with `"on_conflict": "skip"`{n}
+/// (the default) leaves files that are in the way alone.{n}
+/// Prints one JSON object per operation, in order:{n}
+/// {"index": 0, "op": "restore", "id": "e93c362f7a", "status": "ok", "path": "/home/user/notes.txt"}{n}
+/// `status` is `ok`, `skipped` or `error` (with an `error` message). Exits with 1 if any failed
+#[derive(Debug, Clone, Parser)]
+pub struct BatchArgs {}
+
+/// Show everything that is known about a trashed file
+#[derive(Debug, Clone, Parser)]
+pub struct InfoArgs {
+    /// The ID of a file or it's original path
+    pub id_or_path: String,
+}
+
+/// List every trashed version of a path, oldest first.{n}
+/// Restore one of them by its ID
+#[derive(Debug, Clone, Parser)]
+pub struct VersionsArgs {
+    /// The original path (relative paths are relative to the current directory)
+    pub path: PathBuf,
+
+    /// Just output columnns seperated by \t (for easy parsing) (2>/dev/null to ignore erros / warnings)
+    #[arg(short, long)]
+    pub simple: bool,
+}
+
+/// Compare a trashed file with what is at its original location now
+#[derive(Debug, Clone, Parser)]
+pub struct DiffArgs {
+    /// The ID of a file or it's original path (the most recently trashed one if there are several)
+    pub id_or_path: String,
+}
+
+/// Print the contents of a trashed file without restoring it
+#[derive(Debug, Clone, Parser)]
+pub struct CatArgs {
+    /// The ID of a file or it's original path (the most recently trashed one if there are several)
+    pub id_or_path: String,
+}
+
+/// Open a read-only copy of a trashed file with xdg-open (or $EDITOR),
+/// for checking that it is the right file before restoring it
+#[derive(Debug, Clone, Parser)]
+pub struct OpenArgs {
+    /// The ID of a file or it's original path (the most recently trashed one if there are several)
     pub id_or_path: String,
+
+    /// Open it in $VISUAL or $EDITOR instead of the default application
+    #[arg(short, long)]
+    pub editor: bool,
+}
+
+/// Show the journal of puts, restores, removes and empties{n}
+/// (kept in $XDG_STATE_HOME/trash-cli/journal.jsonl)
+#[derive(Debug, Clone, Parser)]
+pub struct LogArgs {
+    /// Only show operations on this path or anything inside it
+    pub path: Option<PathBuf>,
+
+    /// Only show this kind of operation
+    #[arg(short, long, value_enum)]
+    pub action: Option<LogAction>,
+
+    /// Only show the last N operations
+    #[arg(short = 'n', long, value_name = "N")]
+    pub limit: Option<usize>,
+
+    /// Just output columnns seperated by \t (for easy parsing)
+    #[arg(short, long)]
+    pub simple: bool,
+}
+
+/// The arguments of `undo` and `redo`, which have their own help
+#[derive(Debug, Clone, Parser)]
+pub struct UndoArgs {
+    /// How many operations to undo (or redo)
+    #[arg(short = 'n', long, default_value_t = 1, value_name = "N")]
+    pub steps: usize,
+
+    /// Only print what would be done
+    #[arg(short, long)]
+    pub dry_run: bool,
+}
+
+/// Build (or update) an index over the contents of small trashed text files.{n}
+/// The index is stored in $XDG_CACHE_HOME/trash-cli and used by `search --content`
+#[derive(Debug, Clone, Parser)]
+pub struct IndexArgs {
+    /// Don't index files bigger than this (in bytes)
+    #[arg(long, default_value_t = 256 * 1024)]
+    pub max_file_size: u64,
+
+    /// Stop indexing once this many bytes of text are stored
+    #[arg(long, default_value_t = 64 * 1024 * 1024)]
+    pub max_index_size: u64,
+}
+
+/// Search for trashed files
+#[derive(Debug, Clone, Parser)]
+pub struct SearchArgs {
+    /// Find text files containing this text (case insensitive). Requires `trash index` to have been run
+    #[arg(short, long)]
+    pub content: String,
+
+    /// Just output columnns seperated by \t (for easy parsing) (2>/dev/null to ignore erros / warnings)
+    #[arg(short, long)]
+    pub simple: bool,
+}
+
+/// Archive trashed files together with their trashinfo files as tar.{n}
+/// Progress is recorded in <OUTPUT>.manifest, so an interrupted export can be continued with --resume
+#[derive(Debug, Clone, Parser)]
+pub struct ExportArgs {
+    /// Where to write the archive, compressed with zstd if it ends with .zst (example: backup.tar.zst)
+    #[arg(short, long)]
+    pub output: PathBuf,
+
+    /// Only export files that were trashed before this date (format example: 2024-01-24T16:27:00)
+    #[arg(long)]
+    pub before: Option<chrono::NaiveDateTime>,
+
+    /// Only export files that were trashed after this date (format example: 2024-01-24T16:27:00)
+    #[arg(long)]
+    pub after: Option<chrono::NaiveDateTime>,
+
+    /// Only export files that were originally located in this directory
+    #[arg(long)]
+    pub under: Option<PathBuf>,
+
+    /// Split the archive into volumes of about this size (format example: 4GiB).{n}
+    /// Later volumes are named like backup.part2.tar
+    #[arg(long, value_parser = parse_size)]
+    pub volume_size: Option<u64>,
+
+    /// Limit the write speed to this many bytes per second (format example: 20MiB)
+    #[arg(long, value_parser = parse_size)]
+    pub rate_limit: Option<u64>,
+
+    /// Continue a previous export, skipping files that were already exported
+    #[arg(long)]
+    pub resume: bool,
+}
+
+/// Show how many entries the trashes hold and how old they are.{n}
+/// Useful for picking a date for `empty --before-date`
+#[derive(Debug, Clone, Parser)]
+pub struct StatsArgs {
+    /// Just output columnns seperated by \t (for easy parsing) (2>/dev/null to ignore erros / warnings).{n}
+    /// One line per age with the number of entries and their size in bytes
+    #[arg(short, long)]
+    pub simple: bool,
+
+    /// Also list this many of the largest entries across all trashes, with their IDs for `trash remove`.{n}
+    /// With --simple only those are printed, as ID, size in bytes and original location
+    #[arg(short, long, value_name = "N")]
+    pub largest: Option<usize>,
+
+    /// How to print the statistics
+    #[arg(short, long, value_enum, default_value_t = TableOutput::Table, conflicts_with = "simple")]
+    pub output: TableOutput,
+}
+
+/// Check all trashes for violations of the trash spec and other problems
+#[derive(Debug, Clone, Parser)]
+pub struct DoctorArgs {}
+
+/// Check the integrity of all trashes, exits with 1 if any problems are found
+#[derive(Debug, Clone, Parser)]
+pub struct VerifyArgs {}
+
+/// Print entries as they are added to or removed from any trash, by this tool or any other.{n}
+/// Runs until interrupted
+#[derive(Debug, Clone, Parser)]
+pub struct WatchArgs {
+    /// Print tab separated values without colors (event, time, ID, original location, trash)
+    #[arg(short, long)]
+    pub simple: bool,
+}
+
+/// Rewrite trashinfo files written by the python trash-cli (or other older tools){n}
+/// that use unencoded paths, other date formats etc. into a spec compliant form
+#[derive(Debug, Clone, Parser)]
+pub struct ImportTrashCliArgs {
+    /// Dry run. Don't change anything, just print.
+    #[arg(short, long)]
+    pub dry_run: bool,
+}
+
+/// Fix trashinfo files that can't be parsed, either by rewriting them{n}
+/// or by moving them (and their file) into a `recovered` directory inside the trash
+#[derive(Debug, Clone, Parser)]
+pub struct RepairArgs {
+    /// Don't ask, take the date from when the trashinfo file was last changed if it is missing{n}
+    /// and recover entries without a usable path
+    #[arg(short, long)]
+    pub auto: bool,
+
+    /// Dry run. Don't change anything, just print.
+    #[arg(short, long)]
+    pub dry_run: bool,
+}
+
+/// Copy all trashed files into a directory to browse them, ordered by their original location.{n}
+/// Every deletion of a file is a read-only copy named by the deletion date, changing the{n}
+/// snapshot never changes the trash (and later changes of the trash don't show up in it)
+#[derive(Debug, Clone, Parser)]
+pub struct SnapshotArgs {
+    /// An empty (or not yet existing) directory
+    pub dir: PathBuf,
+}
+
+/// Add the files of another trash directory or of an archive created by `export` to the trash.{n}
+/// Every file goes into the trash it would originally have been trashed to
+#[derive(Debug, Clone, Parser)]
+pub struct ImportArgs {
+    /// A trash directory (containing files/ and info/) or a .tar / .tar.zst archive
+    pub source: PathBuf,
+
+    /// Move the files out of the source directory instead of copying them
+    #[arg(short, long = "move")]
+    pub move_entries: bool,
+}
+
+/// Write man pages for trash-cli, its subcommands and the trash-* aliases into a directory
+#[derive(Debug, Clone, Parser)]
+pub struct GenerateManArgs {
+    /// Where to write the pages to, it is created if needed
+    pub dir: PathBuf,
+}
+
+/// Print a shell function named rm that trashes files instead of deleting them.{n}
+/// Add it to your shell config, for example: trash-cli generate-shim >> ~/.bashrc{n}
+/// Use `command rm` to really delete something afterwards
+#[derive(Debug, Clone, Parser)]
+pub struct GenerateShimArgs {
+    #[arg(long, value_enum, default_value_t = ShimShell::Sh)]
+    pub shell: ShimShell,
+
+    /// The name or path the function calls this tool by
+    #[arg(long, default_value = "trash-cli")]
+    pub bin: String,
+}
+
+/// Print the version and which optional capabilities this build has
+#[derive(Debug, Clone, Parser)]
+pub struct VersionArgs {
+    /// Print them as JSON (`{"version": "1.0.1", "capabilities": {"compression": true, ..}}`)
+    #[arg(long)]
+    pub json: bool,
+}
+
+/// Print completion candidates for restore and remove, one `candidate<TAB>description` per line
+#[derive(Debug, Clone, Parser)]
+pub struct CompleteArgs {
+    /// Only print candidates starting with this
+    #[arg(default_value = "")]
+    pub prefix: String,
+}
+
+/// Restore or empty as root on the trash of the user who started pkexec or sudo,
+/// for when they lack the permissions. The polkit policy only covers this mode
+#[derive(Debug, Clone, Parser)]
+pub struct HelperArgs {
+    #[command(subcommand)]
+    pub op: HelperOp,
+}
+
+#[derive(Debug, Clone, Subcommand)]
+pub enum HelperOp {
+    Restore(RestoreArgs),
+    Empty(EmptyArgs),
+}
+
+#[derive(Debug, Clone, PartialEq, ValueEnum)]
+pub enum ListOutput {
+    /// A table, or tab seperated columns with --simple
+    Table,
+
+    /// A GitHub-flavored Markdown table, for pasting into issues and notes
+    Markdown,
+
+    /// A standalone HTML page with a sortable table, for reports
+    Html,
+
+    /// One JSON object per line, printed as soon as each entry is read.{n}
+    /// Entries are not sorted, so memory use stays flat for huge trashes
+    Jsonl,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, ValueEnum)]
+pub enum TableOutput {
+    /// A table, or tab seperated columns with --simple
+    Table,
+
+    /// GitHub-flavored Markdown tables, for pasting into issues and notes
+    Markdown,
+
+    /// A standalone HTML page with sortable tables, for reports
+    Html,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, ValueEnum)]
+pub enum LogAction {
+    Put,
+    Restore,
+    Remove,
+    Empty,
+}
+
+#[derive(Debug, Clone, Copy, ValueEnum)]
+pub enum ShimShell {
+    /// sh, bash, zsh and other POSIX shells
+    Sh,
+    Fish,
+}
+
+#[derive(Debug, Clone, ValueEnum)]
+pub enum Grouping {
+    /// One table per trash location
+    Trash,
 }
 
 #[derive(Debug, Clone, ValueEnum)]
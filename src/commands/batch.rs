@@ -0,0 +1,155 @@
+use std::io::{self, Read, Write};
+
+use anyhow::Context;
+use rustc_hash::FxHashSet;
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    commands::Listing,
+    trashing::{TrashError, Trashinfo, UnifiedTrash},
+};
+
+/// ```json
+/// {"operations": [
+///     {"op": "restore", "id": "e93c362f7a", "on_conflict": "overwrite"},
+///     {"op": "remove", "id": "/home/user/file.zip", "latest": true, "shred": true}
+/// ]}
+/// ```
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct Batch {
+    operations: Vec<Operation>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(tag = "op", rename_all = "lowercase", deny_unknown_fields)]
+enum Operation {
+    Restore {
+        /// An ID, the start of one or an original path
+        id: String,
+
+        /// Pick the most recent entry if several match
+        #[serde(default)]
+        latest: bool,
+
+        #[serde(default)]
+        on_conflict: OnConflict,
+    },
+    Remove {
+        id: String,
+
+        #[serde(default)]
+        latest: bool,
+
+        #[serde(default)]
+        shred: bool,
+    },
+}
+
+/// What to do if something is at the original location of a file that is restored
+#[derive(Debug, Clone, Copy, Default, Deserialize)]
+#[serde(rename_all = "lowercase")]
+enum OnConflict {
+    /// Leave both alone and report the operation as skipped
+    #[default]
+    Skip,
+
+    Overwrite,
+}
+
+/// One line of output for every operation, in the order they were given
+#[derive(Debug, Serialize)]
+struct Outcome<'a> {
+    index: usize,
+    op: &'static str,
+    id: &'a str,
+    status: Status,
+
+    /// The original location of the entry, once it is known
+    #[serde(skip_serializing_if = "Option::is_none")]
+    path: Option<String>,
+
+    #[serde(skip_serializing_if = "Option::is_none")]
+    error: Option<String>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+enum Status {
+    Ok,
+    Skipped,
+    Error,
+}
+
+pub fn batch(_args: crate::cli::BatchArgs, trash: UnifiedTrash) -> anyhow::Result<()> {
+    let mut raw = String::new();
+    io::stdin()
+        .read_to_string(&mut raw)
+        .context("Failed to read stdin")?;
+    let batch: Batch = serde_json::from_str(&raw).context("Invalid batch")?;
+
+    let listing = Listing::new(&trash)?;
+    // an entry is gone once it was handled, the same one twice would fail
+    let mut done = FxHashSet::default();
+    let mut stdout = io::stdout().lock();
+    let mut failed = 0;
+    for (index, operation) in batch.operations.iter().enumerate() {
+        let (op, id, latest) = match operation {
+            Operation::Restore { id, latest, .. } => ("restore", id, *latest),
+            Operation::Remove { id, latest, .. } => ("remove", id, *latest),
+        };
+        let mut outcome = Outcome {
+            index,
+            op,
+            id,
+            status: Status::Ok,
+            path: None,
+            error: None,
+        };
+
+        let result = listing.find(id, latest).and_then(|(entry_id, info)| {
+            outcome.path = Some(info.original_filepath.to_string_lossy().into_owned());
+            anyhow::ensure!(!done.contains(entry_id), "{} was already handled", id);
+            let status = run(&trash, operation, info)?;
+            if status == Status::Ok {
+                done.insert(entry_id);
+            }
+            Ok(status)
+        });
+        match result {
+            Ok(status) => outcome.status = status,
+            Err(e) => {
+                failed += 1;
+                outcome.status = Status::Error;
+                outcome.error = Some(format!("{:#}", e));
+            }
+        }
+
+        writeln!(stdout, "{}", serde_json::to_string(&outcome)?)?;
+    }
+
+    anyhow::ensure!(
+        failed == 0,
+        "{} of {} operations failed",
+        failed,
+        batch.operations.len()
+    );
+    Ok(())
+}
+
+fn run(trash: &UnifiedTrash, operation: &Operation, info: &Trashinfo) -> anyhow::Result<Status> {
+    match operation {
+        Operation::Restore { on_conflict, .. } => {
+            let overwrite = matches!(on_conflict, OnConflict::Overwrite);
+            match trash.restore_entry(info, |_| overwrite) {
+                Ok(_) => Ok(Status::Ok),
+                Err(TrashError::Aborted) => Ok(Status::Skipped),
+                Err(e) => Err(e.into()),
+            }
+        }
+        Operation::Remove { shred, .. } => {
+            trash.remove_entry(info, *shred, false)?;
+            Ok(Status::Ok)
+        }
+    }
+}
@@ -0,0 +1,30 @@
+use std::io::{self, ErrorKind};
+
+use anyhow::Context;
+
+use crate::trashing::UnifiedTrash;
+
+use super::find_entry;
+
+pub fn cat(args: crate::cli::CatArgs, trash: UnifiedTrash) -> anyhow::Result<()> {
+    let info = find_entry(&trash, &args.id_or_path)?;
+    let path = info.trash.files_dir().join(&info.trash_filename);
+
+    anyhow::ensure!(
+        !path.is_dir(),
+        "{} was a directory, use `ls {}` to look inside",
+        info.original_filepath.display(),
+        path.display()
+    );
+
+    let mut file = trash
+        .open_contents(&info)
+        .context(format!("Failed to open {}", path.display()))?;
+    match io::copy(&mut file, &mut io::stdout().lock()) {
+        // `trash cat id | head` closes the pipe early, that's not an error
+        Err(e) if e.kind() == ErrorKind::BrokenPipe => Ok(()),
+        other => other
+            .map(|_| ())
+            .context(format!("Failed to read {}", path.display())),
+    }
+}
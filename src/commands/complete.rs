@@ -0,0 +1,27 @@
+use rustc_hash::FxHashSet;
+
+use crate::{commands::entry_id, trashing::UnifiedTrash};
+
+/// Used by shell completions, so paths that aren't valid UTF-8 are only offered by their ID
+pub fn complete(args: crate::cli::CompleteArgs, trash: UnifiedTrash) -> anyhow::Result<()> {
+    // completions should never print a wall of warnings into the prompt
+    log::set_max_level(log::LevelFilter::Error);
+
+    // a path that was trashed several times is only offered once
+    let mut seen = FxHashSet::default();
+    for info in trash.list()? {
+        let id = entry_id(&info);
+        if id.starts_with(&args.prefix) {
+            println!("{}\t{}", id, info.original_filepath.display());
+        }
+
+        let path = info.original_filepath.to_str();
+        if let Some(path) = path.filter(|x| x.starts_with(&args.prefix)) {
+            if seen.insert(path.to_owned()) {
+                println!("{}\ttrashed file", path);
+            }
+        }
+    }
+
+    Ok(())
+}
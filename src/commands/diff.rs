@@ -0,0 +1,91 @@
+use std::{fs, io::Read};
+
+use anyhow::Context;
+use colored::Colorize;
+use sha2::{Digest, Sha256};
+use similar::TextDiff;
+
+use crate::{size::format_size, trashing::UnifiedTrash};
+
+use super::{encode_hex, entry_id, find_entry};
+
+pub fn diff(args: crate::cli::DiffArgs, trash: UnifiedTrash) -> anyhow::Result<()> {
+    let info = find_entry(&trash, &args.id_or_path)?;
+    let trashed_path = info.trash.files_dir().join(&info.trash_filename);
+    let current_path = &info.original_filepath;
+
+    if fs::symlink_metadata(current_path).is_err() {
+        println!(
+            "Nothing exists at {} anymore, restoring won't overwrite anything",
+            current_path.display()
+        );
+        return Ok(());
+    }
+
+    anyhow::ensure!(
+        fs::metadata(&trashed_path)?.is_file() && fs::metadata(current_path)?.is_file(),
+        "Only files can be compared"
+    );
+
+    let mut trashed = vec![];
+    trash
+        .open_contents(&info)?
+        .read_to_end(&mut trashed)
+        .context(format!("Failed to read {}", trashed_path.display()))?;
+    let current =
+        fs::read(current_path).context(format!("Failed to read {}", current_path.display()))?;
+
+    if trashed == current {
+        println!(
+            "The trashed file and {} are identical",
+            current_path.display()
+        );
+        return Ok(());
+    }
+
+    match (as_text(&trashed), as_text(&current)) {
+        (Some(trashed), Some(current)) => {
+            let diff = TextDiff::from_lines(trashed, current);
+            let unified = diff
+                .unified_diff()
+                .header(
+                    &format!("trash/{}", entry_id(&info)),
+                    &current_path.display().to_string(),
+                )
+                .to_string();
+
+            for line in unified.lines() {
+                match line.as_bytes().first() {
+                    _ if line.starts_with("---") || line.starts_with("+++") => {
+                        println!("{}", line.bold())
+                    }
+                    Some(b'-') => println!("{}", line.red()),
+                    Some(b'+') => println!("{}", line.green()),
+                    Some(b'@') => println!("{}", line.cyan()),
+                    _ => println!("{}", line),
+                }
+            }
+        }
+        // a line diff of binary files is just noise
+        _ => {
+            println!("Binary files differ");
+            for (name, content) in [("Trashed", &trashed), ("Current", &current)] {
+                println!(
+                    "{}: {} sha256 {}",
+                    name,
+                    format_size(content.len() as u64),
+                    encode_hex(&Sha256::digest(content))
+                );
+            }
+        }
+    }
+
+    Ok(())
+}
+
+fn as_text(content: &[u8]) -> Option<&str> {
+    match content.contains(&0) {
+        true => None,
+        false => std::str::from_utf8(content).ok(),
+    }
+}
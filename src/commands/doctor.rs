@@ -0,0 +1,31 @@
+use std::process::exit;
+
+use anyhow::Context;
+use colored::Colorize;
+
+use crate::trashing::{diagnose, Severity, UnifiedTrash};
+
+pub fn doctor(_args: crate::cli::DoctorArgs, trash: UnifiedTrash) -> anyhow::Result<()> {
+    let findings = diagnose(&trash).context("Failed to check trashes")?;
+
+    if findings.is_empty() {
+        println!("No problems found");
+        return Ok(());
+    }
+
+    for finding in &findings {
+        let severity = match finding.severity {
+            Severity::Error => "Error".red(),
+            Severity::Warning => "Warn ".yellow(),
+        };
+        println!(
+            "{} {} {}",
+            severity,
+            finding.path.display(),
+            finding.problem
+        );
+        println!("      {} {}", "->".bright_black(), finding.fix);
+    }
+
+    exit(1);
+}
@@ -1,5 +1,15 @@
+use std::env;
+
 use anyhow::Context;
 use chrono::NaiveTime;
+use glob::{MatchOptions, Pattern};
+
+use crate::{
+    elevate::{is_permission_denied, retry_as_root},
+    notify::notify,
+    size::format_size,
+    trashing::{lexical_absolute, EmptyOptions},
+};
 
 pub fn empty(args: crate::cli::EmptyArgs, trash: crate::UnifiedTrash) -> anyhow::Result<()> {
     let older_than = args
@@ -7,14 +17,166 @@ pub fn empty(args: crate::cli::EmptyArgs, trash: crate::UnifiedTrash) -> anyhow:
         .or(args
             .before_date
             .map(|x| x.and_time(NaiveTime::from_num_seconds_from_midnight_opt(0, 0).unwrap())))
-        .unwrap_or(chrono::Local::now().naive_local());
+        .unwrap_or(trash.now());
+
+    let pattern = args
+        .pattern
+        .map(|x| absolute_pattern(&x))
+        .transpose()
+        .context("Invalid pattern")?;
+
+    // original paths are absolute and never contain `..`
+    let under = args
+        .under
+        .map(|x| lexical_absolute(&x))
+        .transpose()
+        .context("Failed to get current dir")?;
+
+    // `*` should not match across directories, `**` is there for that
+    let options = MatchOptions {
+        require_literal_separator: true,
+        ..Default::default()
+    };
+
+    let opts = EmptyOptions {
+        dry_run: args.dry_run,
+        shred: args.shred,
+        quiet: args.json,
+        under,
+    };
+    let summary = if args.policy {
+        trash.empty_by_retention(&opts, args.emergency_purge)
+    } else {
+        trash.empty(
+            older_than,
+            |info| {
+                pattern
+                    .as_ref()
+                    .is_none_or(|x| x.matches_path_with(&info.original_filepath, options))
+            },
+            &opts,
+        )
+    };
+    let summary = match summary {
+        Err(e) if is_permission_denied(&e) && !args.dry_run => {
+            // the same selection, with the relative parts already resolved
+            let mut helper_args = vec!["--yes".to_owned(), "empty".to_owned()];
+            if args.policy {
+                helper_args.push("--policy".to_owned());
+                if let Some(n) = args.emergency_purge {
+                    helper_args.push(format!("--emergency-purge={}", n));
+                }
+            } else {
+                helper_args.push(format!("--before-datetime={}", older_than.format("%FT%T")));
+            }
+            if let Some(pattern) = &pattern {
+                helper_args.push(format!("--pattern={}", pattern.as_str()));
+            }
+            if let Some(under) = &opts.under {
+                helper_args.push(format!("--under={}", under.display()));
+            }
+            if args.shred {
+                helper_args.push("--shred".to_owned());
+            }
+            if args.json {
+                helper_args.push("--json".to_owned());
+            }
+            return retry_as_root(e, "Failed to empty trash", &helper_args);
+        }
+        other => other.context("Failed to empty trash")?,
+    };
+
+    let message = if args.dry_run {
+        format!(
+            "Would remove {} items, freeing {}",
+            summary.removed,
+            format_size(summary.freed)
+        )
+    } else {
+        format!(
+            "Removed {} items, freed {}",
+            summary.removed,
+            format_size(summary.freed)
+        )
+    };
+
+    let message = match summary.stray_dirs {
+        0 => message,
+        n => format!("{} and {} empty stray directories", message, n),
+    };
 
-    trash
-        .empty(older_than, args.dry_run)
-        .context("Failed to empty trash")?;
+    if args.json {
+        println!("{}", serde_json::to_string(&summary)?);
+    } else {
+        println!("{}", message);
+    }
+
+    // the policy alone won't keep these from filling up the disk
+    let mut warnings = vec![];
+    for stuck in &summary.stuck {
+        let advice = match stuck.suggested_max_age {
+            _ if stuck.untracked > stuck.limit => format!(
+                "{} of it is not tracked by any trashinfo file, run `trash doctor` to find it",
+                format_size(stuck.untracked)
+            ),
+            Some(days) => format!(
+                "consider a stricter policy like retention.max_age = \"{}d\"",
+                days
+            ),
+            None => "consider a lower retention.max_size".to_owned(),
+        };
+        let warning = match stuck.purged {
+            0 => format!(
+                "{} still takes up {} (limit {}), {}",
+                stuck.trash_path.display(),
+                format_size(stuck.used),
+                format_size(stuck.limit),
+                advice
+            ),
+            n => format!(
+                "{} took up more than {}, purged its {} largest entries. It now takes up {}, {}",
+                stuck.trash_path.display(),
+                format_size(stuck.limit),
+                n,
+                format_size(stuck.used),
+                advice
+            ),
+        };
+        log::warn!("{}", warning);
+        warnings.push(warning);
+    }
+    if args.notify && !warnings.is_empty() {
+        if let Err(e) = notify("Trash keeps growing", &warnings.join("\n")) {
+            log::warn!("{:#}", e);
+        }
+    }
 
-    if !args.dry_run {
-        println!("Emptied trash!");
+    if args.notify && summary.removed > 0 {
+        // the trash was emptied either way, a missing notification daemon is no reason to fail
+        let title = match args.dry_run {
+            true => "Trash not emptied (dry run)",
+            false => "Trash emptied",
+        };
+        if let Err(e) = notify(title, &message) {
+            log::warn!("{:#}", e);
+        }
     }
+
     Ok(())
 }
+
+/// Expands a leading `~` and makes relative patterns relative to the current directory,
+/// since original paths are always absolute
+fn absolute_pattern(pattern: &str) -> anyhow::Result<Pattern> {
+    let pattern = if let Some(rest) = pattern.strip_prefix("~/") {
+        let home = env::var("HOME").context("No home dir set!")?;
+        format!("{}/{}", Pattern::escape(&home), rest)
+    } else if !pattern.starts_with('/') {
+        let cwd = env::current_dir().context("Failed to get current dir")?;
+        format!("{}/{}", Pattern::escape(&cwd.to_string_lossy()), pattern)
+    } else {
+        pattern.to_owned()
+    };
+
+    Ok(Pattern::new(&pattern)?)
+}
@@ -0,0 +1,47 @@
+use anyhow::Context;
+
+use crate::trashing::{self, lexical_absolute, ExportOptions, UnifiedTrash};
+
+pub fn export(args: crate::cli::ExportArgs, trash: UnifiedTrash) -> anyhow::Result<()> {
+    let mut entries = trash.list().context("Failed to list trash")?;
+
+    // original paths are absolute and never contain `..`
+    let under = args
+        .under
+        .map(|x| lexical_absolute(&x))
+        .transpose()
+        .context("Failed to get current dir")?
+        .unwrap_or_default();
+    entries.retain(|x| {
+        args.before.is_none_or(|before| x.deleted_at < before)
+            && args.after.is_none_or(|after| x.deleted_at > after)
+            && x.original_filepath.starts_with(&under)
+    });
+
+    let summary = trashing::export(
+        &entries,
+        &ExportOptions {
+            output: args.output,
+            volume_size: args.volume_size,
+            rate_limit: args.rate_limit,
+            resume: args.resume,
+            now: trash.now(),
+        },
+    )
+    .context("Failed to export trash")?;
+
+    for volume in &summary.volumes {
+        println!("Wrote {}", volume.display());
+    }
+
+    if summary.skipped > 0 {
+        println!(
+            "Exported {} files, skipped {} files that were already exported",
+            summary.exported, summary.skipped
+        );
+    } else {
+        println!("Exported {} files", summary.exported);
+    }
+
+    Ok(())
+}
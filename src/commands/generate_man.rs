@@ -0,0 +1,43 @@
+use std::{fs, path::Path};
+
+use anyhow::Context;
+use clap::{Command, CommandFactory};
+
+use crate::{cli, trashing::UnifiedTrash};
+
+pub fn generate_man(args: cli::GenerateManArgs, _trash: UnifiedTrash) -> anyhow::Result<()> {
+    fs::create_dir_all(&args.dir).context("Failed to create output dir")?;
+
+    let root = cli::RootArgs::command();
+    let mut pages = vec![root.clone()];
+
+    for sub in root.get_subcommands().filter(|x| !x.is_hide_set()) {
+        let name = format!("{}-{}", root.get_name(), sub.get_name());
+        pages.push(sub.clone().name(name));
+    }
+
+    // the names main() dispatches on
+    pages.push(cli::PutArgs::command().name("trash"));
+    pages.push(cli::PutArgs::command().name("trash-put"));
+    pages.push(cli::ListArgs::command().name("trash-list"));
+    pages.push(cli::EmptyArgs::command().name("trash-empty"));
+    pages.push(cli::RestoreArgs::command().name("trash-restore"));
+    pages.push(cli::RemoveArgs::command().name("trash-rm"));
+
+    for page in pages {
+        let path = write_page(page, &args.dir)?;
+        println!("Wrote {}", path.display());
+    }
+
+    Ok(())
+}
+
+fn write_page(command: Command, dir: &Path) -> anyhow::Result<std::path::PathBuf> {
+    let path = dir.join(format!("{}.1", command.get_name()));
+    let mut out = vec![];
+    clap_mangen::Man::new(command)
+        .render(&mut out)
+        .context("Failed to render man page")?;
+    fs::write(&path, out).context(format!("Failed to write {}", path.display()))?;
+    Ok(path)
+}
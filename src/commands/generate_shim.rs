@@ -0,0 +1,156 @@
+use crate::{cli, trashing::UnifiedTrash};
+
+/// Works in sh, dash, bash and zsh. POSIX sh has no arrays or locals,
+/// so the files are collected back into `$@` and the helpers are prefixed and unset
+const POSIX: &str = r#"# rm that moves files into the trash instead, generated by `BIN generate-shim`.
+# -f ignores missing files, -v prints what was trashed, -r, -R, -d, -i and -I are accepted and ignored.
+rm() {
+    _trash_opts=
+    _trash_force=
+    _trash_end=
+    _trash_n=$#
+    while [ "$_trash_n" -gt 0 ]; do
+        _trash_arg=$1
+        shift
+        _trash_n=$((_trash_n - 1))
+        if [ -z "$_trash_end" ]; then
+            case $_trash_arg in
+                --) _trash_end=1; continue ;;
+                --force) _trash_force=1; _trash_opts="$_trash_opts -f"; continue ;;
+                --verbose) _trash_opts="$_trash_opts -v"; continue ;;
+                --recursive|--dir|--interactive|--interactive=*|--one-file-system|--preserve-root|--preserve-root=*|--no-preserve-root) continue ;;
+                --*) printf 'rm: unsupported option %s\n' "$_trash_arg" >&2; return 1 ;;
+                -?*)
+                    _trash_flags=${_trash_arg#-}
+                    while [ -n "$_trash_flags" ]; do
+                        _trash_flag=${_trash_flags%"${_trash_flags#?}"}
+                        _trash_flags=${_trash_flags#?}
+                        case $_trash_flag in
+                            f) _trash_force=1; _trash_opts="$_trash_opts -f" ;;
+                            v) _trash_opts="$_trash_opts -v" ;;
+                            r|R|d|i|I) ;;
+                            *) printf "rm: invalid option -- '%s'\n" "$_trash_flag" >&2; return 1 ;;
+                        esac
+                    done
+                    continue ;;
+            esac
+        fi
+        set -- "$@" "$_trash_arg"
+    done
+
+    # like rm -f, missing files are no error
+    if [ -n "$_trash_force" ]; then
+        _trash_n=$#
+        while [ "$_trash_n" -gt 0 ]; do
+            _trash_arg=$1
+            shift
+            _trash_n=$((_trash_n - 1))
+            if [ -e "$_trash_arg" ] || [ -L "$_trash_arg" ]; then
+                set -- "$@" "$_trash_arg"
+            fi
+        done
+    fi
+
+    _trash_verbose=
+    case $_trash_opts in *-v*) _trash_verbose=1 ;; esac
+    _trash_status=0
+    if [ $# -gt 0 ]; then
+        if [ -n "$_trash_verbose" ]; then
+            command BIN put $_trash_opts -- "$@" || _trash_status=$?
+        else
+            command BIN put $_trash_opts -- "$@" >/dev/null || _trash_status=$?
+        fi
+    elif [ -z "$_trash_force" ]; then
+        echo 'rm: missing operand' >&2
+        _trash_status=1
+    fi
+
+    unset _trash_opts _trash_force _trash_end _trash_n _trash_arg _trash_flags _trash_flag _trash_verbose
+    return $_trash_status
+}
+"#;
+
+const FISH: &str = r#"# rm that moves files into the trash instead, generated by `BIN generate-shim --shell fish`.
+# -f ignores missing files, -v prints what was trashed, -r, -R, -d, -i and -I are accepted and ignored.
+function rm --description 'Move files into the trash'
+    set -l opts
+    set -l files
+    set -l force 0
+    set -l verbose 0
+    set -l end 0
+    for arg in $argv
+        if test $end -eq 0
+            switch $arg
+                case --
+                    set end 1
+                    continue
+                case --force
+                    set force 1
+                    continue
+                case --verbose
+                    set verbose 1
+                    continue
+                case --recursive --dir --interactive '--interactive=*' --one-file-system --preserve-root '--preserve-root=*' --no-preserve-root
+                    continue
+                case '--*'
+                    echo "rm: unsupported option $arg" >&2
+                    return 1
+                case '-?*'
+                    for flag in (string split '' -- (string sub -s 2 -- $arg))
+                        switch $flag
+                            case f
+                                set force 1
+                            case v
+                                set verbose 1
+                            case r R d i I
+                            case '*'
+                                echo "rm: invalid option -- '$flag'" >&2
+                                return 1
+                        end
+                    end
+                    continue
+            end
+        end
+        # like rm -f, missing files are no error
+        if test $force -eq 0; or test -e $arg; or test -L $arg
+            set -a files $arg
+        end
+    end
+
+    if test $force -eq 1
+        set -a opts -f
+    end
+    if test (count $files) -eq 0
+        if test $force -eq 0
+            echo 'rm: missing operand' >&2
+            return 1
+        end
+        return 0
+    end
+    if test $verbose -eq 1
+        command BIN put $opts -v -- $files
+    else
+        command BIN put $opts -- $files >/dev/null
+    end
+end
+"#;
+
+pub fn generate_shim(args: cli::GenerateShimArgs, _trash: UnifiedTrash) -> anyhow::Result<()> {
+    // it ends up in the function unquoted
+    anyhow::ensure!(
+        !args.bin.is_empty()
+            && args
+                .bin
+                .chars()
+                .all(|x| x.is_ascii_alphanumeric() || "/._-+".contains(x)),
+        "--bin may only contain letters, digits and / . _ - +"
+    );
+
+    let template = match args.shell {
+        cli::ShimShell::Sh => POSIX,
+        cli::ShimShell::Fish => FISH,
+    };
+    print!("{}", template.replace("BIN", &args.bin));
+
+    Ok(())
+}
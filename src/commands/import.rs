@@ -0,0 +1,19 @@
+use anyhow::Context;
+
+use crate::trashing::{self, UnifiedTrash};
+
+pub fn import(args: crate::cli::ImportArgs, trash: UnifiedTrash) -> anyhow::Result<()> {
+    let summary = trashing::import(&trash, &args.source, args.move_entries)
+        .context(format!("Failed to import {}", args.source.display()))?;
+
+    if summary.skipped > 0 {
+        println!(
+            "Imported {} files, skipped {} incomplete entries",
+            summary.imported, summary.skipped
+        );
+    } else {
+        println!("Imported {} files", summary.imported);
+    }
+
+    Ok(())
+}
@@ -0,0 +1,35 @@
+use anyhow::Context;
+
+use crate::trashing::{migrate_trash_cli, UnifiedTrash};
+
+pub fn import_trash_cli(
+    args: crate::cli::ImportTrashCliArgs,
+    trash: UnifiedTrash,
+) -> anyhow::Result<()> {
+    let migration =
+        migrate_trash_cli(&trash, args.dry_run).context("Failed to migrate trashinfo files")?;
+
+    for adjustment in &migration.adjusted {
+        if args.dry_run {
+            println!("Would adjust {}", adjustment.path.display());
+        } else {
+            println!("Adjusted {}", adjustment.path.display());
+        }
+        for change in &adjustment.changes {
+            println!("  - {}", change);
+        }
+    }
+
+    println!(
+        "{} of {} trashinfo files {}",
+        migration.adjusted.len(),
+        migration.checked,
+        if args.dry_run {
+            "need adjustments"
+        } else {
+            "were adjusted"
+        }
+    );
+
+    Ok(())
+}
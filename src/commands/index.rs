@@ -0,0 +1,23 @@
+use anyhow::Context;
+
+use crate::trashing::{ContentIndex, IndexLimits, UnifiedTrash};
+
+pub fn index(args: crate::cli::IndexArgs, trash: UnifiedTrash) -> anyhow::Result<()> {
+    let previous = ContentIndex::load().context("Failed to load existing content index")?;
+
+    let index = ContentIndex::build(
+        &trash,
+        IndexLimits {
+            max_file_size: args.max_file_size,
+            max_index_size: args.max_index_size,
+        },
+        previous.as_ref(),
+    )
+    .context("Failed to build content index")?;
+
+    index.save().context("Failed to save content index")?;
+
+    println!("Indexed {} files", index.len());
+
+    Ok(())
+}
@@ -0,0 +1,85 @@
+use std::{fs, path::Path};
+
+use anyhow::Context;
+use colored::Colorize;
+
+use crate::{
+    dates::format_exact,
+    size::format_size,
+    trashing::{path_size, Trashinfo, UnifiedTrash},
+};
+
+use super::{entry_id, expand_id};
+
+pub fn info(args: crate::cli::InfoArgs, trash: UnifiedTrash) -> anyhow::Result<()> {
+    let id = expand_id(&trash, &args.id_or_path)?;
+    let matching = trash
+        .list()
+        .context("Failed to list trash")?
+        .into_iter()
+        .filter(|info| {
+            entry_id(info) == id || info.original_filepath == Path::new(&args.id_or_path)
+        })
+        .collect::<Vec<_>>();
+
+    anyhow::ensure!(!matching.is_empty(), "No files match {}", args.id_or_path);
+
+    for (i, info) in matching.iter().enumerate() {
+        if i > 0 {
+            println!();
+        }
+        print_info(info)?;
+    }
+
+    Ok(())
+}
+
+fn print_info(info: &Trashinfo) -> anyhow::Result<()> {
+    let files_path = info.trash.files_dir().join(&info.trash_filename);
+    let info_path = info.trash.info_dir().join(&info.trash_filename_trashinfo);
+
+    let meta = fs::symlink_metadata(&files_path)
+        .context(format!("Failed to stat {}", files_path.display()))?;
+    let kind = if meta.is_symlink() {
+        "symlink"
+    } else if meta.is_dir() {
+        "directory"
+    } else {
+        "file"
+    };
+    let size = path_size(&files_path)
+        .map(format_size)
+        .unwrap_or_else(|e| format!("unknown ({})", e));
+
+    let original_exists = match fs::symlink_metadata(&info.original_filepath) {
+        Ok(_) => "yes, restoring needs a new name".yellow(),
+        Err(_) => "no".normal(),
+    };
+
+    let raw = fs::read_to_string(&info_path)
+        .context(format!("Failed to read {}", info_path.display()))?;
+
+    let rows = [
+        ("ID", entry_id(info)),
+        (
+            "Original path",
+            info.original_filepath.display().to_string(),
+        ),
+        ("Deleted at", format_exact(info.deleted_at)),
+        ("Trash", info.trash.trash_path.display().to_string()),
+        ("Trashed file", files_path.display().to_string()),
+        ("Type", kind.to_owned()),
+        ("Size", size),
+        ("Original exists", original_exists.to_string()),
+    ];
+    for (key, value) in rows {
+        println!("{:<16} {}", format!("{}:", key).bold(), value);
+    }
+
+    println!("{}", "Trashinfo:".bold());
+    for line in raw.lines() {
+        println!("  {}", line);
+    }
+
+    Ok(())
+}
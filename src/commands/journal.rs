@@ -0,0 +1,80 @@
+use std::path::Path;
+
+use anyhow::Context;
+
+use crate::{
+    cli::LogAction,
+    dates::{format_date, format_exact},
+    table::table,
+    trashing::{lexical_absolute, Action, UnifiedTrash, Via},
+};
+
+pub fn log(args: crate::cli::LogArgs, trash: UnifiedTrash) -> anyhow::Result<()> {
+    let journal = trash.journal().context("No journal is configured")?;
+
+    let under = match &args.path {
+        Some(v) => Some(lexical_absolute(v).context("Failed to get current dir")?),
+        None => None,
+    };
+    let action = args.action.map(|x| match x {
+        LogAction::Put => Action::Put,
+        LogAction::Restore => Action::Restore,
+        LogAction::Remove => Action::Remove,
+        LogAction::Empty => Action::Empty,
+    });
+
+    let entries = journal
+        .read()?
+        .into_iter()
+        .filter(|x| action.is_none_or(|action| x.action == action))
+        .filter(|x| {
+            under
+                .as_ref()
+                .is_none_or(|under| Path::new(&x.original_path).starts_with(under))
+        })
+        .collect::<Vec<_>>();
+
+    // the journal is oldest first, so the last ones are the most recent
+    let skip = args
+        .limit
+        .map_or(0, |limit| entries.len().saturating_sub(limit));
+
+    let now = trash.now();
+    let rows = entries
+        .iter()
+        .skip(skip)
+        .map(|x| {
+            [
+                match args.simple {
+                    true => format_exact(x.at),
+                    false => format_date(x.at, now),
+                },
+                match x.via {
+                    Some(Via::Undo) => format!("{} (undo)", x.action),
+                    Some(Via::Redo) => format!("{} (redo)", x.action),
+                    None => x.action.to_string(),
+                },
+                x.uid.to_string(),
+                Path::new(&x.original_path).display().to_string(),
+                Path::new(&x.trash).display().to_string(),
+            ]
+        })
+        .collect::<Vec<_>>();
+
+    if args.simple {
+        for row in rows {
+            println!("{}", row.join("\t"));
+        }
+    } else if rows.is_empty() {
+        println!("Nothing recorded in {}", journal.path().display());
+    } else {
+        println!();
+        table(
+            &rows,
+            &["Time", "Action", "Uid", "Original location", "Trash"],
+        );
+        println!();
+    }
+
+    Ok(())
+}
@@ -1,17 +1,44 @@
 use crate::{
     cli,
-    commands::id_from_bytes,
-    table::table,
+    commands::{entry_id, EXIT_NO_MATCH},
+    dates::{format_date, format_exact},
+    table::{escape_markdown, markdown_table, table, HtmlPage},
     trashing::{Trashinfo, UnifiedTrash},
 };
-use std::os::unix::ffi::OsStrExt;
+use chrono::NaiveDateTime;
+use colored::Colorize;
+use std::{
+    fs,
+    io::{self, IsTerminal, Write},
+    process::exit,
+};
+
+pub fn list(mut args: cli::ListArgs, trash: UnifiedTrash) -> anyhow::Result<()> {
+    // like ls, pipes get what is easy to parse. Grouped tables have no simple form
+    if !args.pretty
+        && args.output == cli::ListOutput::Table
+        && args.group_by.is_none()
+        && !io::stdout().is_terminal()
+    {
+        args.simple = true;
+    }
+
+    if args.count {
+        return list_count(&trash, args.group_by.is_some());
+    }
+    if args.output == cli::ListOutput::Jsonl {
+        let listed = list_jsonl(&trash, args.offset, args.limit, args.exists)?;
+        if listed == 0 {
+            exit(EXIT_NO_MATCH);
+        }
+        return Ok(());
+    }
 
-pub fn list(args: cli::ListArgs, trash: UnifiedTrash) -> anyhow::Result<()> {
     let mut entries = vec![];
 
     let mut trash_list = trash.list()?;
 
-    let sorter: for<'a> fn(&Trashinfo<'a>, &Trashinfo<'a>) -> _ = match args.sort {
+    let sorter: fn(&Trashinfo, &Trashinfo) -> _ = match args.sort {
         cli::Sorting::Trash => |a, b| a.trash.trash_path.cmp(&b.trash.trash_path),
         cli::Sorting::OriginalPath => |a, b| a.original_filepath.cmp(&b.original_filepath),
         cli::Sorting::DeletedAt => |a, b| a.deleted_at.cmp(&b.deleted_at),
@@ -22,47 +49,226 @@ pub fn list(args: cli::ListArgs, trash: UnifiedTrash) -> anyhow::Result<()> {
         trash_list.reverse();
     }
 
-    for entry in trash_list {
-        let id = id_from_bytes(entry.original_filepath.as_os_str().as_bytes());
+    let limit = args.limit.unwrap_or(usize::MAX);
+    if let Some(template) = &args.format {
+        let mut listed = 0;
+        for entry in trash_list.iter().skip(args.offset).take(limit) {
+            println!("{}", template.render(entry));
+            listed += 1;
+        }
+        if listed == 0 {
+            exit(EXIT_NO_MATCH);
+        }
+        return Ok(());
+    }
+
+    let now = trash.now();
+    for entry in trash_list.into_iter().skip(args.offset).take(limit) {
+        entries.push(Row {
+            id: entry_id(&entry),
+            // --simple is meant for scripts and reports are read later, so they keep the exact date
+            deleted_at: match args.simple || args.output == cli::ListOutput::Html {
+                true => format_exact(entry.deleted_at),
+                false => format_date(entry.deleted_at, now),
+            },
+            trash: entry.trash.trash_path.display().to_string(),
+            original: match args.simple {
+                true => entry.original_filepath.display().to_string(),
+                false => display_original(&entry),
+            },
+            original_exists: args
+                .exists
+                .then(|| fs::symlink_metadata(&entry.original_filepath).is_ok()),
+        });
+    }
+
+    if entries.is_empty() {
+        if !args.simple {
+            println!("No trashed files to list");
+        }
+        exit(EXIT_NO_MATCH);
+    }
+
+    if let Some(cli::Grouping::Trash) = args.group_by {
+        print_grouped(entries, args.output, now);
+        return Ok(());
+    }
+
+    let rows = entries
+        .iter()
+        .map(|x| x.columns(args.trash_location))
+        .collect::<Vec<_>>();
 
-        entries.push([
-            id,
-            entry.deleted_at.to_string(),
-            entry.trash.trash_path.display().to_string(),
-            entry.original_filepath.display().to_string(),
-        ]);
+    if args.output == cli::ListOutput::Html {
+        let mut page = HtmlPage::new("Trash", now);
+        page.paragraph(&format!("{} entries", rows.len()));
+        page.table(&rows, &Row::headers(args.trash_location, args.exists));
+        page.print();
+    } else if args.output == cli::ListOutput::Markdown {
+        markdown_table(&rows, &Row::headers(args.trash_location, args.exists));
+    } else if args.simple {
+        for row in rows {
+            println!("{}", row.join("\t"));
+        }
+    } else {
+        println!();
+        table(&rows, &Row::headers(args.trash_location, args.exists));
+        println!();
     }
 
-    match (args.simple, args.trash_location) {
-        (true, true) => {
-            for row in entries {
-                println!("{}\t{}\t{}\t{}", row[0], row[1], row[2], row[3]);
-            }
+    Ok(())
+}
+
+/// An entry as it is printed
+struct Row {
+    id: String,
+    deleted_at: String,
+    trash: String,
+    original: String,
+
+    /// Whether something is at the original path (restoring would conflict), if requested
+    original_exists: Option<bool>,
+}
+
+impl Row {
+    fn columns(&self, trash_location: bool) -> Vec<String> {
+        let mut columns = vec![self.id.clone(), self.deleted_at.clone()];
+        if trash_location {
+            columns.push(self.trash.clone());
         }
-        (true, false) => {
-            for row in entries {
-                println!("{}\t{}\t{}", row[0], row[1], row[3]);
-            }
+        columns.push(self.original.clone());
+        if let Some(exists) = self.original_exists {
+            columns.push(match exists {
+                true => "yes".to_owned(),
+                false => "no".to_owned(),
+            });
+        }
+        columns
+    }
+
+    fn headers(trash_location: bool, exists: bool) -> Vec<&'static str> {
+        let mut headers = vec!["ID", "Deleted at"];
+        if trash_location {
+            headers.push("Trash location");
         }
-        (false, true) => {
+        headers.push("Original location");
+        if exists {
+            headers.push("Original exists");
+        }
+        headers
+    }
+}
+
+/// The original path, with the target if a symlink was trashed (so it isn't mistaken for the content)
+fn display_original(entry: &Trashinfo) -> String {
+    let trashed = entry.trash.files_dir().join(&entry.trash_filename);
+    match fs::read_link(trashed) {
+        Ok(target) => format!(
+            "{} -> {}",
+            entry.original_filepath.display(),
+            target.display()
+        ),
+        Err(_) => entry.original_filepath.display().to_string(),
+    }
+}
+
+/// Prints a header and table for every trash, in the order the trashes first appear in `entries`
+fn print_grouped(entries: Vec<Row>, output: cli::ListOutput, now: NaiveDateTime) {
+    let exists = entries.iter().any(|x| x.original_exists.is_some());
+
+    let mut groups: Vec<(String, Vec<Vec<String>>)> = vec![];
+    for entry in entries {
+        let row = entry.columns(false);
+        match groups.iter_mut().find(|(x, _)| *x == entry.trash) {
+            Some((_, rows)) => rows.push(row),
+            None => groups.push((entry.trash, vec![row])),
+        }
+    }
+
+    if output == cli::ListOutput::Html {
+        let mut page = HtmlPage::new("Trash", now);
+        for (trash, rows) in groups {
+            page.heading(&format!("{} ({} entries)", trash, rows.len()));
+            page.table(&rows, &Row::headers(false, exists));
+        }
+        page.print();
+        return;
+    }
+
+    for (trash, rows) in groups {
+        if output == cli::ListOutput::Markdown {
+            println!("### {} ({} entries)", escape_markdown(&trash), rows.len());
             println!();
-            table(
-                &entries,
-                ["ID", "Deleted at", "Trash location", "Original location"],
-            );
+            markdown_table(&rows, &Row::headers(false, exists));
             println!();
+            continue;
         }
-        (false, false) => {
-            println!();
-            let mut accum2 = vec![];
-            for x in entries {
-                accum2.push([x[0].clone(), x[1].clone(), x[3].clone()]);
-            }
 
-            table(&accum2, ["ID", "Deleted at", "Original location"]);
-            println!();
+        println!();
+        println!("{} ({} entries)", trash.bold(), rows.len());
+        println!();
+        table(&rows, &Row::headers(false, exists));
+    }
+    if output != cli::ListOutput::Markdown {
+        println!();
+    }
+}
+
+/// Counts without keeping the entries around, for prompts that run this all the time
+fn list_count(trash: &UnifiedTrash, per_trash: bool) -> anyhow::Result<()> {
+    let trashes = trash.list_trashes();
+    let mut counts = vec![0usize; trashes.len()];
+    trash.for_each_entry(|entry| {
+        if let Some(i) = trashes.iter().position(|x| *x == entry.trash) {
+            counts[i] += 1;
         }
+        Ok(())
+    })?;
+
+    if per_trash {
+        for (trash, count) in trashes.iter().zip(counts) {
+            println!("{}\t{}", count, trash.trash_path.display());
+        }
+    } else {
+        println!("{}", counts.iter().sum::<usize>());
     }
 
     Ok(())
 }
+
+/// `offset` and `limit` apply to the order the entries are found in, since they aren't sorted.
+/// Returns the number of entries that were printed
+fn list_jsonl(
+    trash: &UnifiedTrash,
+    offset: usize,
+    limit: Option<usize>,
+    exists: bool,
+) -> anyhow::Result<usize> {
+    let end = limit.map_or(usize::MAX, |x| offset.saturating_add(x));
+    let mut stdout = io::stdout().lock();
+    let mut index = 0;
+    let mut listed = 0;
+    trash.for_each_entry(|entry| {
+        index += 1;
+        if !(offset..end).contains(&(index - 1)) {
+            return Ok(());
+        }
+        listed += 1;
+
+        let mut line = serde_json::json!({
+            "id": entry_id(&entry),
+            "deleted_at": entry.deleted_at,
+            "trash": entry.trash.trash_path.to_string_lossy(),
+            "trash_filename": entry.trash_filename.to_string_lossy(),
+            "original_path": entry.original_filepath.to_string_lossy(),
+        });
+        if exists {
+            line["original_exists"] = fs::symlink_metadata(&entry.original_filepath)
+                .is_ok()
+                .into();
+        }
+        writeln!(stdout, "{}", line)?;
+        Ok(())
+    })?;
+    Ok(listed)
+}
@@ -1,9 +1,17 @@
-use crate::{table::table, trashing::UnifiedTrash};
+use std::io::{stdout, IsTerminal};
+
+use crate::{
+    cli::TableOutput,
+    table::{markdown_table, table, HtmlPage},
+    trashing::UnifiedTrash,
+};
 
 pub fn list_trashes(args: crate::cli::ListTrashesArgs, trash: UnifiedTrash) -> anyhow::Result<()> {
     let trashes = trash.list_trashes();
 
-    if args.simple {
+    // like ls, pipes get what is easy to parse
+    let piped = !args.pretty && args.output == TableOutput::Table && !stdout().is_terminal();
+    if args.simple || piped {
         for trash in trashes {
             println!(
                 "{}\t{}\t{}",
@@ -24,7 +32,16 @@ pub fn list_trashes(args: crate::cli::ListTrashesArgs, trash: UnifiedTrash) -> a
             })
             .collect::<Vec<_>>();
 
-        table(&trashes_table, ["Path", "Relative root", "Device ID"]);
+        let headers = ["Path", "Relative root", "Device ID"];
+        match args.output {
+            TableOutput::Table => table(&trashes_table, &headers),
+            TableOutput::Markdown => markdown_table(&trashes_table, &headers),
+            TableOutput::Html => {
+                let mut page = HtmlPage::new("Trashes", trash.now());
+                page.table(&trashes_table, &headers);
+                page.print();
+            }
+        }
     }
 
     Ok(())
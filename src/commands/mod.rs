@@ -1,3 +1,4 @@
+use anyhow::Context;
 use colored::Colorize;
 use sha2::Digest;
 use sha2::Sha256;
@@ -5,20 +6,279 @@ use std::fmt::Write;
 use std::io::stdin;
 use std::io::stdout;
 use std::io::BufRead;
+use std::io::IsTerminal;
+use std::io::Read;
 use std::io::Write as _;
+use std::os::unix::ffi::OsStrExt;
+use std::path::Path;
+use std::process::exit;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::OnceLock;
 
+use rustc_hash::FxHashSet;
+use serde::Deserialize;
+
+use crate::trashing::{TrashError, Trashinfo, UnifiedTrash};
+
+pub mod batch;
+pub mod cat;
+pub mod complete;
+pub mod diff;
+pub mod doctor;
 pub mod empty;
+pub mod export;
+pub mod generate_man;
+pub mod generate_shim;
+pub mod import;
+pub mod import_trash_cli;
+pub mod index;
+pub mod info;
+pub mod journal;
 pub mod list;
 pub mod list_trashes;
+pub mod open;
 pub mod orphaned;
 pub mod put;
 pub mod remove;
+pub mod repair;
 pub mod restore;
+pub mod search;
+pub mod snapshot;
+pub mod stats;
+pub mod undo;
+pub mod verify;
+pub mod version;
+pub mod versions;
+pub mod watch;
+
+static ASSUME_YES: AtomicBool = AtomicBool::new(false);
+static NON_INTERACTIVE: OnceLock<NonInteractive> = OnceLock::new();
+
+/// What questions do when stdin isn't a terminal (cron, pipelines, ...)
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum NonInteractive {
+    /// Exit with an error that tells to pass --yes
+    #[default]
+    Fail,
+
+    /// Take the default answer of yes/no questions, other questions still fail
+    Default,
+
+    /// Read the answers from stdin anyway, like `yes | trash-cli ...`
+    Read,
+}
+
+pub fn set_non_interactive(mode: NonInteractive) {
+    let _ = NON_INTERACTIVE.set(mode);
+}
+
+/// The [`NonInteractive`] mode that applies right now, `None` if stdin is a terminal
+fn non_interactive() -> Option<NonInteractive> {
+    match stdin().is_terminal() {
+        true => None,
+        false => Some(NON_INTERACTIVE.get().copied().unwrap_or_default()),
+    }
+}
+
+/// Makes every yes/no question answer itself with yes
+pub fn set_assume_yes() {
+    ASSUME_YES.store(true, Ordering::Relaxed);
+}
+
+pub fn assume_yes() -> bool {
+    ASSUME_YES.load(Ordering::Relaxed)
+}
+
+/// Exits if questions must not be asked, for questions that have no yes
+pub fn ensure_interactive(question: &str) {
+    if assume_yes() {
+        log::error!("{} (can't ask because of --yes)", question);
+        exit(1);
+    }
+}
+
+/// Exit code for when nothing matched or there is nothing to list,
+/// so that scripts can tell an empty result from something that broke
+pub const EXIT_NO_MATCH: i32 = 3;
+
+/// Exit code for when some files were handled and others failed (`put --force`),
+/// so that scripts know the failures are not all there is to clean up
+pub const EXIT_PARTIAL_FAILURE: i32 = 4;
+
+/// Exits with [`EXIT_NO_MATCH`] if `result` failed because no entry matched `id_or_path`
+pub fn exit_on_no_match<T>(
+    result: Result<T, TrashError>,
+    id_or_path: &str,
+) -> Result<T, TrashError> {
+    // other errors that come down to something missing are wrapped in context
+    if let Err(TrashError::NotFound(_)) = result {
+        log::error!("No files match {}", id_or_path);
+        exit(EXIT_NO_MATCH);
+    }
+    result
+}
+
+/// Number of hex digits in an ID
+const ID_LEN: usize = 10;
 
 pub fn id_from_bytes(input: &[u8]) -> String {
     let hash = Sha256::digest(input);
     let hash = hash.as_slice();
-    encode_hex(hash).chars().take(10).collect()
+    encode_hex(hash).chars().take(ID_LEN).collect()
+}
+
+/// The ID shown for an entry.
+///
+/// The same path can be trashed many times, so the location inside the trash is part of it too.
+/// That location never changes while the entry exists, so neither does the ID.
+pub fn entry_id(info: &Trashinfo) -> String {
+    let mut input = info.original_filepath.as_os_str().as_bytes().to_vec();
+    input.push(0);
+    input.extend_from_slice(info.trash.files_dir().as_os_str().as_bytes());
+    input.push(b'/');
+    input.extend_from_slice(info.trash_filename.as_bytes());
+    id_from_bytes(&input)
+}
+
+/// Finds the entry with the ID (or the start of it) or original path `id_or_path`.
+/// If that path was trashed several times, the most recent one is used.
+pub fn find_entry(trash: &UnifiedTrash, id_or_path: &str) -> anyhow::Result<Trashinfo> {
+    let id = expand_id(trash, id_or_path)?;
+    trash
+        .list()?
+        .into_iter()
+        .filter(|x| entry_id(x) == id || x.original_filepath == Path::new(id_or_path))
+        .max_by_key(|x| x.deleted_at)
+        .with_context(|| format!("No files match {}", id_or_path))
+}
+
+/// Whether `input` could be the start of an ID
+fn is_id_prefix(input: &str) -> bool {
+    !input.is_empty() && input.len() <= ID_LEN && input.bytes().all(|x| x.is_ascii_hexdigit())
+}
+
+/// Expands `id_or_path` to the full ID if it is the start of exactly one ID (like git does
+/// for commits), errors if it is the start of several. Anything else is returned unchanged.
+pub fn expand_id(trash: &UnifiedTrash, id_or_path: &str) -> anyhow::Result<String> {
+    let prefix = id_or_path.to_lowercase();
+    if prefix.len() >= ID_LEN || !is_id_prefix(&prefix) {
+        return Ok(id_or_path.to_owned());
+    }
+
+    let mut ids = trash
+        .list()?
+        .iter()
+        .map(entry_id)
+        .filter(|x| x.starts_with(&prefix))
+        .collect::<Vec<_>>();
+    ids.sort();
+    ids.dedup();
+
+    match ids.len() {
+        0 => Ok(id_or_path.to_owned()),
+        1 => Ok(ids.remove(0)),
+        _ => anyhow::bail!(
+            "{} is ambiguous, it is the start of the IDs {}",
+            id_or_path,
+            ids.join(", ")
+        ),
+    }
+}
+
+/// Reads the IDs or paths for `--stdin`, one per line or separated by NUL with `null`.
+/// Of lines from `list --simple`, only the ID is used
+pub fn read_stdin_inputs(null: bool) -> anyhow::Result<Vec<String>> {
+    let mut raw = vec![];
+    stdin()
+        .lock()
+        .read_to_end(&mut raw)
+        .context("Failed to read stdin")?;
+
+    let separator = if null { 0 } else { b'\n' };
+    raw.split(|x| *x == separator)
+        .filter(|x| !x.is_empty())
+        .map(|x| {
+            let input = String::from_utf8(x.to_vec()).context("Input is not valid UTF-8")?;
+            Ok(match input.split_once('\t') {
+                Some((id, _)) if id.len() == ID_LEN && is_id_prefix(id) => id.to_owned(),
+                _ => input,
+            })
+        })
+        .collect()
+}
+
+/// The entries of the trash with their IDs, to look up many inputs in a single listing
+pub struct Listing {
+    entries: Vec<(String, Trashinfo)>,
+}
+
+impl Listing {
+    pub fn new(trash: &UnifiedTrash) -> anyhow::Result<Self> {
+        let entries = trash
+            .list()
+            .context("Failed to list trash")?
+            .into_iter()
+            .map(|x| (entry_id(&x), x))
+            .collect();
+        Ok(Self { entries })
+    }
+
+    /// The ID and entry `input` (an ID, the start of one or an original path) refers to.
+    /// If it matches several, the most recent one with `latest` and an error otherwise
+    pub fn find(&self, input: &str, latest: bool) -> anyhow::Result<&(String, Trashinfo)> {
+        let prefix = input.to_lowercase();
+        let prefix = is_id_prefix(&prefix).then_some(prefix);
+        let matching = self
+            .entries
+            .iter()
+            .filter(|(id, info)| {
+                prefix.as_ref().is_some_and(|x| id.starts_with(x))
+                    || info.original_filepath == Path::new(input)
+            })
+            .collect::<Vec<_>>();
+
+        match matching.len() {
+            0 => anyhow::bail!("No files match {}", input),
+            1 => Ok(matching[0]),
+            _ if latest => Ok(matching
+                .into_iter()
+                .max_by_key(|(_, x)| x.deleted_at)
+                .expect("matching is not empty")),
+            n => anyhow::bail!("{} matches {} files, pass an ID to pick one", input, n),
+        }
+    }
+}
+
+/// Looks up each of the `inputs` (IDs, starts of IDs or original paths) in a single listing
+/// of the trash and hands the entry to `f`. Inputs that match no entry, or several without
+/// `latest`, are reported and skipped, just like the ones `f` fails for.
+/// Returns the number of inputs that failed.
+pub fn for_each_input(
+    trash: &UnifiedTrash,
+    inputs: &[String],
+    latest: bool,
+    mut f: impl FnMut(&Trashinfo) -> anyhow::Result<()>,
+) -> anyhow::Result<usize> {
+    let listing = Listing::new(trash)?;
+
+    // an entry is gone once it was handled, the same one twice would fail
+    let mut done = FxHashSet::default();
+    let mut failed = 0;
+    for input in inputs {
+        let result = listing
+            .find(input, latest)
+            .and_then(|(id, info)| match done.insert(id) {
+                true => f(info),
+                false => Ok(()),
+            });
+        if let Err(e) = result {
+            log::error!("{:#}", e);
+            failed += 1;
+        }
+    }
+
+    Ok(failed)
 }
 
 pub fn encode_hex(bytes: &[u8]) -> String {
@@ -30,6 +290,17 @@ pub fn encode_hex(bytes: &[u8]) -> String {
 }
 
 pub fn ask(prompt: &str) -> String {
+    if matches!(
+        non_interactive(),
+        Some(NonInteractive::Fail | NonInteractive::Default)
+    ) {
+        log::error!(
+            "{} (can't ask, stdin is not a terminal)",
+            prompt.trim_end().trim_end_matches(':')
+        );
+        exit(1);
+    }
+
     print!("{}", prompt);
     stdout().flush().expect("Failed to flush stdout");
     stdin()
@@ -41,6 +312,31 @@ pub fn ask(prompt: &str) -> String {
 }
 
 pub fn ask_yes_no(prompt: &str, default: bool) -> bool {
+    if assume_yes() {
+        println!("{} {}", prompt, "yes".green());
+        return true;
+    }
+
+    match non_interactive() {
+        Some(NonInteractive::Fail) => {
+            log::error!(
+                "{} (can't ask, stdin is not a terminal. Pass --yes to answer yes, \
+                or set prompts.non_interactive in the config)",
+                prompt
+            );
+            exit(1);
+        }
+        Some(NonInteractive::Default) => {
+            let answer = match default {
+                true => "yes".green(),
+                false => "no".bright_red(),
+            };
+            println!("{} {} (stdin is not a terminal)", prompt, answer);
+            return default;
+        }
+        Some(NonInteractive::Read) | None => {}
+    }
+
     let p = ask(&format!(
         "{} [{}] ",
         prompt,
@@ -50,9 +346,34 @@ pub fn ask_yes_no(prompt: &str, default: bool) -> bool {
         }
     ));
 
-    match (p.to_lowercase().as_str(), default) {
-        ("n", true) => true,
-        ("y", false) => true,
-        _ => false,
-    }
+    matches!(
+        (p.to_lowercase().as_str(), default),
+        ("n", true) | ("y", false)
+    )
+}
+
+#[test]
+fn test_entry_id_unique() {
+    use crate::trashing::{OriginalMetadata, Trash};
+    use std::{path::PathBuf, sync::Arc};
+
+    let trash = Arc::new(Trash {
+        is_home_trash: true,
+        is_admin_trash: false,
+        dev_root: PathBuf::from("/home/user/.local/share"),
+        trash_path: PathBuf::from("/home/user/.local/share/Trash"),
+        device: 0,
+    });
+    let info = |name: &str| Trashinfo {
+        trash: Arc::clone(&trash),
+        trash_filename: name.into(),
+        trash_filename_trashinfo: format!("{}.trashinfo", name).into(),
+        deleted_at: chrono::NaiveDateTime::default(),
+        original_filepath: PathBuf::from("/home/user/file.txt"),
+        metadata: OriginalMetadata::default(),
+    };
+
+    assert_ne!(entry_id(&info("file.txt")), entry_id(&info("file1.txt")));
+    assert_eq!(entry_id(&info("file.txt")), entry_id(&info("file.txt")));
+    assert_eq!(entry_id(&info("file.txt")).len(), ID_LEN);
 }
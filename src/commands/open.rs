@@ -0,0 +1,97 @@
+use std::{
+    env, fs, io,
+    os::unix::fs::PermissionsExt,
+    path::{Path, PathBuf},
+    process::Command,
+};
+
+use anyhow::Context;
+
+use crate::trashing::{copy_all, remove_all, UnifiedTrash};
+
+use super::{entry_id, find_entry};
+
+pub fn open(args: crate::cli::OpenArgs, trash: UnifiedTrash) -> anyhow::Result<()> {
+    let info = find_entry(&trash, &args.id_or_path)?;
+    let trashed_path = info.trash.files_dir().join(&info.trash_filename);
+
+    // a copy, so nothing can accidentally change the trashed file.
+    // It keeps the original name, applications are picked based on it
+    let dir = env::temp_dir().join(format!("trash-cli-open-{}", entry_id(&info)));
+    if fs::symlink_metadata(&dir).is_ok() {
+        make_writable(&dir).context("Failed to clean up previous copy")?;
+        remove_all(&dir).context("Failed to clean up previous copy")?;
+    }
+    fs::create_dir_all(&dir).context(format!("Failed to create {}", dir.display()))?;
+
+    let name = info
+        .original_filepath
+        .file_name()
+        .unwrap_or(&info.trash_filename);
+    let copy = dir.join(name);
+    if info.metadata.compressed || info.metadata.encrypted {
+        let mut contents = trash.open_contents(&info)?;
+        io::copy(&mut contents, &mut fs::File::create(&copy)?)
+            .context(format!("Failed to copy {}", trashed_path.display()))?;
+    } else {
+        copy_all(&trashed_path, &copy)
+            .context(format!("Failed to copy {}", trashed_path.display()))?;
+    }
+    make_read_only(&copy).context("Failed to make the copy read-only")?;
+
+    let status = if args.editor {
+        let editor = env::var("VISUAL")
+            .or_else(|_| env::var("EDITOR"))
+            .context("Neither $VISUAL nor $EDITOR is set")?;
+        // the editor can contain arguments (`code --wait`)
+        let mut editor = editor.split_whitespace();
+        let program = editor.next().context("$EDITOR is empty")?;
+        Command::new(program).args(editor).arg(&copy).status()
+    } else {
+        Command::new("xdg-open").arg(&copy).status()
+    }
+    .context("Failed to start the application")?;
+
+    anyhow::ensure!(status.success(), "The application failed ({})", status);
+
+    // xdg-open returns right away while the file is still open, so the copy
+    // is left for the system to clean up with the rest of the temp dir
+    if args.editor {
+        make_writable(&dir)?;
+        remove_all(&dir).context(format!("Failed to remove {}", dir.display()))?;
+    } else {
+        println!("Opened a copy at {}", copy.display());
+    }
+
+    Ok(())
+}
+
+fn make_read_only(path: &Path) -> io::Result<()> {
+    set_mode_recursive(path, 0o444, 0o555)
+}
+
+fn make_writable(path: &Path) -> io::Result<()> {
+    set_mode_recursive(path, 0o644, 0o755)
+}
+
+/// Directories are changed after their contents, so they stay accessible while walking
+fn set_mode_recursive(path: &Path, file_mode: u32, dir_mode: u32) -> io::Result<()> {
+    let meta = fs::symlink_metadata(path)?;
+    if meta.is_symlink() {
+        return Ok(());
+    }
+
+    if meta.is_dir() {
+        // needs to be readable and writable for the walk itself
+        fs::set_permissions(path, fs::Permissions::from_mode(0o755))?;
+        let children = fs::read_dir(path)?
+            .map(|x| x.map(|x| x.path()))
+            .collect::<io::Result<Vec<PathBuf>>>()?;
+        for child in children {
+            set_mode_recursive(&child, file_mode, dir_mode)?;
+        }
+        fs::set_permissions(path, fs::Permissions::from_mode(dir_mode))
+    } else {
+        fs::set_permissions(path, fs::Permissions::from_mode(file_mode))
+    }
+}
@@ -1,23 +1,324 @@
-use anyhow::Context;
+use std::{
+    cell::RefCell,
+    path::{Path, PathBuf},
+    process::exit,
+    sync::Arc,
+};
+
 use format as f;
 use log::error;
 
-use crate::{cli, trashing::UnifiedTrash};
+use crate::{
+    cli,
+    commands::{ask_yes_no, EXIT_PARTIAL_FAILURE},
+    size::format_size,
+    trashing::{
+        is_removable, portal, OpenFiles, QuotaUsage, Trash, TrashError, Trashinfo, UnifiedTrash,
+    },
+};
 
 pub fn put(args: cli::PutArgs, trash: UnifiedTrash) -> anyhow::Result<()> {
-    for file in args.files {
-        if args.force {
-            if let Err(err) = trash.put(&file, args.follow_symlinks) {
-                error!("Failed to trash {}: {}", file.display(), err);
-            }
+    // inside a sandbox, only the portal can reach the trash of the host.
+    // A trash dir given by the user is used directly though
+    let sandboxed = portal::is_sandboxed() && args.trash_dir.is_none();
+    if sandboxed {
+        log::info!("Running in a sandbox, trashing through xdg-desktop-portal");
+        anyhow::ensure!(
+            args.name.is_none(),
+            "--as is not supported inside a sandbox, the portal picks the name"
+        );
+        anyhow::ensure!(
+            !args.compress,
+            "--compress is not supported inside a sandbox, the portal moves the files"
+        );
+        anyhow::ensure!(
+            !args.encrypt,
+            "--encrypt is not supported inside a sandbox, the portal moves the files"
+        );
+    }
+
+    let trash = match &args.trash_dir {
+        Some(dir) => trash.with_trash_dir(dir)?,
+        None if args.home_only => trash.with_home_only(),
+        None => trash,
+    };
+    let trash = match args.compress {
+        true => trash.with_compression(true),
+        false => trash,
+    };
+    let trash = match args.encrypt {
+        true => trash.with_encryption(true),
+        false => trash,
+    };
+    let trash = match args.checksum {
+        true => trash.with_checksums(true),
+        false => trash,
+    };
+    let trash = match args.name.clone() {
+        Some(name) => trash.with_put_name(name)?,
+        None => trash,
+    };
+
+    let mut report = Report {
+        verbose: args.verbose,
+        explicit_trash_dir: args.trash_dir.is_some(),
+        trash: &trash,
+        created: vec![],
+        used: vec![],
+        summary: PutSummary::default(),
+    };
+
+    // only scanned once, processes opening files while we trash is nothing we could catch anyway
+    let open_files = args.check_open.then(OpenFiles::scan);
+    let in_use = |file: &Path| match &open_files {
+        Some(open) => !confirm_in_use(open, file),
+        None => false,
+    };
+
+    // people share thumb drives and don't expect to find a trash on them
+    let decisions: RefCell<Vec<(PathBuf, bool)>> = RefCell::new(vec![]);
+    let refused_new_trash = |file: &Path| {
+        if sandboxed {
+            return false;
+        }
+        let Some(new_trash) = trash.new_trash_for(file, args.follow_symlinks) else {
+            return false;
+        };
+        if let Some((_, approved)) = decisions.borrow().iter().find(|(x, _)| *x == new_trash) {
+            return !approved;
+        }
+        if !is_removable(new_trash.parent().unwrap_or(&new_trash)) {
+            return false;
+        }
+
+        let approved = ask_yes_no(
+            &f!(
+                "{} is on removable media without a trash, create {}?",
+                file.display(),
+                new_trash.display()
+            ),
+            false,
+        );
+        decisions.borrow_mut().push((new_trash, approved));
+        !approved
+    };
+
+    if args.atomic {
+        anyhow::ensure!(
+            !sandboxed,
+            "--atomic is not supported inside a sandbox, the portal can't undo trashing"
+        );
+
+        if let Some(file) = args.files.iter().find(|x| in_use(x)) {
+            anyhow::bail!("Not trashing anything, {} is in use", file.display());
+        }
+        if let Some(file) = args.files.iter().find(|x| refused_new_trash(x)) {
+            anyhow::bail!(
+                "Not trashing anything, {} would need a new trash",
+                file.display()
+            );
+        }
+
+        let trashed = trash.put_many(&args.files, args.follow_symlinks, confirm_exceed_quota)?;
+        for (file, info) in args.files.iter().zip(&trashed) {
+            report.trashed(file, Some(info));
+        }
+
+        if args.files.len() > 1 {
+            report.summary.print();
+        }
+        trash.warn_on_usage(&report.used);
+
+        return Ok(());
+    }
+
+    let put = |file: &Path| {
+        if sandboxed {
+            portal::trash_file(file, args.follow_symlinks).map(|_| None)
         } else {
             trash
-                .put(&file, args.follow_symlinks)
-                .context(f!("Failed to trash {}", file.display()))?;
+                .put(file, args.follow_symlinks, confirm_exceed_quota)
+                .map(Some)
+        }
+    };
+
+    for file in &args.files {
+        if in_use(file) || refused_new_trash(file) {
+            println!("Skipped {}", file.display());
+            report.summary.skipped += 1;
+            continue;
+        }
+
+        match put(file) {
+            Ok(info) => report.trashed(file, info.as_ref()),
+            // the user refused to exceed the quota
+            Err(err) if matches!(err.root(), TrashError::Aborted) => {
+                println!("Skipped {}", file.display());
+                report.summary.skipped += 1;
+            }
+            Err(err) if args.force => {
+                error!("Failed to trash {}: {:#}", file.display(), err);
+                report.summary.failed.push(file.clone());
+            }
+            Err(err) => {
+                return Err(
+                    anyhow::Error::from(err).context(f!("Failed to trash {}", file.display()))
+                )
+            }
+        }
+    }
+
+    if args.files.len() > 1 {
+        report.summary.print();
+    }
+    trash.warn_on_usage(&report.used);
+
+    let failed = &report.summary.failed;
+    if failed.is_empty() {
+        return Ok(());
+    }
+    let failed_list = failed
+        .iter()
+        .map(|x| x.display().to_string())
+        .collect::<Vec<_>>();
+    error!(
+        "Failed to trash {} of {} files: {}",
+        failed.len(),
+        args.files.len(),
+        failed_list.join(", ")
+    );
+    match failed.len() == args.files.len() {
+        true => exit(1),
+        false => exit(EXIT_PARTIAL_FAILURE),
+    }
+}
+
+/// Prints a line for every trashed file and collects the summary
+struct Report<'a> {
+    verbose: bool,
+    explicit_trash_dir: bool,
+    trash: &'a UnifiedTrash,
+
+    /// Trashes that didn't exist before this run
+    created: Vec<PathBuf>,
+
+    /// Trashes files were put into, portal puts aside
+    used: Vec<Arc<Trash>>,
+    summary: PutSummary,
+}
+
+impl Report<'_> {
+    /// `info` is `None` if the file went through the portal
+    fn trashed(&mut self, file: &Path, info: Option<&Trashinfo>) {
+        let destination = match info {
+            Some(v) => v.trash.trash_path.clone(),
+            None => PathBuf::from("xdg-desktop-portal"),
+        };
+
+        match info {
+            Some(info) if self.verbose => println!(
+                "Trashed {} into {} ({}) as {}",
+                file.display(),
+                destination.display(),
+                self.trash_kind(&info.trash),
+                info.trash_filename.to_string_lossy()
+            ),
+            _ => println!("Trashed {}", file.display()),
+        }
+
+        if let Some(info) = info {
+            if !self.used.contains(&info.trash) {
+                self.used.push(Arc::clone(&info.trash));
+            }
         }
 
-        println!("Trashed {}", file.display());
+        self.summary.trashed += 1;
+        if !self.summary.destinations.contains(&destination) {
+            self.summary.destinations.push(destination);
+        }
     }
 
-    Ok(())
+    fn trash_kind(&mut self, target: &Trash) -> &'static str {
+        if self.explicit_trash_dir {
+            "--trash-dir"
+        } else if target.is_admin_trash {
+            ".Trash/$uid"
+        } else if target.is_home_trash {
+            "home trash"
+        } else if self.trash.list_trashes().iter().any(|x| **x == *target)
+            || self.created.contains(&target.trash_path)
+        {
+            ".Trash-$uid"
+        } else {
+            self.created.push(target.trash_path.clone());
+            ".Trash-$uid, newly created"
+        }
+    }
+}
+
+#[derive(Debug, Default)]
+struct PutSummary {
+    trashed: usize,
+    skipped: usize,
+
+    /// Only with --force, the first failure stops everything otherwise
+    failed: Vec<PathBuf>,
+
+    /// The trashes files were put into, in the order they were first used
+    destinations: Vec<PathBuf>,
+}
+
+impl PutSummary {
+    fn print(&self) {
+        let mut line = f!("Trashed {} files", self.trashed);
+        if !self.destinations.is_empty() {
+            let destinations = self
+                .destinations
+                .iter()
+                .map(|x| x.display().to_string())
+                .collect::<Vec<_>>();
+            line.push_str(&f!(" into {}", destinations.join(", ")));
+        }
+        if self.skipped > 0 {
+            line.push_str(&f!(", skipped {}", self.skipped));
+        }
+        if !self.failed.is_empty() {
+            line.push_str(&f!(", {} failed", self.failed.len()));
+        }
+        println!("{}", line);
+    }
+}
+
+/// Whether `file` should be trashed even though processes have it open, asks if there are any
+fn confirm_in_use(open: &OpenFiles, file: &Path) -> bool {
+    let users = open.users_of(file);
+    if users.is_empty() {
+        return true;
+    }
+
+    let users = users
+        .iter()
+        .map(|x| f!("{} ({})", x.name, x.pid))
+        .collect::<Vec<_>>();
+    ask_yes_no(
+        &f!(
+            "{} is in use by {}. Trash anyway?",
+            file.display(),
+            users.join(", ")
+        ),
+        false,
+    )
+}
+
+fn confirm_exceed_quota(trash: &Trash, usage: QuotaUsage) -> bool {
+    ask_yes_no(
+        &f!(
+            "{} uses {} of its {} quota, trashing {} more would exceed it. Trash anyway?",
+            trash.trash_path.display(),
+            format_size(usage.used),
+            format_size(usage.limit),
+            format_size(usage.incoming)
+        ),
+        false,
+    )
 }
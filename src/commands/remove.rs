@@ -1,51 +1,109 @@
-use crate::{commands::ask, table::table, trashing::UnifiedTrash};
+use crate::{
+    commands::{ask, ensure_interactive},
+    dates::format_date,
+    table::table,
+    trashing::{Trashinfo, UnifiedTrash},
+};
 use anyhow::Context;
 use log::error;
-use std::{os::unix::ffi::OsStrExt, path::PathBuf, process::exit};
+use std::{path::Path, process::exit};
 
-use super::id_from_bytes;
+use super::{entry_id, exit_on_no_match, expand_id, for_each_input, read_stdin_inputs};
 
 pub fn remove(args: crate::cli::RemoveArgs, trash: UnifiedTrash) -> anyhow::Result<()> {
-    let removed = trash
-        .remove(
-            |trash| {
-                let hash = id_from_bytes(trash.original_filepath.as_os_str().as_bytes());
-
-                hash == args.id_or_path
-                    || PathBuf::from(&args.id_or_path) == trash.original_filepath
-            },
-            |matched| {
-                println!("Multiple files match {}:\n", args.id_or_path);
-
-                let mut collector = vec![];
-                for (i, info) in matched.iter().enumerate() {
-                    collector.push([
-                        i.to_string(),
-                        args.id_or_path.to_string(),
-                        info.deleted_at.to_string(),
-                    ]);
-                }
-                table(&collector, ["Index", "File", "Deleted At"]);
-                println!();
-
-                let res: usize = ask(&format!("Choose one [{:?}]: ", 0..matched.len() - 1))
-                    .parse()
-                    .unwrap_or_else(|e| {
-                        error!("Invalid number: {}", e);
-                        exit(1);
-                    });
-
-                if let Some(t) = matched.get(res) {
-                    t
-                } else {
-                    error!("Index {} does not exist", res);
+    if args.stdin {
+        return remove_stdin(&args, &trash);
+    }
+
+    let id_or_path = args
+        .id_or_path
+        .clone()
+        .expect("clap requires it without --stdin");
+    let id = expand_id(&trash, &id_or_path)?;
+    let removed = trash.remove(
+        |trash| {
+            let hash = entry_id(trash);
+
+            hash == id || trash.original_filepath == Path::new(&id_or_path)
+        },
+        |matched| {
+            ensure_interactive(&format!("Multiple files match {}", id_or_path));
+            println!("Multiple files match {}:\n", id_or_path);
+
+            let mut collector = vec![];
+            for (i, info) in matched.iter().enumerate() {
+                collector.push([
+                    i.to_string(),
+                    id_or_path.to_string(),
+                    format_date(info.deleted_at, trash.now()),
+                ]);
+            }
+            table(&collector, &["Index", "File", "Deleted At"]);
+            println!();
+
+            let res: usize = ask(&format!("Choose one [{:?}]: ", 0..matched.len() - 1))
+                .parse()
+                .unwrap_or_else(|e| {
+                    error!("Invalid number: {}", e);
                     exit(1);
-                }
-            },
-        )
-        .context("Failed to remove file")?;
+                });
+
+            if let Some(t) = matched.get(res) {
+                t
+            } else {
+                error!("Index {} does not exist", res);
+                exit(1);
+            }
+        },
+        args.shred,
+        args.dry_run,
+    );
+    let removed = exit_on_no_match(removed, &id_or_path).context("Failed to remove file")?;
 
-    println!("Removed {}", removed.display());
+    print_removed(&removed, args.dry_run);
+    Ok(())
+}
+
+/// Removes every ID or path read from stdin
+fn remove_stdin(args: &crate::cli::RemoveArgs, trash: &UnifiedTrash) -> anyhow::Result<()> {
+    let inputs = read_stdin_inputs(args.null)?;
+    let failed = for_each_input(trash, &inputs, false, |info| {
+        trash
+            .remove_entry(info, args.shred, args.dry_run)
+            .context(format!(
+                "Failed to remove {}",
+                info.original_filepath.display()
+            ))?;
+        print_removed(info, args.dry_run);
+        Ok(())
+    })?;
 
+    anyhow::ensure!(
+        failed == 0,
+        "Failed to remove {} of {} files",
+        failed,
+        inputs.len()
+    );
     Ok(())
 }
+
+fn print_removed(removed: &Trashinfo, dry_run: bool) {
+    if dry_run {
+        println!(
+            "Would remove {}:\n  {}\n  {}",
+            removed.original_filepath.display(),
+            removed
+                .trash
+                .files_dir()
+                .join(&removed.trash_filename)
+                .display(),
+            removed
+                .trash
+                .info_dir()
+                .join(&removed.trash_filename_trashinfo)
+                .display()
+        );
+    } else {
+        println!("Removed {}", removed.original_filepath.display());
+    }
+}
@@ -0,0 +1,114 @@
+use std::path::PathBuf;
+
+use anyhow::Context;
+use colored::Colorize;
+
+use crate::{
+    commands::{ask, assume_yes},
+    trashing::{self, Repair, UnifiedTrash},
+};
+
+pub fn repair(args: crate::cli::RepairArgs, trash: UnifiedTrash) -> anyhow::Result<()> {
+    let corrupt = trashing::find_corrupt(&trash).context("Failed to look for corrupt files")?;
+
+    let mut repaired = 0;
+    for entry in &corrupt {
+        println!(
+            "{} {} ({})",
+            "Corrupt".red(),
+            entry.info_path.display(),
+            entry.error
+        );
+
+        let deleted_at = entry.deleted_at.or(entry.changed_at);
+        let action = if args.auto || assume_yes() {
+            match (&entry.original_filepath, deleted_at) {
+                (Some(path), Some(deleted_at)) => Some(Repair::Rewrite {
+                    original_filepath: path.clone(),
+                    deleted_at,
+                }),
+                _ => Some(Repair::Recover),
+            }
+        } else {
+            choose_repair(entry.original_filepath.clone(), deleted_at)
+        };
+
+        let Some(action) = action else {
+            println!("  skipped");
+            continue;
+        };
+
+        match &action {
+            Repair::Rewrite {
+                original_filepath,
+                deleted_at,
+            } => println!(
+                "  rewrite with Path={} DeletionDate={}",
+                original_filepath.display(),
+                deleted_at
+            ),
+            Repair::Recover => println!(
+                "  move to {}",
+                entry.trash.trash_path.join("recovered").display()
+            ),
+        }
+
+        if !args.dry_run {
+            trashing::repair(entry, action)
+                .context(format!("Failed to repair {}", entry.info_path.display()))?;
+        }
+        repaired += 1;
+    }
+
+    println!(
+        "{} of {} corrupt trashinfo files {}",
+        repaired,
+        corrupt.len(),
+        if args.dry_run {
+            "would be repaired"
+        } else {
+            "were repaired"
+        }
+    );
+
+    Ok(())
+}
+
+/// Asks the user what to do, filling in missing values along the way
+fn choose_repair(
+    original_filepath: Option<PathBuf>,
+    deleted_at: Option<chrono::NaiveDateTime>,
+) -> Option<Repair> {
+    loop {
+        let answer = ask("  [r]ewrite, move to re[c]overed or [s]kip? ");
+        match answer.trim() {
+            "r" => {}
+            "c" => return Some(Repair::Recover),
+            "s" | "" => return None,
+            _ => continue,
+        }
+
+        let original_filepath = match &original_filepath {
+            Some(v) => v.clone(),
+            None => {
+                let path = ask("  Original location of the file (absolute path): ");
+                let path = PathBuf::from(path.trim());
+                if !path.is_absolute() {
+                    println!("  {} is not an absolute path", path.display());
+                    continue;
+                }
+                path
+            }
+        };
+
+        let Some(deleted_at) = deleted_at else {
+            println!("  No deletion date available, the entry can only be recovered");
+            continue;
+        };
+
+        return Some(Repair::Rewrite {
+            original_filepath,
+            deleted_at,
+        });
+    }
+}
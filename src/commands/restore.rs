@@ -1,65 +1,136 @@
-use std::{os::unix::ffi::OsStrExt, path::PathBuf, process::exit};
+use std::{cell::Cell, path::Path, process::exit};
 
 use anyhow::Context;
 use log::error;
 
 use crate::{
-    commands::{ask, ask_yes_no, id_from_bytes},
+    commands::{
+        ask, ask_yes_no, assume_yes, ensure_interactive, entry_id, exit_on_no_match, expand_id,
+        for_each_input, read_stdin_inputs,
+    },
+    dates::format_date,
+    elevate::{is_permission_denied, retry_as_root},
     table::table,
+    trashing::TrashError,
 };
 
 pub fn restore(args: crate::cli::RestoreArgs, trash: crate::UnifiedTrash) -> anyhow::Result<()> {
-    let restored = trash
-        .restore(
-            |info| {
-                let hash = id_from_bytes(info.original_filepath.as_os_str().as_bytes());
+    if args.stdin {
+        return restore_stdin(&args, &trash);
+    }
 
-                hash == args.id_or_path || PathBuf::from(&args.id_or_path) == info.original_filepath
-            },
-            |matched| {
-                println!("Multiple files match {}:\n", args.id_or_path);
+    let id_or_path = args
+        .id_or_path
+        .clone()
+        .expect("clap requires it without --stdin");
+    let id = expand_id(&trash, &id_or_path)?;
 
-                let mut collector = vec![];
-                for (i, info) in matched.iter().enumerate() {
-                    collector.push([
-                        i.to_string(),
-                        args.id_or_path.to_string(),
-                        info.deleted_at.to_string(),
-                    ]);
-                }
-                table(&collector, ["Index", "File", "Deleted At"]);
-                println!();
-                let res: usize = ask(&format!("Choose one [{:?}]: ", 0..matched.len() - 1))
-                    .parse()
-                    .unwrap_or_else(|e| {
-                        error!("Invalid number: {}", e);
-                        exit(1);
-                    });
+    // what was picked, to repeat exactly that restore as root if it isn't allowed
+    let chosen = Cell::new(None);
+    let overwrite = Cell::new(false);
+    let restored = trash.restore(
+        |info| {
+            let hash = entry_id(info);
 
-                if let Some(t) = matched.get(res) {
-                    t
-                } else {
-                    error!("Index {} does not exist", res);
+            hash == id || info.original_filepath == Path::new(&id_or_path)
+        },
+        |matched| {
+            if args.latest {
+                // matched is never empty
+                return matched.iter().max_by_key(|x| x.deleted_at).unwrap();
+            }
+
+            ensure_interactive(&format!("Multiple files match {}", id_or_path));
+            println!("Multiple files match {}:\n", id_or_path);
+
+            let mut collector = vec![];
+            for (i, info) in matched.iter().enumerate() {
+                collector.push([
+                    i.to_string(),
+                    id_or_path.to_string(),
+                    format_date(info.deleted_at, trash.now()),
+                ]);
+            }
+            table(&collector, &["Index", "File", "Deleted At"]);
+            println!();
+            let res: usize = ask(&format!("Choose one [{:?}]: ", 0..matched.len() - 1))
+                .parse()
+                .unwrap_or_else(|e| {
+                    error!("Invalid number: {}", e);
                     exit(1);
-                }
-            },
-            |info| {
-                if !ask_yes_no(
-                    &format!(
-                        "A file already exists at '{}', do you want to overwrite it?",
-                        info.original_filepath.display()
-                    ),
-                    false,
-                ) {
-                    error!("Aborted by user");
-                    exit(0);
-                }
-                true
-            },
-        )
-        .context("Failed to restore form trash")?;
+                });
+
+            if let Some(t) = matched.get(res) {
+                chosen.set(Some(entry_id(t)));
+                t
+            } else {
+                error!("Index {} does not exist", res);
+                exit(1);
+            }
+        },
+        |info| {
+            if !ask_yes_no(
+                &format!(
+                    "A file already exists at '{}', do you want to overwrite it?",
+                    info.original_filepath.display()
+                ),
+                false,
+            ) {
+                error!("Aborted by user");
+                exit(0);
+            }
+            overwrite.set(true);
+            true
+        },
+    );
+    let restored = match restored {
+        Err(e) if is_permission_denied(&e) => {
+            let mut helper_args = vec![];
+            if overwrite.get() {
+                helper_args.push("--yes".to_owned());
+            }
+            helper_args.push("restore".to_owned());
+            helper_args.push(chosen.take().unwrap_or(id_or_path));
+            return retry_as_root(e, "Failed to restore form trash", &helper_args);
+        }
+        other => other,
+    };
+    let restored =
+        exit_on_no_match(restored, &id_or_path).context("Failed to restore form trash")?;
 
     println!("Restored {}", restored.display());
 
     Ok(())
 }
+
+/// Restores every ID or path read from stdin, a file that is in the way is only overwritten with --yes
+fn restore_stdin(
+    args: &crate::cli::RestoreArgs,
+    trash: &crate::UnifiedTrash,
+) -> anyhow::Result<()> {
+    let inputs = read_stdin_inputs(args.null)?;
+    let failed = for_each_input(trash, &inputs, args.latest, |info| {
+        match trash.restore_entry(info, |_| assume_yes()) {
+            Ok(restored) => println!("Restored {}", restored.display()),
+            Err(TrashError::Aborted) => anyhow::bail!(
+                "A file already exists at '{}', pass --yes to overwrite it",
+                info.original_filepath.display()
+            ),
+            Err(e) => {
+                return Err(anyhow::Error::from(e).context(format!(
+                    "Failed to restore {}",
+                    info.original_filepath.display()
+                )))
+            }
+        }
+        Ok(())
+    })?;
+
+    anyhow::ensure!(
+        failed == 0,
+        "Failed to restore {} of {} files",
+        failed,
+        inputs.len()
+    );
+    Ok(())
+}
@@ -0,0 +1,44 @@
+use anyhow::Context;
+
+use crate::{
+    commands::entry_id,
+    dates::format_date,
+    table::table,
+    trashing::{ContentIndex, UnifiedTrash},
+};
+
+pub fn search(args: crate::cli::SearchArgs, trash: UnifiedTrash) -> anyhow::Result<()> {
+    let index = ContentIndex::load()
+        .context("Failed to load content index")?
+        .context("No content index found, run `trash index` first")?;
+
+    let trashed = trash.list().context("Failed to list trash")?;
+
+    let entries = index
+        .search(&trashed, &args.content)
+        .into_iter()
+        .map(|(info, line)| {
+            [
+                entry_id(info),
+                format_date(info.deleted_at, trash.now()),
+                info.original_filepath.display().to_string(),
+                line,
+            ]
+        })
+        .collect::<Vec<_>>();
+
+    if args.simple {
+        for row in entries {
+            println!("{}\t{}\t{}\t{}", row[0], row[1], row[2], row[3]);
+        }
+    } else {
+        println!();
+        table(
+            &entries,
+            &["ID", "Deleted at", "Original location", "Matching line"],
+        );
+        println!();
+    }
+
+    Ok(())
+}
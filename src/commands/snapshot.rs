@@ -0,0 +1,18 @@
+use anyhow::Context;
+
+use crate::trashing::{self, UnifiedTrash};
+
+pub fn snapshot(args: crate::cli::SnapshotArgs, trash: UnifiedTrash) -> anyhow::Result<()> {
+    let created = trashing::create_snapshot(&trash, &args.dir).context(format!(
+        "Failed to create snapshot in {}",
+        args.dir.display()
+    ))?;
+
+    println!(
+        "Copied {} trashed files into {}, remove it with `chmod -R u+w` and `rm -rf` once you are done",
+        created,
+        args.dir.display()
+    );
+
+    Ok(())
+}
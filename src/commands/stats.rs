@@ -0,0 +1,187 @@
+use anyhow::Context;
+use chrono::NaiveDateTime;
+
+use crate::{
+    cli::TableOutput,
+    commands::entry_id,
+    dates::{format_date, format_exact},
+    size::format_size,
+    table::{markdown_table, table, HtmlPage},
+    trashing::{path_size, Trashinfo, UnifiedTrash},
+};
+
+/// The widest bar of the age histogram, in characters
+const BAR_WIDTH: usize = 30;
+
+pub fn stats(args: crate::cli::StatsArgs, trash: UnifiedTrash) -> anyhow::Result<()> {
+    let now = trash.now();
+    let mut buckets = [
+        Bucket::new("Today"),
+        Bucket::new("This week"),
+        Bucket::new("This month"),
+        Bucket::new("Older"),
+    ];
+    let mut sizes: Vec<(u64, Trashinfo)> = vec![];
+
+    trash
+        .for_each_entry(|entry| {
+            // orphaned trashinfo files simply don't take up any space
+            let size = path_size(&entry.trash.files_dir().join(&entry.trash_filename)).unwrap_or(0);
+            let bucket = &mut buckets[age_bucket(entry.deleted_at, now)];
+            bucket.entries += 1;
+            bucket.size += size;
+            if args.largest.is_some() {
+                sizes.push((size, entry));
+            }
+            Ok(())
+        })
+        .context("Failed to list trash")?;
+
+    sizes.sort_by_key(|x| std::cmp::Reverse(x.0));
+    sizes.truncate(args.largest.unwrap_or(0));
+
+    let entries = buckets.iter().map(|x| x.entries).sum::<usize>();
+    let size = buckets.iter().map(|x| x.size).sum::<u64>();
+
+    if args.simple && args.largest.is_some() {
+        for (size, info) in &sizes {
+            println!(
+                "{}\t{}\t{}",
+                entry_id(info),
+                size,
+                info.original_filepath.display()
+            );
+        }
+        return Ok(());
+    }
+
+    if args.simple {
+        for bucket in &buckets {
+            println!("{}\t{}\t{}", bucket.name, bucket.entries, bucket.size);
+        }
+        return Ok(());
+    }
+
+    let summary = format!("{} entries, {}", entries, format_size(size));
+    let largest = buckets.iter().map(|x| x.size).max().unwrap_or(0);
+    let rows = buckets
+        .iter()
+        .map(|x| {
+            [
+                x.name.to_owned(),
+                x.entries.to_string(),
+                format_size(x.size),
+                bar(x.size, largest),
+            ]
+        })
+        .collect::<Vec<_>>();
+    let bucket_headers = ["Deleted", "Entries", "Size", ""];
+    let largest_rows = sizes
+        .iter()
+        .map(|(size, info)| {
+            [
+                entry_id(info),
+                format_size(*size),
+                // reports are read later, so they keep the exact date
+                match args.output == TableOutput::Html {
+                    true => format_exact(info.deleted_at),
+                    false => format_date(info.deleted_at, now),
+                },
+                info.original_filepath.display().to_string(),
+            ]
+        })
+        .collect::<Vec<_>>();
+    let largest_headers = ["ID", "Size", "Deleted at", "Original location"];
+
+    if args.output == TableOutput::Html {
+        let mut page = HtmlPage::new("Trash statistics", now);
+        page.paragraph(&summary);
+        page.table(&rows, &bucket_headers);
+        if args.largest.is_some() {
+            page.heading("Largest entries");
+            page.table(&largest_rows, &largest_headers);
+        }
+        page.print();
+        return Ok(());
+    }
+
+    let markdown = args.output == TableOutput::Markdown;
+    let print_table = |rows: &[[String; 4]], headers: &[&str]| match markdown {
+        true => markdown_table(rows, headers),
+        false => table(rows, headers),
+    };
+
+    if !markdown {
+        println!();
+    }
+    println!("{}", summary);
+    println!();
+
+    print_table(&rows, &bucket_headers);
+    println!();
+
+    if args.largest.is_some() {
+        match markdown {
+            true => println!("### Largest entries"),
+            false => println!("Largest entries:"),
+        }
+        println!();
+        print_table(&largest_rows, &largest_headers);
+        println!();
+    }
+
+    Ok(())
+}
+
+struct Bucket {
+    name: &'static str,
+    entries: usize,
+    size: u64,
+}
+
+impl Bucket {
+    fn new(name: &'static str) -> Self {
+        Self {
+            name,
+            entries: 0,
+            size: 0,
+        }
+    }
+}
+
+/// Today, within the last 7 days, within the last 30 days or older
+fn age_bucket(deleted_at: NaiveDateTime, now: NaiveDateTime) -> usize {
+    let age = now - deleted_at;
+    if deleted_at.date() == now.date() {
+        0
+    } else if age < chrono::Duration::days(7) {
+        1
+    } else if age < chrono::Duration::days(30) {
+        2
+    } else {
+        3
+    }
+}
+
+/// `value` as a bar relative to `max`, anything non-zero gets at least one character
+fn bar(value: u64, max: u64) -> String {
+    if value == 0 {
+        return String::new();
+    }
+    let width = (value as u128 * BAR_WIDTH as u128 / max as u128) as usize;
+    "█".repeat(width.max(1))
+}
+
+#[test]
+fn test_age_bucket() {
+    let now = chrono::NaiveDate::from_ymd_opt(2024, 1, 22)
+        .unwrap()
+        .and_hms_opt(12, 0, 0)
+        .unwrap();
+
+    assert_eq!(age_bucket(now - chrono::Duration::hours(12), now), 0);
+    assert_eq!(age_bucket(now - chrono::Duration::hours(13), now), 1);
+    assert_eq!(age_bucket(now - chrono::Duration::days(29), now), 2);
+    assert_eq!(age_bucket(now - chrono::Duration::days(30), now), 3);
+    assert_eq!(bar(1, 1 << 40), "█");
+}
@@ -0,0 +1,69 @@
+use std::path::Path;
+
+use anyhow::Context;
+
+use crate::{
+    cli::UndoArgs,
+    trashing::{self, Action, History, Operation, UnifiedTrash, Via},
+};
+
+pub fn undo(args: UndoArgs, trash: UnifiedTrash) -> anyhow::Result<()> {
+    step(args, trash, Via::Undo)
+}
+
+pub fn redo(args: UndoArgs, trash: UnifiedTrash) -> anyhow::Result<()> {
+    step(args, trash, Via::Redo)
+}
+
+fn step(args: UndoArgs, mut trash: UnifiedTrash, via: Via) -> anyhow::Result<()> {
+    let journal = trash.journal().context("No journal is configured")?.clone();
+    let name = match via {
+        Via::Undo => "undo",
+        Via::Redo => "redo",
+    };
+    let stack = |history: History| match via {
+        Via::Undo => history.done,
+        Via::Redo => history.undone,
+    };
+
+    if args.dry_run {
+        let ops = stack(History::from_journal(journal.read()?));
+        anyhow::ensure!(!ops.is_empty(), "Nothing to {}", name);
+        for op in ops.iter().rev().take(args.steps) {
+            for entry in op.entries.iter().rev() {
+                let verb = match entry.action {
+                    Action::Put => "Would restore",
+                    _ => "Would trash",
+                };
+                println!("{} {}", verb, Path::new(&entry.original_path).display());
+            }
+        }
+        return Ok(());
+    }
+
+    for i in 0..args.steps {
+        // every step is recorded, so the history is read again each time
+        let ops = stack(History::from_journal(journal.read()?));
+        let Some(op) = ops.last() else {
+            anyhow::ensure!(i > 0, "Nothing to {}", name);
+            println!("Nothing more to {}", name);
+            break;
+        };
+
+        trash = trash.with_journal(journal.next_op(Some(via)));
+        revert(&trash, op).context(format!("Failed to {}", name))?;
+    }
+
+    Ok(())
+}
+
+fn revert(trash: &UnifiedTrash, op: &Operation) -> anyhow::Result<()> {
+    let (restored, trashed) = trashing::revert(trash, op)?;
+    for path in restored {
+        println!("Restored {}", path.display());
+    }
+    for path in trashed {
+        println!("Trashed {}", path.display());
+    }
+    Ok(())
+}
@@ -0,0 +1,32 @@
+use std::process::exit;
+
+use anyhow::Context;
+use colored::Colorize;
+
+use crate::trashing::{self, UnifiedTrash};
+
+pub fn verify(_args: crate::cli::VerifyArgs, trash: UnifiedTrash) -> anyhow::Result<()> {
+    let verification = trashing::verify(&trash).context("Failed to verify trash")?;
+
+    for problem in &verification.problems {
+        println!(
+            "{} {} {}",
+            "Error".red(),
+            problem.path.display(),
+            problem.kind
+        );
+    }
+
+    println!(
+        "Checked {} trashinfo files in {} trashes, found {} problems",
+        verification.checked,
+        trash.list_trashes().len(),
+        verification.problems.len()
+    );
+
+    if !verification.problems.is_empty() {
+        exit(1);
+    }
+
+    Ok(())
+}
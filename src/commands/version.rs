@@ -0,0 +1,42 @@
+use crate::{cli, trashing::UnifiedTrash};
+
+/// What this build can do beyond the basics, so wrappers don't have to guess from the version.
+/// There is no FUSE mount (`mount` creates a snapshot), it is listed so that checking for it works
+const CAPABILITIES: &[(&str, bool)] = &[
+    ("compression", true),
+    ("encryption", true),
+    ("dbus", true),
+    ("portal", true),
+    ("fuse", false),
+    ("selinux", cfg!(feature = "selinux")),
+    ("async", cfg!(feature = "async")),
+];
+
+pub fn version(args: cli::VersionArgs, _trash: UnifiedTrash) -> anyhow::Result<()> {
+    let version = env!("CARGO_PKG_VERSION");
+
+    if args.json {
+        let capabilities = CAPABILITIES
+            .iter()
+            .map(|(name, enabled)| (name.to_string(), serde_json::Value::from(*enabled)))
+            .collect::<serde_json::Map<_, _>>();
+        println!(
+            "{}",
+            serde_json::json!({
+                "version": version,
+                "capabilities": capabilities,
+            })
+        );
+        return Ok(());
+    }
+
+    let enabled = CAPABILITIES
+        .iter()
+        .filter(|(_, enabled)| *enabled)
+        .map(|(name, _)| *name)
+        .collect::<Vec<_>>();
+    println!("trash-cli {}", version);
+    println!("Capabilities: {}", enabled.join(", "));
+
+    Ok(())
+}
@@ -0,0 +1,58 @@
+use anyhow::Context;
+
+use crate::{
+    dates::{format_date, format_exact},
+    size::format_size,
+    table::table,
+    trashing::{lexical_absolute, path_size, UnifiedTrash},
+};
+
+use super::entry_id;
+
+pub fn versions(args: crate::cli::VersionsArgs, trash: UnifiedTrash) -> anyhow::Result<()> {
+    let path = lexical_absolute(&args.path).context("Failed to get current dir")?;
+
+    let mut versions = trash
+        .list()
+        .context("Failed to list trash")?
+        .into_iter()
+        .filter(|x| x.original_filepath == path)
+        .collect::<Vec<_>>();
+    versions.sort_by_key(|x| x.deleted_at);
+
+    anyhow::ensure!(
+        !versions.is_empty(),
+        "{} is not in the trash",
+        path.display()
+    );
+
+    let now = trash.now();
+    let rows = versions
+        .iter()
+        .enumerate()
+        .map(|(i, info)| {
+            let size = path_size(&info.trash.files_dir().join(&info.trash_filename));
+            [
+                format!("v{}", i + 1),
+                entry_id(info),
+                match args.simple {
+                    true => format_exact(info.deleted_at),
+                    false => format_date(info.deleted_at, now),
+                },
+                size.map(format_size).unwrap_or_default(),
+            ]
+        })
+        .collect::<Vec<_>>();
+
+    if args.simple {
+        for row in rows {
+            println!("{}", row.join("\t"));
+        }
+    } else {
+        println!();
+        table(&rows, &["Version", "ID", "Deleted at", "Size"]);
+        println!();
+    }
+
+    Ok(())
+}
@@ -0,0 +1,76 @@
+use anyhow::Context;
+use colored::Colorize;
+
+use crate::trashing::{self, UnifiedTrash, WatchEvent};
+
+use super::entry_id;
+
+pub fn watch(args: crate::cli::WatchArgs, mut trash: UnifiedTrash) -> anyhow::Result<()> {
+    if !args.simple {
+        println!(
+            "Watching {} trashes, press Ctrl+C to stop",
+            trash.list_trashes().len()
+        );
+    }
+
+    trashing::watch(&mut trash, |event| {
+        let time = chrono::Local::now().format("%H:%M:%S").to_string();
+        // removed entries that were never seen only have their name in the trash
+        let (added, trash_path, id, path) = match &event {
+            WatchEvent::Added(info) => (
+                true,
+                &info.trash.trash_path,
+                entry_id(info),
+                info.original_filepath.display().to_string(),
+            ),
+            WatchEvent::Removed {
+                trash,
+                info: Some(info),
+                ..
+            } => (
+                false,
+                &trash.trash_path,
+                entry_id(info),
+                info.original_filepath.display().to_string(),
+            ),
+            WatchEvent::Removed {
+                trash,
+                trash_filename,
+                info: None,
+            } => (
+                false,
+                &trash.trash_path,
+                "?".to_owned(),
+                trash_filename.to_string_lossy().into_owned(),
+            ),
+        };
+
+        if args.simple {
+            let event = if added { "added" } else { "removed" };
+            println!(
+                "{}\t{}\t{}\t{}\t{}",
+                event,
+                time,
+                id,
+                path,
+                trash_path.display()
+            );
+        } else {
+            let event = if added {
+                "+ added  ".green()
+            } else {
+                "- removed".red()
+            };
+            println!(
+                "{} {} {} {} {}",
+                time.dimmed(),
+                event,
+                id.bold(),
+                path,
+                format!("({})", trash_path.display()).dimmed()
+            );
+        }
+        Ok(())
+    })
+    .context("Failed to watch trash")
+}
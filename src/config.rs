@@ -0,0 +1,296 @@
+use std::{env, fs, path::PathBuf};
+
+use anyhow::Context;
+use rustc_hash::FxHashMap;
+use serde::Deserialize;
+
+use crate::{
+    commands::NonInteractive,
+    dates::parse_age,
+    size::parse_size,
+    trashing::{
+        DeviceExclusions, ExclusionFallback, KeySource, Quota, QuotaPolicy, Retention, UsageWarning,
+    },
+};
+
+/// The user configuration, read from `$XDG_CONFIG_HOME/trash-cli/config.toml`.
+///
+/// Every setting is optional, a missing file is the same as an empty one.
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Config {
+    pub quota: Option<QuotaConfig>,
+    pub usage_warning: Option<UsageWarningConfig>,
+    pub retention: Option<RetentionConfig>,
+    pub device_exclusions: Option<DeviceExclusionsConfig>,
+    pub display: DisplayConfig,
+    pub put: PutConfig,
+    pub encryption: EncryptionConfig,
+    pub prompts: PromptsConfig,
+}
+
+/// ```toml
+/// [display]
+/// relative_dates = true
+/// date_format = "%d.%m.%Y %H:%M"
+/// block_size = "si"
+/// ```
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct DisplayConfig {
+    pub relative_dates: bool,
+    pub date_format: Option<String>,
+    pub block_size: Option<String>,
+}
+
+/// ```toml
+/// [put]
+/// compress = true
+/// encrypt = true
+/// check_open = true
+/// checksum = true
+/// ```
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct PutConfig {
+    pub compress: bool,
+    pub encrypt: bool,
+    pub check_open: bool,
+    pub checksum: bool,
+}
+
+/// ```toml
+/// [prompts]
+/// non_interactive = "default" # or "fail" (default) or "read"
+/// ```
+///
+/// What questions do when stdin isn't a terminal
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct PromptsConfig {
+    pub non_interactive: NonInteractive,
+}
+
+/// ```toml
+/// [encryption]
+/// key_file = "/home/user/.config/trash-cli/key"
+/// ```
+///
+/// Without a key file, the key is looked up in the keyring
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct EncryptionConfig {
+    pub key_file: Option<PathBuf>,
+}
+
+/// ```toml
+/// [quota]
+/// max_size = "10GiB"
+/// policy = "evict" # or "refuse" (default) or "prompt"
+///
+/// [quota.mounts]
+/// "/mnt/usb" = "1GiB"
+/// ```
+#[derive(Debug, Clone, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct QuotaConfig {
+    pub max_size: ByteSize,
+
+    #[serde(default)]
+    pub policy: QuotaPolicy,
+
+    #[serde(default)]
+    pub mounts: FxHashMap<PathBuf, ByteSize>,
+}
+
+/// ```toml
+/// [usage_warning]
+/// max_percent = 10
+/// max_size = "20GiB"
+/// ```
+///
+/// Warns after putting files when a trash exceeds either of them
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct UsageWarningConfig {
+    pub max_percent: Option<f64>,
+    pub max_size: Option<ByteSize>,
+}
+
+/// ```toml
+/// [retention]
+/// max_age = "30d"
+/// max_size = "5GiB"
+/// ```
+///
+/// Applied by `empty --policy`
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct RetentionConfig {
+    pub max_age: Option<Age>,
+    pub max_size: Option<ByteSize>,
+}
+
+/// ```toml
+/// [device_exclusions]
+/// mounts = ["/run/media/*/*"]
+/// uuids = ["1234-ABCD"]
+/// fallback = "skip" # or "home" (default)
+/// ```
+///
+/// Devices on which put never creates a trash
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct DeviceExclusionsConfig {
+    pub mounts: Vec<Glob>,
+    pub uuids: Vec<String>,
+    pub fallback: ExclusionFallback,
+}
+
+/// A glob like `"/run/media/*/*"`
+#[derive(Debug, Clone, Deserialize)]
+#[serde(try_from = "String")]
+pub struct Glob(pub glob::Pattern);
+
+impl TryFrom<String> for Glob {
+    type Error = glob::PatternError;
+
+    fn try_from(value: String) -> Result<Self, Self::Error> {
+        glob::Pattern::new(&value).map(Self)
+    }
+}
+
+/// An age like `"30d"`, see [`parse_age`]
+#[derive(Debug, Clone, Copy, Deserialize)]
+#[serde(try_from = "String")]
+pub struct Age(pub chrono::Duration);
+
+impl TryFrom<String> for Age {
+    type Error = anyhow::Error;
+
+    fn try_from(value: String) -> Result<Self, Self::Error> {
+        parse_age(&value).map(Self)
+    }
+}
+
+/// A size that can be written either as a plain number of bytes or as a string like `"5GiB"`
+#[derive(Debug, Clone, Copy, Deserialize)]
+#[serde(try_from = "RawSize")]
+pub struct ByteSize(pub u64);
+
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum RawSize {
+    Bytes(u64),
+    Human(String),
+}
+
+impl TryFrom<RawSize> for ByteSize {
+    type Error = anyhow::Error;
+
+    fn try_from(value: RawSize) -> Result<Self, Self::Error> {
+        match value {
+            RawSize::Bytes(v) => Ok(Self(v)),
+            RawSize::Human(v) => parse_size(&v).map(Self),
+        }
+    }
+}
+
+impl Config {
+    pub fn location() -> anyhow::Result<PathBuf> {
+        let config_home = match env::var("XDG_CONFIG_HOME") {
+            Ok(v) => PathBuf::from(v),
+            Err(_) => PathBuf::from(env::var("HOME").context("No home dir set!")?).join(".config"),
+        };
+        Ok(config_home.join("trash-cli").join("config.toml"))
+    }
+
+    pub fn load() -> anyhow::Result<Self> {
+        let location = Self::location()?;
+        let raw = match fs::read_to_string(&location) {
+            Ok(v) => v,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(Self::default()),
+            Err(e) => {
+                return Err(e).context(format!("Failed to read {}", location.display()));
+            }
+        };
+
+        toml::from_str(&raw).context(format!("Invalid config file {}", location.display()))
+    }
+
+    pub fn key_source(&self) -> KeySource {
+        match &self.encryption.key_file {
+            Some(path) => KeySource::File(path.clone()),
+            None => KeySource::Keyring,
+        }
+    }
+
+    pub fn quota(&self) -> Option<Quota> {
+        self.quota.as_ref().map(|x| Quota {
+            max_size: x.max_size.0,
+            policy: x.policy,
+            mounts: x.mounts.iter().map(|(k, v)| (k.clone(), v.0)).collect(),
+        })
+    }
+
+    pub fn retention(&self) -> Option<Retention> {
+        self.retention.as_ref().map(|x| Retention {
+            max_age: x.max_age.map(|x| x.0),
+            max_size: x.max_size.map(|x| x.0),
+        })
+    }
+
+    pub fn device_exclusions(&self) -> Option<DeviceExclusions> {
+        self.device_exclusions.as_ref().map(|x| DeviceExclusions {
+            mounts: x.mounts.iter().map(|x| x.0.clone()).collect(),
+            uuids: x.uuids.clone(),
+            fallback: x.fallback,
+        })
+    }
+
+    pub fn usage_warning(&self) -> Option<UsageWarning> {
+        self.usage_warning.as_ref().map(|x| UsageWarning {
+            max_percent: x.max_percent,
+            max_size: x.max_size.map(|x| x.0),
+        })
+    }
+}
+
+#[test]
+fn test_parse_config() {
+    let config: Config = toml::from_str(
+        r#"
+        [quota]
+        max_size = "10GiB"
+        policy = "evict"
+
+        [quota.mounts]
+        "/mnt/usb" = 1024
+
+        [usage_warning]
+        max_percent = 12.5
+
+        [device_exclusions]
+        mounts = ["/run/media/*/*"]
+        uuids = ["1234-ABCD"]
+        fallback = "skip"
+        "#,
+    )
+    .unwrap();
+
+    let quota = config.quota().unwrap();
+    assert_eq!(quota.max_size, 10 * 1024 * 1024 * 1024);
+    assert_eq!(quota.policy, QuotaPolicy::Evict);
+    assert_eq!(quota.mounts[&PathBuf::from("/mnt/usb")], 1024);
+
+    let usage_warning = config.usage_warning().unwrap();
+    assert!(usage_warning.exceeded(13, 100));
+    assert!(!usage_warning.exceeded(12, 100));
+    assert!(!usage_warning.exceeded(u64::MAX, 0));
+
+    let exclusions = config.device_exclusions().unwrap();
+    assert_eq!(exclusions.fallback, ExclusionFallback::Skip);
+    assert!(exclusions.excludes(&PathBuf::from("/run/media/user/stick"), || None));
+    assert!(!exclusions.excludes(&PathBuf::from("/run/media/user"), || None));
+    assert!(exclusions.excludes(&PathBuf::from("/mnt/data"), || Some("1234-abcd".to_owned())));
+}
@@ -0,0 +1,124 @@
+use std::sync::{
+    atomic::{AtomicBool, Ordering},
+    OnceLock,
+};
+
+use chrono::{
+    format::{Item, StrftimeItems},
+    NaiveDateTime,
+};
+
+static RELATIVE_DATES: AtomicBool = AtomicBool::new(false);
+static DATE_FORMAT: OnceLock<String> = OnceLock::new();
+
+/// Makes `format_date` print dates like `2 days ago`
+pub fn set_relative_dates() {
+    RELATIVE_DATES.store(true, Ordering::Relaxed);
+}
+
+/// Makes `format_exact` use a strftime `format` (like `%d.%m.%Y %H:%M`), can only be set once
+pub fn set_date_format(format: String) -> anyhow::Result<()> {
+    anyhow::ensure!(
+        !StrftimeItems::new(&format).any(|x| x == Item::Error),
+        "Invalid date format: {}",
+        format
+    );
+    let _ = DATE_FORMAT.set(format);
+    Ok(())
+}
+
+/// Formats a deletion date for humans, machine readable output should use the date directly
+pub fn format_date(date: NaiveDateTime, now: NaiveDateTime) -> String {
+    if RELATIVE_DATES.load(Ordering::Relaxed) {
+        format_relative(date, now)
+    } else {
+        format_exact(date)
+    }
+}
+
+/// Formats a date using the format set with `set_date_format`, if any
+pub fn format_exact(date: NaiveDateTime) -> String {
+    match DATE_FORMAT.get() {
+        Some(format) => date.format(format).to_string(),
+        None => date.to_string(),
+    }
+}
+
+/// Formats the distance between `date` and `now` in the largest fitting unit, e.g. `3 hours ago`
+pub fn format_relative(date: NaiveDateTime, now: NaiveDateTime) -> String {
+    let seconds = (now - date).num_seconds();
+    if seconds.abs() < 60 {
+        return "just now".to_owned();
+    }
+
+    let (count, unit) = [
+        (365 * 24 * 60 * 60, "year"),
+        (30 * 24 * 60 * 60, "month"),
+        (7 * 24 * 60 * 60, "week"),
+        (24 * 60 * 60, "day"),
+        (60 * 60, "hour"),
+        (60, "minute"),
+    ]
+    .into_iter()
+    .find(|(len, _)| seconds.abs() >= *len)
+    .map(|(len, unit)| (seconds.abs() / len, unit))
+    .expect("minutes always fit");
+
+    let plural = if count == 1 { "" } else { "s" };
+    if seconds < 0 {
+        // the trash of another machine or a clock that was off
+        format!("in {} {}{}", count, unit, plural)
+    } else {
+        format!("{} {}{} ago", count, unit, plural)
+    }
+}
+
+/// Parses ages like `30d`, `12h` or `2w` (seconds, minutes, hours, days and weeks)
+pub fn parse_age(input: &str) -> anyhow::Result<chrono::Duration> {
+    let input = input.trim();
+    let split = input
+        .find(|x: char| !x.is_ascii_digit())
+        .unwrap_or(input.len());
+    let (number, unit) = input.split_at(split);
+
+    let number: i64 = number
+        .parse()
+        .map_err(|_| anyhow::anyhow!("Invalid age: {}", input))?;
+
+    let seconds = match unit.trim() {
+        "s" => 1,
+        "m" => 60,
+        "h" => 60 * 60,
+        "d" => 24 * 60 * 60,
+        "w" => 7 * 24 * 60 * 60,
+        "" => anyhow::bail!("Missing unit in age {} (s, m, h, d or w)", input),
+        other => anyhow::bail!("Unknown unit in age: {}", other),
+    };
+
+    number
+        .checked_mul(seconds)
+        .and_then(chrono::Duration::try_seconds)
+        .ok_or_else(|| anyhow::anyhow!("Age too large: {}", input))
+}
+
+#[test]
+fn test_parse_age() {
+    assert_eq!(parse_age("30d").unwrap(), chrono::Duration::days(30));
+    assert_eq!(parse_age("12 h").unwrap(), chrono::Duration::hours(12));
+    assert!(parse_age("30").is_err());
+    assert!(parse_age("5y").is_err());
+    assert!(parse_age("99999999999w").is_err());
+}
+
+#[test]
+fn test_format_relative() {
+    let now = "2024-01-24T16:27:00".parse().unwrap();
+    let at = |x: &str| format_relative(x.parse().unwrap(), now);
+
+    assert_eq!(at("2024-01-24T16:26:30"), "just now");
+    assert_eq!(at("2024-01-24T16:25:00"), "2 minutes ago");
+    assert_eq!(at("2024-01-24T15:27:00"), "1 hour ago");
+    assert_eq!(at("2024-01-22T10:00:00"), "2 days ago");
+    assert_eq!(at("2023-11-01T00:00:00"), "2 months ago");
+    assert_eq!(at("2024-01-25T16:27:00"), "in 1 day");
+}
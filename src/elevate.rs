@@ -0,0 +1,61 @@
+use std::{env, process::Command};
+
+use anyhow::Context;
+
+use crate::{
+    commands::{ask_yes_no, assume_yes},
+    trashing::TrashError,
+};
+
+/// The program that runs a command as root here: pkexec shows an authentication dialog
+/// in graphical sessions, sudo asks on the terminal. `None` if neither is installed.
+pub fn helper() -> Option<&'static str> {
+    let graphical = env::var_os("WAYLAND_DISPLAY").is_some() || env::var_os("DISPLAY").is_some();
+    let candidates = match graphical {
+        true => ["pkexec", "sudo"],
+        false => ["sudo", "pkexec"],
+    };
+    candidates.into_iter().find(|x| in_path(x))
+}
+
+fn in_path(program: &str) -> bool {
+    env::var_os("PATH")
+        .is_some_and(|path| env::split_paths(&path).any(|dir| dir.join(program).is_file()))
+}
+
+pub fn is_permission_denied(err: &TrashError) -> bool {
+    matches!(err.root(), TrashError::Io(e) if e.kind() == std::io::ErrorKind::PermissionDenied)
+}
+
+/// Offers to repeat an operation that failed with `err` for lack of permissions as root,
+/// by running the helper mode (`__helper`) of this program with `args` through [`helper`].
+/// Only that operation is privileged, on the trash of the invoking user.
+///
+/// Fails with `err` (with `context`) if that's not possible, or exits if the user declines.
+/// pkexec starts in the home of root, so `args` must not contain relative paths.
+pub fn retry_as_root(err: TrashError, context: &str, args: &[String]) -> anyhow::Result<()> {
+    let err = anyhow::Error::from(err).context(context.to_owned());
+    let Some(helper) = helper() else {
+        return Err(err);
+    };
+    // asking for a password can't be answered with --yes
+    if assume_yes() || rustix::process::geteuid().is_root() {
+        return Err(err);
+    }
+
+    log::error!("{:#}", err);
+    if !ask_yes_no(&format!("Retry as root with {}?", helper), false) {
+        std::process::exit(1);
+    }
+
+    let exe = env::current_exe().context("Failed to find the trash-cli binary")?;
+    log::debug!("Running {} {} __helper {:?}", helper, exe.display(), args);
+    let status = Command::new(helper)
+        .arg(exe)
+        .arg("__helper")
+        .args(args)
+        .status()
+        .context(format!("Failed to run {}", helper))?;
+    anyhow::ensure!(status.success(), "Retrying as root with {} failed", helper);
+    Ok(())
+}
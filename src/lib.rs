@@ -0,0 +1,9 @@
+//! The trash behind trash-cli, for applications that want to trash and restore files
+//! like the cli does: [`trashing::UnifiedTrash`], or `trashing::AsyncUnifiedTrash`
+//! with the `async` feature.
+
+pub mod size;
+pub mod trashing;
+
+#[cfg(test)]
+mod test;
@@ -1,17 +1,20 @@
 use anyhow::Context;
+use chrono::NaiveDateTime;
 use clap::Parser;
 use std::env;
 use std::path::PathBuf;
-use trashing::UnifiedTrash;
+use trash_cli::{size, trashing};
+use trashing::{FixedClock, UnifiedTrash};
 
 mod cli;
 mod commands;
+mod config;
+mod dates;
+mod elevate;
 mod microlog;
+mod notify;
 mod table;
-mod trashing;
-
-#[cfg(test)]
-mod test;
+mod template;
 
 /// Based on `The FreeDesktop.org Trash specification`:
 /// <https://specifications.freedesktop.org/trash-spec/trashspec-latest.html> at 2024-01-22
@@ -29,47 +32,199 @@ fn main() -> anyhow::Result<()> {
         .to_string_lossy()
         .to_string();
 
-    let trash = UnifiedTrash::new().context("Failed to establish a list of trash locations")?;
-
-    match bin_name.as_str() {
-        "trash" => {
-            let args = cli::PutArgs::parse();
-            commands::put::put(args, trash)?;
+    let mut now = None;
+    let mut date_format = None;
+    let mut block_size = None;
+    let mut user = None;
+    let mut simulate = false;
+    let mut subcommand = match bin_name.as_str() {
+        "trash" => cli::SubCmd::Put(cli::PutArgs::parse()),
+        "trash-put" => cli::SubCmd::Put(cli::PutArgs::parse()),
+        "trash-list" => cli::SubCmd::List(cli::ListArgs::parse()),
+        "trash-empty" => cli::SubCmd::Empty(cli::EmptyArgs::parse()),
+        "trash-restore" => cli::SubCmd::Restore(cli::RestoreArgs::parse()),
+        "trash-rm" => cli::SubCmd::Remove(cli::RemoveArgs::parse()),
+        _ => {
+            let root_args = cli::RootArgs::parse();
+            if root_args.debug_internal {
+                microlog::enable_debug_internal();
+            }
+            if root_args.yes {
+                commands::set_assume_yes();
+            }
+            if root_args.relative_dates {
+                dates::set_relative_dates();
+            }
+            date_format = root_args.date_format;
+            block_size = root_args.block_size;
+            simulate = root_args.simulate;
+            now = root_args.now;
+            user = root_args.uid.map(|uid| (uid, root_args.home));
+            if root_args.invoking_user {
+                let uid = trashing::invoking_uid()
+                    .context("--invoking-user needs sudo or a setuid binary")?;
+                user = Some((uid, None));
+            }
+            root_args.subcommand
         }
-        "trash-put" => {
-            let args = cli::PutArgs::parse();
-            commands::put::put(args, trash)?;
+    };
+
+    // the helper runs as root for someone else, only ever on their trash
+    if matches!(subcommand, cli::SubCmd::Helper(_)) {
+        anyhow::ensure!(
+            rustix::process::getuid().is_root(),
+            "__helper only runs through pkexec or sudo"
+        );
+        let uid = trashing::invoking_uid().context("__helper only runs through pkexec or sudo")?;
+        user = Some((uid, None));
+    }
+
+    // the env var makes this also usable through the trash-* aliases
+    let now = match now {
+        Some(v) => Some(v),
+        None => env::var("TRASH_NOW")
+            .ok()
+            .map(|x| x.parse::<NaiveDateTime>())
+            .transpose()
+            .context("Invalid datetime in TRASH_NOW")?,
+    };
+
+    if env::var("TRASH_ASSUME_YES").is_ok_and(|x| !x.is_empty() && x != "0") {
+        commands::set_assume_yes();
+    }
+
+    let config = config::Config::load().context("Failed to load config")?;
+    commands::set_non_interactive(config.prompts.non_interactive);
+    if config.display.relative_dates {
+        dates::set_relative_dates();
+    }
+    if let Some(format) = date_format.or(config.display.date_format.clone()) {
+        dates::set_date_format(format)?;
+    }
+    if let Some(spec) = block_size.or(config.display.block_size.clone()) {
+        size::set_block_size(&spec).context("Invalid --block-size")?;
+    }
+
+    // like --trash-dir of put, but for every command
+    let trash_dir = env::var_os("TRASH_DIR")
+        .filter(|x| !x.is_empty())
+        .map(PathBuf::from);
+
+    let trash = match (user, &trash_dir) {
+        (Some(_), Some(_)) => {
+            anyhow::bail!("TRASH_DIR can't be used together with --uid or --invoking-user")
         }
-        "trash-list" => {
-            let args = cli::ListArgs::parse();
-            commands::list::list(args, trash)?;
+        (None, Some(dir)) => UnifiedTrash::in_dir(dir),
+        (Some((uid, home)), None) => {
+            // files would end up in trashes and with names the user can't touch
+            anyhow::ensure!(
+                !matches!(subcommand, cli::SubCmd::Put(_)),
+                "--uid and --invoking-user can't be used to put files"
+            );
+            let home = match home {
+                Some(v) => v,
+                None => trashing::home_of(uid).context("Failed to find home dir")?,
+            };
+            UnifiedTrash::for_user(uid, home)
         }
-        "trash-empty" => {
-            let args = cli::EmptyArgs::parse();
-            commands::empty::empty(args, trash)?
+        (None, None) => {
+            // the trash of root is right for root, but easily surprises someone using sudo
+            if let Some(uid) = trashing::invoking_uid() {
+                let euid = rustix::process::geteuid().as_raw();
+                let mut msg = format!(
+                    "Started by uid {} but running as uid {}, using the trash of uid {}",
+                    uid, euid, euid
+                );
+                if !matches!(subcommand, cli::SubCmd::Put(_)) {
+                    msg += &format!(" (pass --invoking-user to use the trash of uid {})", uid);
+                }
+                log::warn!("{}", msg);
+            }
+            UnifiedTrash::new()
         }
-        "trash-restore" => {
-            let args = cli::RestoreArgs::parse();
-            commands::restore::restore(args, trash)?;
+    };
+    let trash = trash
+        .context("Failed to establish a list of trash locations")?
+        .with_quota(config.quota())
+        .with_usage_warning(config.usage_warning())
+        .with_retention(config.retention())
+        .with_device_exclusions(config.device_exclusions())
+        .with_compression(config.put.compress)
+        .with_encryption(config.put.encrypt)
+        .with_checksums(config.put.checksum)
+        .with_key_source(config.key_source());
+
+    // nothing changes, so there is nothing to record either
+    let trash = match simulate {
+        true => {
+            anyhow::ensure!(
+                matches!(
+                    subcommand,
+                    cli::SubCmd::Put(_)
+                        | cli::SubCmd::Restore(_)
+                        | cli::SubCmd::Remove(_)
+                        | cli::SubCmd::Empty(_)
+                        | cli::SubCmd::RemoveOrphaned(_)
+                ),
+                "--simulate only works with put, restore, remove, empty and remove-orphaned"
+            );
+            log::info!("Simulating, nothing is changed");
+            trash.with_simulation()
         }
-        "trash-rm" => {
-            let args = cli::RemoveArgs::parse();
-            commands::remove::remove(args, trash)?;
+        false => trash
+            .with_journal(trashing::Journal::open_default().context("Failed to find the journal")?),
+    };
+
+    let trash = match now {
+        Some(now) => trash.with_clock(FixedClock(now)),
+        None => trash,
+    };
+
+    if let cli::SubCmd::Put(args) = &mut subcommand {
+        args.check_open |= config.put.check_open;
+        if args.trash_dir.is_none() {
+            args.trash_dir = trash_dir;
         }
-        _ => {
-            let root_args = cli::RootArgs::parse();
-            match root_args.subcommand {
-                cli::SubCmd::Put(args) => commands::put::put(args, trash)?,
-                cli::SubCmd::List(args) => commands::list::list(args, trash)?,
-                cli::SubCmd::Empty(args) => commands::empty::empty(args, trash)?,
-                cli::SubCmd::RemoveOrphaned(args) => commands::orphaned::orphaned(args, trash)?,
-                cli::SubCmd::Restore(args) => commands::restore::restore(args, trash)?,
-                cli::SubCmd::Remove(args) => commands::remove::remove(args, trash)?,
-                cli::SubCmd::ListTrashes(args) => {
-                    commands::list_trashes::list_trashes(args, trash)?
-                }
-            }
+    }
+
+    match subcommand {
+        cli::SubCmd::Put(args) => commands::put::put(args, trash)?,
+        cli::SubCmd::List(args) => commands::list::list(args, trash)?,
+        cli::SubCmd::Empty(args) => commands::empty::empty(args, trash)?,
+        cli::SubCmd::RemoveOrphaned(args) => commands::orphaned::orphaned(args, trash)?,
+        cli::SubCmd::Restore(args) => commands::restore::restore(args, trash)?,
+        cli::SubCmd::Remove(args) => commands::remove::remove(args, trash)?,
+        cli::SubCmd::Batch(args) => commands::batch::batch(args, trash)?,
+        cli::SubCmd::Info(args) => commands::info::info(args, trash)?,
+        cli::SubCmd::Versions(args) => commands::versions::versions(args, trash)?,
+        cli::SubCmd::Diff(args) => commands::diff::diff(args, trash)?,
+        cli::SubCmd::Cat(args) => commands::cat::cat(args, trash)?,
+        cli::SubCmd::Open(args) => commands::open::open(args, trash)?,
+        cli::SubCmd::Log(args) => commands::journal::log(args, trash)?,
+        cli::SubCmd::Undo(args) => commands::undo::undo(args, trash)?,
+        cli::SubCmd::Redo(args) => commands::undo::redo(args, trash)?,
+        cli::SubCmd::ListTrashes(args) => commands::list_trashes::list_trashes(args, trash)?,
+        cli::SubCmd::Index(args) => commands::index::index(args, trash)?,
+        cli::SubCmd::Search(args) => commands::search::search(args, trash)?,
+        cli::SubCmd::Stats(args) => commands::stats::stats(args, trash)?,
+        cli::SubCmd::Export(args) => commands::export::export(args, trash)?,
+        cli::SubCmd::Doctor(args) => commands::doctor::doctor(args, trash)?,
+        cli::SubCmd::Verify(args) => commands::verify::verify(args, trash)?,
+        cli::SubCmd::Watch(args) => commands::watch::watch(args, trash)?,
+        cli::SubCmd::ImportTrashCli(args) => {
+            commands::import_trash_cli::import_trash_cli(args, trash)?
         }
+        cli::SubCmd::Repair(args) => commands::repair::repair(args, trash)?,
+        cli::SubCmd::Snapshot(args) => commands::snapshot::snapshot(args, trash)?,
+        cli::SubCmd::Import(args) => commands::import::import(args, trash)?,
+        cli::SubCmd::GenerateMan(args) => commands::generate_man::generate_man(args, trash)?,
+        cli::SubCmd::GenerateShim(args) => commands::generate_shim::generate_shim(args, trash)?,
+        cli::SubCmd::Version(args) => commands::version::version(args, trash)?,
+        cli::SubCmd::Complete(args) => commands::complete::complete(args, trash)?,
+        cli::SubCmd::Helper(args) => match args.op {
+            cli::HelperOp::Restore(args) => commands::restore::restore(args, trash)?,
+            cli::HelperOp::Empty(args) => commands::empty::empty(args, trash)?,
+        },
     };
 
     Ok(())
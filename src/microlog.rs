@@ -1,10 +1,16 @@
-use std::env;
+use std::{
+    env,
+    sync::atomic::{AtomicBool, Ordering},
+};
 
 use colored::Colorize;
 use log::{Level, LevelFilter};
 
 struct MicroLog {}
 
+/// Whether internal diagnostics (`--debug-internal`) are enabled
+static DEBUG_INTERNAL: AtomicBool = AtomicBool::new(false);
+
 pub fn init(mut level: LevelFilter) {
     static LOGGER: MicroLog = MicroLog {};
     let rust_log_var = env::var("RUST_LOG").unwrap_or("".to_owned());
@@ -17,6 +23,12 @@ pub fn init(mut level: LevelFilter) {
     log::set_max_level(level);
 }
 
+/// Logs everything, annotating each message with where it originated from
+pub fn enable_debug_internal() {
+    DEBUG_INTERNAL.store(true, Ordering::Relaxed);
+    log::set_max_level(LevelFilter::Trace);
+}
+
 impl log::Log for MicroLog {
     fn enabled(&self, _metadata: &log::Metadata) -> bool {
         true
@@ -31,7 +43,18 @@ impl log::Log for MicroLog {
                 Level::Debug => "Debug".blue(),
                 Level::Trace => "Trace".white(),
             };
-            eprintln!("{} {}", lvl, record.args());
+
+            if DEBUG_INTERNAL.load(Ordering::Relaxed) {
+                let location = format!(
+                    "[{} {}:{}]",
+                    record.target(),
+                    record.file().unwrap_or("?"),
+                    record.line().unwrap_or(0)
+                );
+                eprintln!("{} {} {}", lvl, location.bright_black(), record.args());
+            } else {
+                eprintln!("{} {}", lvl, record.args());
+            }
         }
     }
 
@@ -0,0 +1,31 @@
+use std::collections::HashMap;
+
+use anyhow::Context;
+use zbus::{blocking::Connection, zvariant::Value};
+
+/// Shows a desktop notification through `org.freedesktop.Notifications`
+/// (what `notify-send` uses), for runs where nobody looks at the terminal.
+pub fn notify(summary: &str, body: &str) -> anyhow::Result<()> {
+    let connection = Connection::session().context("Failed to connect to the session bus")?;
+    connection
+        .call_method(
+            Some("org.freedesktop.Notifications"),
+            "/org/freedesktop/Notifications",
+            Some("org.freedesktop.Notifications"),
+            "Notify",
+            &(
+                "trash-cli",
+                0u32,
+                "user-trash",
+                summary,
+                body,
+                Vec::<&str>::new(),
+                HashMap::<&str, Value>::new(),
+                // let the notification server decide
+                -1i32,
+            ),
+        )
+        .context("Failed to send notification")?;
+
+    Ok(())
+}
@@ -0,0 +1,143 @@
+use std::sync::OnceLock;
+
+const UNITS: [&str; 6] = ["B", "KiB", "MiB", "GiB", "TiB", "PiB"];
+const SI_UNITS: [&str; 6] = ["B", "kB", "MB", "GB", "TB", "PB"];
+
+static BLOCK_SIZE: OnceLock<BlockSize> = OnceLock::new();
+
+/// How `format_size` prints sizes
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum BlockSize {
+    /// Binary units like `4.2 GiB`, the default
+    Human,
+
+    /// Powers of 1000 like `4.5 GB`
+    Si,
+
+    /// The number of blocks of `size` bytes (rounded up like du does), followed by `suffix`
+    Blocks { size: u64, suffix: String },
+}
+
+/// Makes `format_size` use a du-style `--block-size`, can only be set once.
+///
+/// `human-readable` and `si` pick the units automatically, anything else is a size
+/// as `parse_size` understands it. Like du, a size without a number (`K`, `MB`)
+/// is also printed after every value.
+pub fn set_block_size(spec: &str) -> anyhow::Result<()> {
+    let _ = BLOCK_SIZE.set(parse_block_size(spec)?);
+    Ok(())
+}
+
+fn parse_block_size(spec: &str) -> anyhow::Result<BlockSize> {
+    let block_size = match spec {
+        "human-readable" => BlockSize::Human,
+        "si" => BlockSize::Si,
+        _ => {
+            let has_number = spec.starts_with(|x: char| x.is_ascii_digit());
+            let size = match has_number {
+                true => parse_size(spec)?,
+                false => parse_size(&format!("1{}", spec))?,
+            };
+            anyhow::ensure!(size > 0, "Invalid block size: {}", spec);
+            BlockSize::Blocks {
+                size,
+                suffix: match has_number {
+                    true => String::new(),
+                    false => spec.to_owned(),
+                },
+            }
+        }
+    };
+    Ok(block_size)
+}
+
+/// Formats a byte count using binary units (e.g. `4.2 GiB`), or as set with `set_block_size`
+pub fn format_size(bytes: u64) -> String {
+    format_with(bytes, BLOCK_SIZE.get().unwrap_or(&BlockSize::Human))
+}
+
+fn format_with(bytes: u64, block_size: &BlockSize) -> String {
+    let (base, units) = match block_size {
+        BlockSize::Human => (1024.0, UNITS),
+        BlockSize::Si => (1000.0, SI_UNITS),
+        BlockSize::Blocks { size, suffix } => {
+            return format!("{}{}", bytes.div_ceil(*size), suffix);
+        }
+    };
+
+    let mut size = bytes as f64;
+    let mut unit = 0;
+    while size >= base && unit + 1 < units.len() {
+        size /= base;
+        unit += 1;
+    }
+
+    if unit == 0 {
+        format!("{} {}", bytes, units[0])
+    } else {
+        format!("{:.1} {}", size, units[unit])
+    }
+}
+
+/// Parses sizes like `512`, `10K`, `5GiB` or `1.5 GB`.
+///
+/// Both the SI (`KB`, `MB`, ..) and the binary (`K`, `KiB`, `MiB`, ..) suffixes are understood,
+/// a suffix without the `B` is treated as binary (as `du` does).
+pub fn parse_size(input: &str) -> anyhow::Result<u64> {
+    let input = input.trim();
+    let split = input
+        .find(|x: char| !x.is_ascii_digit() && x != '.')
+        .unwrap_or(input.len());
+    let (number, suffix) = input.split_at(split);
+
+    let number: f64 = number
+        .parse()
+        .map_err(|_| anyhow::anyhow!("Invalid size: {}", input))?;
+
+    let multiplier: u64 = match suffix.trim().to_lowercase().as_str() {
+        "" | "b" => 1,
+        "k" | "kib" => 1 << 10,
+        "m" | "mib" => 1 << 20,
+        "g" | "gib" => 1 << 30,
+        "t" | "tib" => 1 << 40,
+        "p" | "pib" => 1 << 50,
+        "kb" => 1000,
+        "mb" => 1000u64.pow(2),
+        "gb" => 1000u64.pow(3),
+        "tb" => 1000u64.pow(4),
+        "pb" => 1000u64.pow(5),
+        other => anyhow::bail!("Unknown size suffix: {}", other),
+    };
+
+    Ok((number * multiplier as f64) as u64)
+}
+
+#[test]
+fn test_parse_size() {
+    assert_eq!(parse_size("512").unwrap(), 512);
+    assert_eq!(parse_size("10K").unwrap(), 10 * 1024);
+    assert_eq!(parse_size("5GiB").unwrap(), 5 * 1024 * 1024 * 1024);
+    assert_eq!(parse_size("1.5 MB").unwrap(), 1_500_000);
+    assert!(parse_size("GiB").is_err());
+    assert!(parse_size("5 parsecs").is_err());
+}
+
+#[test]
+fn test_format_size() {
+    assert_eq!(format_size(12), "12 B");
+    assert_eq!(format_size(1536), "1.5 KiB");
+    assert_eq!(format_size(4_509_715_661), "4.2 GiB");
+}
+
+#[test]
+fn test_block_size() {
+    let format = |bytes, spec| format_with(bytes, &parse_block_size(spec).unwrap());
+
+    assert_eq!(format(4_509_715_661, "si"), "4.5 GB");
+    assert_eq!(format(1025, "1K"), "2");
+    assert_eq!(format(1025, "K"), "2K");
+    assert_eq!(format(3_000_000, "MB"), "3MB");
+    assert_eq!(format(5, "1"), "5");
+    assert!(parse_block_size("0").is_err());
+    assert!(parse_block_size("parsecs").is_err());
+}
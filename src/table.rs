@@ -1,9 +1,14 @@
 // This whole thing is kinda messy, but it works :)
 
+use chrono::SubsecRound;
 use colored::{ColoredString, Colorize};
 
+use crate::dates::format_exact;
+
 /// Print a pretty table
-pub fn table<const COLS: usize>(data: &[[String; COLS]], headers: [&str; COLS]) {
+pub fn table<R: AsRef<[String]>>(data: &[R], headers: &[&str]) {
+    let cols = headers.len();
+
     #[allow(non_snake_case)]
     let VERTICAL: ColoredString = " | ".bright_black();
     #[allow(non_snake_case)]
@@ -11,9 +16,9 @@ pub fn table<const COLS: usize>(data: &[[String; COLS]], headers: [&str; COLS])
     #[allow(non_snake_case)]
     let INTERSECTION: ColoredString = "-+-".bright_black();
 
-    let mut longest = [0; COLS];
+    let mut longest = vec![0; cols];
     for row in data {
-        for (i, row) in row.iter().enumerate() {
+        for (i, row) in row.as_ref().iter().enumerate() {
             longest[i] = row.chars().count().max(longest[i]);
         }
     }
@@ -24,24 +29,24 @@ pub fn table<const COLS: usize>(data: &[[String; COLS]], headers: [&str; COLS])
 
     for (col_idx, header) in headers.iter().enumerate() {
         print!("{}", pad(header, longest[col_idx], " ").white());
-        if col_idx + 1 != COLS {
+        if col_idx + 1 != cols {
             print!("{}", VERTICAL)
         }
     }
     println!();
 
-    for col_idx in 0..COLS {
-        print!("{}", pad_col("", longest[col_idx], &HORIZONTAL));
-        if col_idx + 1 != COLS {
+    for (col_idx, len) in longest.iter().enumerate() {
+        print!("{}", pad_col("", *len, &HORIZONTAL));
+        if col_idx + 1 != cols {
             print!("{}", INTERSECTION)
         }
     }
     println!();
 
     for row in data {
-        for (col_idx, item) in row.iter().enumerate() {
+        for (col_idx, item) in row.as_ref().iter().enumerate() {
             print!("{}", item);
-            if col_idx + 1 != COLS {
+            if col_idx + 1 != cols {
                 print!(
                     "{}{}",
                     pad("", longest[col_idx] - item.chars().count(), " "),
@@ -53,6 +58,131 @@ pub fn table<const COLS: usize>(data: &[[String; COLS]], headers: [&str; COLS])
     }
 }
 
+/// Print a GitHub-flavored Markdown table
+pub fn markdown_table<R: AsRef<[String]>>(data: &[R], headers: &[&str]) {
+    let headers = headers
+        .iter()
+        .map(|x| escape_markdown(x))
+        .collect::<Vec<_>>();
+    println!("| {} |", headers.join(" | "));
+    println!("|{}|", vec![" --- "; headers.len()].join("|"));
+
+    for row in data {
+        let cells = row
+            .as_ref()
+            .iter()
+            .map(|x| escape_markdown(x))
+            .collect::<Vec<_>>();
+        println!("| {} |", cells.join(" | "));
+    }
+}
+
+/// Escapes what Markdown would format in `cell`, a `|` would also end the cell
+/// and a newline the whole table
+pub fn escape_markdown(cell: &str) -> String {
+    let mut escaped = String::with_capacity(cell.len());
+    for c in cell.chars() {
+        match c {
+            '\n' => escaped.push(' '),
+            '\\' | '`' | '*' | '_' | '[' | ']' | '<' | '>' | '|' | '#' | '~' => {
+                escaped.push('\\');
+                escaped.push(c);
+            }
+            c => escaped.push(c),
+        }
+    }
+    escaped
+}
+
+/// Makes the tables of an [`HtmlPage`] sortable by clicking their headers
+const SORT_SCRIPT: &str = r#"document.querySelectorAll("th").forEach((th) => th.addEventListener("click", () => {
+  const body = th.closest("table").tBodies[0];
+  const ascending = th.dataset.order !== "asc";
+  th.dataset.order = ascending ? "asc" : "desc";
+  const text = (row) => row.cells[th.cellIndex].textContent;
+  const rows = Array.from(body.rows).sort((a, b) =>
+    text(a).localeCompare(text(b), undefined, { numeric: true }) * (ascending ? 1 : -1));
+  body.append(...rows);
+}));"#;
+
+const STYLE: &str = "body { font-family: sans-serif; margin: 2em; }
+table { border-collapse: collapse; margin-bottom: 2em; }
+th, td { border: 1px solid #ccc; padding: 0.3em 0.6em; text-align: left; }
+th { cursor: pointer; background: #f0f0f0; }";
+
+/// A standalone HTML page of tables and the text around them, printed at once by [`HtmlPage::print`]
+pub struct HtmlPage {
+    title: String,
+    body: String,
+}
+
+impl HtmlPage {
+    /// Reports are read later, so they say when they were made
+    pub fn new(title: &str, generated: chrono::NaiveDateTime) -> Self {
+        let mut page = Self {
+            title: title.to_owned(),
+            body: String::new(),
+        };
+        page.paragraph(&format!(
+            "Generated {}",
+            format_exact(generated.trunc_subsecs(0))
+        ));
+        page
+    }
+
+    pub fn heading(&mut self, text: &str) {
+        self.body += &format!("<h2>{}</h2>\n", escape_html(text));
+    }
+
+    pub fn paragraph(&mut self, text: &str) {
+        self.body += &format!("<p>{}</p>\n", escape_html(text));
+    }
+
+    pub fn table<R: AsRef<[String]>>(&mut self, data: &[R], headers: &[&str]) {
+        self.body += "<table>\n<thead><tr>";
+        for header in headers {
+            self.body += &format!("<th>{}</th>", escape_html(header));
+        }
+        self.body += "</tr></thead>\n<tbody>\n";
+        for row in data {
+            self.body += "<tr>";
+            for cell in row.as_ref() {
+                self.body += &format!("<td>{}</td>", escape_html(cell));
+            }
+            self.body += "</tr>\n";
+        }
+        self.body += "</tbody>\n</table>\n";
+    }
+
+    pub fn print(self) {
+        let title = escape_html(&self.title);
+        println!("<!DOCTYPE html>");
+        println!(
+            "<html>\n<head>\n<meta charset=\"utf-8\">\n<title>{}</title>",
+            title
+        );
+        println!("<style>\n{}\n</style>\n</head>\n<body>", STYLE);
+        println!("<h1>{}</h1>", title);
+        print!("{}", self.body);
+        println!("<script>\n{}\n</script>\n</body>\n</html>", SORT_SCRIPT);
+    }
+}
+
+fn escape_html(text: &str) -> String {
+    let mut escaped = String::with_capacity(text.len());
+    for c in text.chars() {
+        match c {
+            '&' => escaped.push_str("&amp;"),
+            '<' => escaped.push_str("&lt;"),
+            '>' => escaped.push_str("&gt;"),
+            '"' => escaped.push_str("&quot;"),
+            '\'' => escaped.push_str("&#39;"),
+            c => escaped.push(c),
+        }
+    }
+    escaped
+}
+
 fn pad(input: &str, mut len: usize, c: &str) -> String {
     let in_chars = input.chars().count();
     if in_chars > len {
@@ -69,3 +199,13 @@ fn pad_col(input: &str, mut len: usize, c: &ColoredString) -> ColoredString {
     let o = input.to_string() + &c.repeat(len - in_chars);
     o.color(c.fgcolor().unwrap_or(colored::Color::BrightWhite))
 }
+
+#[test]
+fn test_escape_markdown() {
+    assert_eq!(escape_markdown("/home/a|b/*.txt"), r"/home/a\|b/\*.txt");
+    assert_eq!(escape_markdown("line\nbreak"), "line break");
+    assert_eq!(
+        escape_html("<a href='x'>&</a>"),
+        "&lt;a href=&#39;x&#39;&gt;&amp;&lt;/a&gt;"
+    );
+}
@@ -0,0 +1,166 @@
+use std::fs;
+
+use chrono::format::{Item, StrftimeItems};
+
+use crate::{
+    commands::entry_id,
+    dates::format_exact,
+    trashing::{path_size, Trashinfo},
+};
+
+/// A line of `list --format`, like `{id}\t{deleted_at:%Y-%m-%d}\t{path}`
+#[derive(Debug, Clone)]
+pub struct Template(Vec<Piece>);
+
+#[derive(Debug, Clone, PartialEq)]
+enum Piece {
+    Text(String),
+    Id,
+    /// With a strftime format, otherwise the configured date format is used
+    DeletedAt(Option<String>),
+    Path,
+    Name,
+    Trash,
+    TrashFilename,
+    Size,
+    Exists,
+}
+
+/// The placeholders a template can contain
+const FIELDS: &str = "id, deleted_at, path, name, trash, trash_filename, size, exists";
+
+/// Parses a template, `\t`, `\n` and `\\` are unescaped and `{{` and `}}` are literal braces
+pub fn parse_template(input: &str) -> anyhow::Result<Template> {
+    let mut pieces = vec![];
+    let mut text = String::new();
+    let mut chars = input.chars();
+    while let Some(c) = chars.next() {
+        match c {
+            '\\' => match chars.next() {
+                Some('t') => text.push('\t'),
+                Some('n') => text.push('\n'),
+                Some('\\') => text.push('\\'),
+                Some(other) => anyhow::bail!("Unknown escape \\{} in format", other),
+                None => anyhow::bail!("Format ends with a \\"),
+            },
+            '{' if chars.as_str().starts_with('{') => {
+                chars.next();
+                text.push('{');
+            }
+            '}' if chars.as_str().starts_with('}') => {
+                chars.next();
+                text.push('}');
+            }
+            '}' => anyhow::bail!("Unmatched }} in format, use }}}} for a literal one"),
+            '{' => {
+                let rest = chars.as_str();
+                let end = rest
+                    .find('}')
+                    .ok_or_else(|| anyhow::anyhow!("Unclosed {{ in format"))?;
+                let (name, argument) = match rest[..end].split_once(':') {
+                    Some((name, argument)) => (name, Some(argument)),
+                    None => (&rest[..end], None),
+                };
+                if !text.is_empty() {
+                    pieces.push(Piece::Text(std::mem::take(&mut text)));
+                }
+                pieces.push(parse_field(name, argument)?);
+                chars = rest[end + 1..].chars();
+            }
+            c => text.push(c),
+        }
+    }
+    if !text.is_empty() {
+        pieces.push(Piece::Text(text));
+    }
+
+    Ok(Template(pieces))
+}
+
+fn parse_field(name: &str, argument: Option<&str>) -> anyhow::Result<Piece> {
+    let piece = match name {
+        "deleted_at" => {
+            if let Some(format) = argument {
+                anyhow::ensure!(
+                    !StrftimeItems::new(format).any(|x| x == Item::Error),
+                    "Invalid date format: {}",
+                    format
+                );
+            }
+            return Ok(Piece::DeletedAt(argument.map(str::to_owned)));
+        }
+        "id" => Piece::Id,
+        "path" => Piece::Path,
+        "name" => Piece::Name,
+        "trash" => Piece::Trash,
+        "trash_filename" => Piece::TrashFilename,
+        "size" => Piece::Size,
+        "exists" => Piece::Exists,
+        other => anyhow::bail!(
+            "Unknown field {{{}}} in format, known are: {}",
+            other,
+            FIELDS
+        ),
+    };
+    anyhow::ensure!(argument.is_none(), "{{{}}} takes no format", name);
+    Ok(piece)
+}
+
+impl Template {
+    /// Only the fields that are used are looked up, so `{size}` and `{exists}` cost nothing otherwise
+    pub fn render(&self, info: &Trashinfo) -> String {
+        let mut line = String::new();
+        for piece in &self.0 {
+            match piece {
+                Piece::Text(text) => line += text,
+                Piece::Id => line += &entry_id(info),
+                Piece::DeletedAt(Some(format)) => {
+                    line += &info.deleted_at.format(format).to_string()
+                }
+                Piece::DeletedAt(None) => line += &format_exact(info.deleted_at),
+                Piece::Path => line += &info.original_filepath.to_string_lossy(),
+                Piece::Name => {
+                    if let Some(name) = info.original_filepath.file_name() {
+                        line += &name.to_string_lossy();
+                    }
+                }
+                Piece::Trash => line += &info.trash.trash_path.to_string_lossy(),
+                Piece::TrashFilename => line += &info.trash_filename.to_string_lossy(),
+                Piece::Size => {
+                    // orphaned trashinfo files simply don't take up any space
+                    let path = info.trash.files_dir().join(&info.trash_filename);
+                    line += &path_size(&path).unwrap_or(0).to_string();
+                }
+                Piece::Exists => {
+                    line += match fs::symlink_metadata(&info.original_filepath).is_ok() {
+                        true => "yes",
+                        false => "no",
+                    }
+                }
+            }
+        }
+        line
+    }
+}
+
+#[test]
+fn test_parse_template() {
+    let template = parse_template(r"{id}\t{deleted_at:%Y-%m-%d} {{{path}}}").unwrap();
+    assert_eq!(
+        template.0,
+        [
+            Piece::Id,
+            Piece::Text("\t".to_owned()),
+            Piece::DeletedAt(Some("%Y-%m-%d".to_owned())),
+            Piece::Text(" {".to_owned()),
+            Piece::Path,
+            Piece::Text("}".to_owned()),
+        ]
+    );
+
+    assert!(parse_template("{nope}").is_err());
+    assert!(parse_template("{id").is_err());
+    assert!(parse_template("id}").is_err());
+    assert!(parse_template("{id:%Y}").is_err());
+    assert!(parse_template("{deleted_at:%Q}").is_err());
+}
@@ -1,5 +1,37 @@
 use crate::trashing::UnifiedTrash;
-use std::{path::PathBuf, process::Command};
+use std::{
+    env, fs,
+    ops::Deref,
+    path::{Path, PathBuf},
+    process::Command,
+};
+
+/// A fresh directory under the system temp dir, removed again when dropped so a failing
+/// test doesn't leave it behind
+pub struct TempDir(PathBuf);
+
+impl TempDir {
+    pub fn new(name: &str) -> TempDir {
+        let path = env::temp_dir().join(format!("trash-cli-test-{}-{}", name, std::process::id()));
+        let _ = fs::remove_dir_all(&path);
+        fs::create_dir_all(&path).unwrap();
+        TempDir(path)
+    }
+}
+
+impl Deref for TempDir {
+    type Target = Path;
+
+    fn deref(&self) -> &Path {
+        &self.0
+    }
+}
+
+impl Drop for TempDir {
+    fn drop(&mut self) {
+        let _ = fs::remove_dir_all(&self.0);
+    }
+}
 
 #[test]
 // Fails when trash contains any utf-8 chars, as gio just doesn't seem to try to do utf-8
@@ -0,0 +1,502 @@
+use std::{
+    ffi::{OsStr, OsString},
+    fs::{self, File, OpenOptions},
+    io::{self, Write},
+    os::unix::ffi::{OsStrExt, OsStringExt},
+    path::{Path, PathBuf},
+    thread,
+    time::{Duration, Instant},
+};
+
+use chrono::NaiveDateTime;
+use rustc_hash::FxHashSet;
+
+use super::{
+    error::{Context, Result, TrashError},
+    numbered_filename, path_size, Trashinfo,
+};
+
+/// Options for [`export`]
+#[derive(Debug, Clone)]
+pub struct ExportOptions {
+    /// Path of the (first) archive volume
+    pub output: PathBuf,
+
+    /// Start a new volume once the current one would grow beyond this many bytes.
+    /// Entries are never split, so a single big entry can still exceed this.
+    pub volume_size: Option<u64>,
+
+    /// Maximum number of bytes written per second
+    pub rate_limit: Option<u64>,
+
+    /// Continue a previous export, skipping everything that was already archived
+    pub resume: bool,
+
+    /// Local time used as the modification time of the trashinfo files in the archive,
+    /// usually [`UnifiedTrash::now`](super::UnifiedTrash::now)
+    pub now: NaiveDateTime,
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct ExportSummary {
+    /// Number of entries archived in this run
+    pub exported: usize,
+
+    /// Number of entries that were archived by a previous run
+    pub skipped: usize,
+
+    /// The volumes written in this run
+    pub volumes: Vec<PathBuf>,
+}
+
+/// Archives the `entries` as tar, laid out like a trash directory (`files/` and `info/`).
+/// The trashinfo files in the archive always contain absolute paths.
+/// Volumes are compressed with zstd if the output ends with `.zst`.
+///
+/// Progress is recorded in a manifest next to the output (`<output>.manifest`) after every entry,
+/// so an interrupted export can be resumed. Every run writes into new volumes, except that a volume
+/// that was interrupted before any of its entries was complete is started over.
+/// Volumes after the first one are named like `backup.part2.tar`.
+pub fn export(entries: &[Trashinfo], opts: &ExportOptions) -> Result<ExportSummary> {
+    let manifest_path = manifest_path(&opts.output);
+
+    let mut manifest = match (manifest_path.exists(), opts.resume) {
+        (true, true) => Manifest::read(&manifest_path).context("Failed to read manifest")?,
+        (false, false) => Manifest::default(),
+        (true, false) => {
+            return Err(TrashError::Conflict(format!(
+                "{} already exists, pass --resume to continue the previous export",
+                manifest_path.display()
+            )))
+        }
+        (false, true) => {
+            return Err(TrashError::NotFound(format!(
+                "No previous export to resume, {} does not exist",
+                manifest_path.display()
+            )))
+        }
+    };
+
+    let mut manifest_file = OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(&manifest_path)
+        .context("Failed to open manifest")?;
+
+    let mut summary = ExportSummary::default();
+    let mut volume_nr = match manifest.last_volume_used {
+        true => manifest.last_volume,
+        false => manifest.last_volume.saturating_sub(1),
+    };
+    let mut volume: Option<tar::Builder<Volume>> = None;
+
+    for entry in entries {
+        let files_path = entry.trash.files_dir().join(&entry.trash_filename);
+        if manifest.done.contains(files_path.as_os_str()) {
+            summary.skipped += 1;
+            continue;
+        }
+
+        let size = path_size(&files_path)
+            .context(format!("Failed to get size of {}", files_path.display()))?;
+
+        if let (Some(limit), Some(builder)) = (opts.volume_size, &volume) {
+            let written = builder.get_ref().written();
+            if written > 0 && written + size > limit {
+                finish_volume(volume.take().unwrap())?;
+            }
+        }
+
+        let builder = match &mut volume {
+            Some(v) => v,
+            None => {
+                volume_nr += 1;
+                let path = volume_path(&opts.output, volume_nr);
+
+                // recorded before it's created, so a resumed export knows it's ours to overwrite
+                let reuse = volume_nr <= manifest.last_volume;
+                if !reuse {
+                    writeln!(manifest_file, "{}", volume_nr).context("Failed to write manifest")?;
+                }
+                let file = OpenOptions::new()
+                    .write(true)
+                    .create(reuse)
+                    .truncate(reuse)
+                    .create_new(!reuse)
+                    .open(&path)
+                    .context(format!("Failed to create {}", path.display()))?;
+
+                let file = Throttle::new(file, opts.rate_limit);
+                let writer = if path.extension() == Some(OsStr::new("zst")) {
+                    Volume::Zstd(
+                        zstd::Encoder::new(file, 0).context("Failed to start compression")?,
+                    )
+                } else {
+                    Volume::Plain(file)
+                };
+
+                let mut builder = tar::Builder::new(writer);
+                builder.follow_symlinks(false);
+                summary.volumes.push(path);
+                volume.insert(builder)
+            }
+        };
+
+        let mut name = entry.trash_filename.clone();
+        for i in 1.. {
+            if !manifest.names.contains(&name) {
+                break;
+            }
+            name = numbered_filename(&entry.trash_filename, i);
+        }
+
+        append_entry(builder, entry, &files_path, &name, opts.now)
+            .context(format!("Failed to archive {}", files_path.display()))?;
+        builder
+            .get_mut()
+            .flush()
+            .context("Failed to write archive")?;
+
+        writeln!(
+            manifest_file,
+            "{}\t{}\t{}",
+            volume_nr,
+            urlencoding::encode_binary(name.as_bytes()),
+            urlencoding::encode_binary(files_path.as_os_str().as_bytes())
+        )
+        .context("Failed to write manifest")?;
+
+        manifest.names.insert(name);
+        manifest.done.insert(files_path.into_os_string());
+        summary.exported += 1;
+    }
+
+    if let Some(builder) = volume {
+        finish_volume(builder)?;
+    }
+
+    Ok(summary)
+}
+
+fn append_entry(
+    builder: &mut tar::Builder<Volume>,
+    entry: &Trashinfo,
+    files_path: &Path,
+    name: &OsStr,
+    now: NaiveDateTime,
+) -> Result<()> {
+    let archive_path = Path::new("files").join(name);
+    if fs::symlink_metadata(files_path)?.is_dir() {
+        builder.append_dir_all(&archive_path, files_path)?;
+    } else {
+        builder.append_path_with_name(files_path, &archive_path)?;
+    }
+
+    let trashinfo = entry.trashinfo_file_abs();
+    let mut info_name = name.to_os_string();
+    info_name.push(".trashinfo");
+
+    let mut header = tar::Header::new_gnu();
+    header.set_size(trashinfo.len() as u64);
+    header.set_mode(0o600);
+    let mtime = now
+        .and_local_timezone(chrono::Local)
+        .earliest()
+        .map_or(0, |x| x.timestamp());
+    header.set_mtime(mtime.max(0) as u64);
+    builder.append_data(
+        &mut header,
+        Path::new("info").join(info_name),
+        trashinfo.as_bytes(),
+    )?;
+
+    Ok(())
+}
+
+fn finish_volume(builder: tar::Builder<Volume>) -> Result<()> {
+    let file = match builder.into_inner().context("Failed to finish archive")? {
+        Volume::Plain(v) => v,
+        Volume::Zstd(v) => v.finish().context("Failed to finish compression")?,
+    };
+    file.inner.sync_all().context("Failed to sync archive")?;
+    Ok(())
+}
+
+fn manifest_path(output: &Path) -> PathBuf {
+    let mut path = output.as_os_str().to_os_string();
+    path.push(".manifest");
+    PathBuf::from(path)
+}
+
+/// The first volume is the `output` itself, later ones get `.part<n>` inserted
+/// before the `.tar` extension (`backup.tar.zst` -> `backup.part2.tar.zst`)
+fn volume_path(output: &Path, nr: usize) -> PathBuf {
+    if nr <= 1 {
+        return output.to_path_buf();
+    }
+
+    let file_name = output.file_name().unwrap_or_default().as_bytes();
+    // a `.tar` earlier on is part of the name, like in `my.tarballs.tar.zst`
+    let split = file_name
+        .windows(4)
+        .rposition(|x| x == b".tar")
+        .unwrap_or(file_name.len());
+
+    let mut new_name = file_name[..split].to_vec();
+    new_name.extend_from_slice(format!(".part{}", nr).as_bytes());
+    new_name.extend_from_slice(&file_name[split..]);
+
+    output.with_file_name(OsString::from_vec(new_name))
+}
+
+/// Records which volumes were started and which entries were already exported.
+///
+/// A line with only `<volume>` is written before a volume is created, each exported entry adds
+/// `<volume>\t<name in archive>\t<path in files dir>`, with both paths url encoded.
+#[derive(Debug, Default)]
+struct Manifest {
+    last_volume: usize,
+
+    /// Whether any entry was completed in the last volume
+    last_volume_used: bool,
+
+    names: FxHashSet<OsString>,
+    done: FxHashSet<OsString>,
+}
+
+impl Manifest {
+    fn read(path: &Path) -> Result<Self> {
+        let mut manifest = Self::default();
+        for line in fs::read_to_string(path)?.lines() {
+            let mut parts = line.split('\t');
+            let volume = parts
+                .next()
+                .unwrap_or_default()
+                .parse()
+                .context("Invalid volume number")?;
+            if volume > manifest.last_volume {
+                manifest.last_volume = volume;
+                manifest.last_volume_used = false;
+            }
+
+            let (Some(name), Some(files_path)) = (parts.next(), parts.next()) else {
+                // a started volume, or a line that was cut off by an interruption
+                continue;
+            };
+
+            manifest.last_volume_used |= volume == manifest.last_volume;
+            manifest.names.insert(OsString::from_vec(
+                urlencoding::decode_binary(name.as_bytes()).into_owned(),
+            ));
+            manifest.done.insert(OsString::from_vec(
+                urlencoding::decode_binary(files_path.as_bytes()).into_owned(),
+            ));
+        }
+
+        Ok(manifest)
+    }
+}
+
+/// A single archive file, optionally compressed
+enum Volume {
+    Plain(Throttle<File>),
+    Zstd(zstd::Encoder<'static, Throttle<File>>),
+}
+
+impl Volume {
+    /// Bytes that reached the file so far (after compression)
+    fn written(&self) -> u64 {
+        match self {
+            Volume::Plain(v) => v.written,
+            Volume::Zstd(v) => v.get_ref().written,
+        }
+    }
+}
+
+impl Write for Volume {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        match self {
+            Volume::Plain(v) => v.write(buf),
+            Volume::Zstd(v) => v.write(buf),
+        }
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        match self {
+            Volume::Plain(v) => v.flush(),
+            Volume::Zstd(v) => v.flush(),
+        }
+    }
+}
+
+/// Counts the bytes written through it and optionally limits the write speed
+struct Throttle<W> {
+    inner: W,
+    rate_limit: Option<u64>,
+    written: u64,
+    started: Instant,
+}
+
+impl<W> Throttle<W> {
+    fn new(inner: W, rate_limit: Option<u64>) -> Self {
+        Self {
+            inner,
+            rate_limit,
+            written: 0,
+            started: Instant::now(),
+        }
+    }
+}
+
+impl<W: Write> Write for Throttle<W> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        let written = self.inner.write(buf)?;
+        self.written += written as u64;
+
+        if let Some(rate_limit) = self.rate_limit {
+            // sleep until the average speed is back under the limit
+            let expected = Duration::from_secs_f64(self.written as f64 / rate_limit as f64);
+            if let Some(ahead) = expected.checked_sub(self.started.elapsed()) {
+                thread::sleep(ahead);
+            }
+        }
+
+        Ok(written)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.inner.flush()
+    }
+}
+
+#[test]
+fn test_volume_path() {
+    let output = Path::new("/mnt/backup/trash.tar.zst");
+    assert_eq!(volume_path(output, 1), output);
+    assert_eq!(
+        volume_path(output, 2),
+        Path::new("/mnt/backup/trash.part2.tar.zst")
+    );
+    assert_eq!(volume_path(Path::new("trash"), 3), Path::new("trash.part3"));
+    assert_eq!(
+        volume_path(Path::new("my.tarballs.tar.zst"), 2),
+        Path::new("my.tarballs.part2.tar.zst")
+    );
+}
+
+#[cfg(test)]
+fn archived_names(volume: &Path) -> Vec<PathBuf> {
+    let file = File::open(volume).unwrap();
+    let reader: Box<dyn io::Read> = match volume.extension() == Some(OsStr::new("zst")) {
+        true => Box::new(zstd::Decoder::new(file).unwrap()),
+        false => Box::new(file),
+    };
+    tar::Archive::new(reader)
+        .entries()
+        .unwrap()
+        .map(|x| x.unwrap().path().unwrap().into_owned())
+        .collect()
+}
+
+#[test]
+fn test_manifest_round_trip() {
+    let dir = crate::test::TempDir::new("manifest");
+    let trash = super::UnifiedTrash::in_dir(&dir.join("trash")).unwrap();
+    for name in ["with\ttab", "with\nnewline", "plain"] {
+        fs::write(dir.join(name), name).unwrap();
+        trash.put(&dir.join(name), false, |_, _| false).unwrap();
+    }
+    let entries = trash.list().unwrap();
+    let opts = ExportOptions {
+        output: dir.join("export.tar"),
+        volume_size: None,
+        rate_limit: None,
+        resume: false,
+        now: trash.now(),
+    };
+    export(&entries, &opts).unwrap();
+
+    let manifest = Manifest::read(&manifest_path(&opts.output)).unwrap();
+    assert_eq!(manifest.last_volume, 1);
+    assert!(manifest.last_volume_used);
+    for entry in &entries {
+        assert!(manifest.names.contains(&entry.trash_filename));
+        let files_path = entry.trash.files_dir().join(&entry.trash_filename);
+        assert!(manifest.done.contains(files_path.as_os_str()));
+    }
+}
+
+#[test]
+fn test_export_resume() {
+    let dir = crate::test::TempDir::new("resume");
+    let trash = super::UnifiedTrash::in_dir(&dir.join("trash")).unwrap();
+    for name in ["a", "b"] {
+        fs::write(dir.join(name), name).unwrap();
+        trash.put(&dir.join(name), false, |_, _| false).unwrap();
+    }
+    let mut opts = ExportOptions {
+        output: dir.join("export.tar.zst"),
+        volume_size: None,
+        rate_limit: None,
+        resume: false,
+        now: trash.now(),
+    };
+    export(&trash.list().unwrap(), &opts).unwrap();
+    assert!(export(&trash.list().unwrap(), &opts).is_err());
+
+    fs::write(dir.join("c"), "c").unwrap();
+    trash.put(&dir.join("c"), false, |_, _| false).unwrap();
+    opts.resume = true;
+    let summary = export(&trash.list().unwrap(), &opts).unwrap();
+    assert_eq!((summary.exported, summary.skipped), (1, 2));
+    assert_eq!(summary.volumes, [dir.join("export.part2.tar.zst")]);
+    assert_eq!(
+        archived_names(&summary.volumes[0]),
+        [Path::new("files/c"), Path::new("info/c.trashinfo")]
+    );
+
+    // interrupted while writing the first entry of the third volume
+    fs::write(dir.join("d"), "d").unwrap();
+    trash.put(&dir.join("d"), false, |_, _| false).unwrap();
+    let mut manifest = OpenOptions::new()
+        .append(true)
+        .open(manifest_path(&opts.output))
+        .unwrap();
+    writeln!(manifest, "3").unwrap();
+    fs::write(dir.join("export.part3.tar.zst"), b"\x28\xb5\x2f\xfd").unwrap();
+
+    let summary = export(&trash.list().unwrap(), &opts).unwrap();
+    assert_eq!((summary.exported, summary.skipped), (1, 3));
+    assert_eq!(summary.volumes, [dir.join("export.part3.tar.zst")]);
+    assert_eq!(
+        archived_names(&summary.volumes[0]),
+        [Path::new("files/d"), Path::new("info/d.trashinfo")]
+    );
+}
+
+#[test]
+fn test_export_volume_size() {
+    let dir = crate::test::TempDir::new("volumes");
+    let trash = super::UnifiedTrash::in_dir(&dir.join("trash")).unwrap();
+    for name in ["a", "b", "c"] {
+        fs::write(dir.join(name), vec![0u8; 10_000]).unwrap();
+        trash.put(&dir.join(name), false, |_, _| false).unwrap();
+    }
+    let opts = ExportOptions {
+        output: dir.join("export.tar"),
+        volume_size: Some(15_000),
+        rate_limit: None,
+        resume: false,
+        now: trash.now(),
+    };
+    let summary = export(&trash.list().unwrap(), &opts).unwrap();
+
+    assert_eq!(summary.exported, 3);
+    assert_eq!(
+        summary.volumes,
+        [1, 2, 3].map(|x| volume_path(&opts.output, x))
+    );
+    for volume in &summary.volumes {
+        assert_eq!(archived_names(volume).len(), 2);
+    }
+}
@@ -0,0 +1,72 @@
+use std::{path::PathBuf, sync::Arc};
+
+use chrono::NaiveDateTime;
+
+use super::{
+    error::{Result, TrashError},
+    unified_trash::{EmptyOptions, EmptySummary},
+    Trashinfo, UnifiedTrash,
+};
+
+/// Wraps [`UnifiedTrash`] for async applications (file managers, daemons) running on tokio.
+///
+/// There is no real async filesystem API, so every operation runs on tokio's blocking
+/// thread pool instead of blocking the runtime. Cloning is cheap.
+#[derive(Debug, Clone)]
+pub struct AsyncUnifiedTrash {
+    inner: Arc<UnifiedTrash>,
+}
+
+impl AsyncUnifiedTrash {
+    pub async fn new() -> Result<Self> {
+        let trash = blocking(UnifiedTrash::new).await?;
+        Ok(Self::from_sync(trash))
+    }
+
+    /// Wraps an already configured trash (quota, clock, ...)
+    pub fn from_sync(trash: UnifiedTrash) -> Self {
+        Self {
+            inner: Arc::new(trash),
+        }
+    }
+
+    pub async fn list(&self) -> Result<Vec<Trashinfo>> {
+        let trash = self.inner.clone();
+        blocking(move || trash.list()).await
+    }
+
+    /// Like [`UnifiedTrash::put`], but never prompts: files that would exceed a quota
+    /// with the `Prompt` policy are refused.
+    pub async fn put(&self, file: PathBuf, follow_links: bool) -> Result<Trashinfo> {
+        let trash = self.inner.clone();
+        blocking(move || trash.put(&file, follow_links, |_, _| false)).await
+    }
+
+    /// Like [`UnifiedTrash::empty`], nothing is printed
+    pub async fn empty(
+        &self,
+        before: NaiveDateTime,
+        filter: impl Fn(&Trashinfo) -> bool + Send + 'static,
+        dry_run: bool,
+    ) -> Result<EmptySummary> {
+        let trash = self.inner.clone();
+        blocking(move || {
+            trash.empty(
+                before,
+                filter,
+                &EmptyOptions {
+                    dry_run,
+                    quiet: true,
+                    ..Default::default()
+                },
+            )
+        })
+        .await
+    }
+}
+
+async fn blocking<T: Send + 'static>(f: impl FnOnce() -> Result<T> + Send + 'static) -> Result<T> {
+    tokio::task::spawn_blocking(f)
+        .await
+        .map_err(|e| TrashError::Other(format!("Blocking task failed: {}", e)))?
+}
@@ -0,0 +1,305 @@
+use std::{fs, path::Path, sync::Arc};
+
+use format as f;
+use log::warn;
+use rustc_hash::FxHashSet;
+
+use super::{
+    error::{Context, Result, TrashError},
+    info_cache::InfoCache,
+    shred,
+    trash_fs::TrashFs,
+    trashinfo, xattrs, Trash, Trashinfo,
+};
+
+/// Where and how the entries of a trash are stored.
+///
+/// [`super::UnifiedTrash`] finds the trashes and decides what happens to which entry
+/// (quotas, filters, the journal, asking the user), the backend only does it.
+/// Entries are still described by [`Trashinfo`]s, backends that don't store trashinfo
+/// files have to come up with one for every entry.
+pub trait TrashBackend: std::fmt::Debug + Send + Sync {
+    /// Hands every complete entry of the `trashes` to `f`, stopping at the first error it returns
+    fn for_each_entry(
+        &self,
+        trashes: &[Arc<Trash>],
+        f: &mut dyn FnMut(Trashinfo) -> Result<()>,
+    ) -> Result<()>;
+
+    /// Records `info` in its trash without storing a file, that is left to the caller.
+    /// The entry is renamed if its name is taken.
+    fn add_entry(&self, info: &mut Trashinfo) -> Result<()>;
+
+    /// Records `info` and moves the file at its original path into its trash.
+    /// The entry is renamed if its name is taken.
+    fn put(&self, info: &mut Trashinfo) -> Result<()>;
+
+    /// Moves the trashed file of `info` back to its original path, the entry stays recorded
+    fn move_back(&self, info: &Trashinfo) -> Result<()>;
+
+    /// Drops the record of `info`, once its file is gone from the trash
+    fn forget(&self, info: &Trashinfo) -> Result<()>;
+
+    /// Permanently deletes the entry, returning the bytes freed.
+    /// With `shred`, the contents are overwritten first.
+    fn remove(&self, info: &Trashinfo, shred: bool) -> Result<u64>;
+
+    /// The size of the trashed file of `info`
+    fn entry_size(&self, info: &Trashinfo) -> Result<u64>;
+
+    /// The space used by `trash`, including files that don't belong to any entry
+    fn trash_size(&self, trash: &Trash) -> Result<u64>;
+
+    /// Drops the entries whose trashed file is gone
+    fn remove_orphaned(&self, trashes: &[Arc<Trash>]) -> Result<()>;
+
+    /// Removes the empty directories interrupted operations leave behind in the `trashes`
+    /// that don't belong to any entry, returning how many were removed
+    fn remove_stray_dirs(&self, trashes: &[Arc<Trash>]) -> Result<usize>;
+}
+
+/// The `files` and `info` dirs of the spec, on `fs`
+#[derive(Debug)]
+pub struct XdgBackend {
+    fs: Arc<dyn TrashFs>,
+}
+
+impl XdgBackend {
+    pub fn new(fs: Arc<dyn TrashFs>) -> Self {
+        Self { fs }
+    }
+
+    fn parse_trashinfo(&self, path: &Path, trash: &Arc<Trash>) -> Result<Trashinfo> {
+        let raw = self
+            .fs
+            .read(path)
+            .context("Failed reading trashinfo file")?;
+        let content = String::from_utf8(raw).context("Trashinfo file is not valid UTF-8")?;
+        trashinfo::parse_trashinfo_content(&content, path, trash)
+    }
+}
+
+impl TrashBackend for XdgBackend {
+    fn for_each_entry(
+        &self,
+        trashes: &[Arc<Trash>],
+        f: &mut dyn FnMut(Trashinfo) -> Result<()>,
+    ) -> Result<()> {
+        // the cache only knows about the local filesystem
+        let mut cache = match self.fs.is_local() {
+            true => InfoCache::load(),
+            false => InfoCache::default(),
+        };
+        for trash in trashes {
+            // one readdir is a lot cheaper than a stat per entry on big trashes
+            let files = match self.fs.read_dir(&trash.files_dir()) {
+                Ok(names) => names.into_iter().collect::<FxHashSet<_>>(),
+                // every trashinfo file is orphaned then
+                Err(e) if e.kind() == std::io::ErrorKind::NotFound => FxHashSet::default(),
+                Err(e) => {
+                    return Err(e).context(f!("Failed to read {}", trash.files_dir().display()));
+                }
+            };
+
+            let info_dir = trash.info_dir();
+            for name in self
+                .fs
+                .read_dir(&info_dir)
+                .context("Failed to read info dir")?
+            {
+                let path = info_dir.join(name);
+                log::trace!("Parsing {}", path.display());
+                let result = match self.fs.is_local() {
+                    true => fs::symlink_metadata(&path)
+                        .map_err(TrashError::from)
+                        .and_then(|meta| cache.parse(&path, &meta, trash)),
+                    false => self.parse_trashinfo(&path, trash),
+                };
+                let Some(info) = ok_or_warn(&path, result) else {
+                    continue;
+                };
+
+                if !files.contains(&info.trash_filename) {
+                    warn!(
+                        "Orphaned trashinfo file: {}",
+                        trash
+                            .info_dir()
+                            .join(&info.trash_filename_trashinfo)
+                            .display()
+                    );
+                    continue;
+                }
+
+                f(info)?;
+            }
+        }
+
+        // the cache only speeds things up, it's not worth failing over
+        if self.fs.is_local() {
+            if let Err(e) = cache.save() {
+                warn!("Failed to save trashinfo cache: {:#}", e);
+            }
+        }
+
+        Ok(())
+    }
+
+    fn add_entry(&self, info: &mut Trashinfo) -> Result<()> {
+        let trash = Arc::clone(&info.trash);
+        trash.add_trashinfo(&*self.fs, info)
+    }
+
+    fn put(&self, info: &mut Trashinfo) -> Result<()> {
+        let trash = Arc::clone(&info.trash);
+        trash.write_trashinfo(&*self.fs, info)
+    }
+
+    fn move_back(&self, info: &Trashinfo) -> Result<()> {
+        let files_path = info.trash.files_dir().join(&info.trash_filename);
+        Ok(self.fs.move_path(&files_path, &info.original_filepath)?)
+    }
+
+    fn forget(&self, info: &Trashinfo) -> Result<()> {
+        let info_path = info.trash.info_dir().join(&info.trash_filename_trashinfo);
+        self.fs.remove_file(&info_path).context(f!(
+            "Failed to remove trashinfo file {}",
+            info_path.display()
+        ))?;
+        remove_sidecar_or_warn(&*self.fs, info);
+        Ok(())
+    }
+
+    fn remove(&self, info: &Trashinfo, shred: bool) -> Result<u64> {
+        let files_path = info.trash.files_dir().join(&info.trash_filename);
+
+        // orphaned trashinfo files simply don't free anything
+        let size = self.fs.size(&files_path).unwrap_or(0);
+
+        if shred && self.fs.stat(&files_path, true).is_ok() {
+            if !self.fs.is_local() {
+                return Err(TrashError::Unsupported(
+                    "Shredding needs the local filesystem".to_owned(),
+                ));
+            }
+            shred::shred(&files_path).context(f!("Failed to shred {}", files_path.display()))?;
+        }
+
+        if let Err(e) = self.fs.remove_all(&files_path) {
+            match e.kind() {
+                std::io::ErrorKind::NotFound => log::info!(
+                    "Removing orphaned trashinfo file of {}",
+                    files_path.display()
+                ),
+                _ => return Err(e).context(f!("Failed to remove {}", files_path.display())),
+            }
+        }
+
+        self.forget(info)?;
+        Ok(size)
+    }
+
+    fn entry_size(&self, info: &Trashinfo) -> Result<u64> {
+        Ok(self
+            .fs
+            .size(&info.trash.files_dir().join(&info.trash_filename))?)
+    }
+
+    fn trash_size(&self, trash: &Trash) -> Result<u64> {
+        Ok(trash.size(&*self.fs)?)
+    }
+
+    fn remove_orphaned(&self, trashes: &[Arc<Trash>]) -> Result<()> {
+        for trash in trashes {
+            let info_dir = trash.info_dir();
+            for name in self
+                .fs
+                .read_dir(&info_dir)
+                .context("Failed to read info dir")?
+            {
+                let path = info_dir.join(name);
+                let Some(info) = ok_or_warn(&path, self.parse_trashinfo(&path, trash)) else {
+                    continue;
+                };
+
+                let files_path = trash.files_dir().join(&info.trash_filename);
+                if self.fs.stat(&files_path, true).is_err() {
+                    log::info!("Removing orphaned trashinfo file: {}", path.display());
+                    self.forget(&info)?;
+                }
+            }
+        }
+
+        Ok(())
+    }
+
+    fn remove_stray_dirs(&self, trashes: &[Arc<Trash>]) -> Result<usize> {
+        let mut removed = 0;
+        for trash in trashes {
+            let files_dir = trash.files_dir();
+            let names = match self.fs.read_dir(&files_dir) {
+                Ok(v) => v,
+                Err(e) if e.kind() == std::io::ErrorKind::NotFound => continue,
+                Err(e) => return Err(e).context(f!("Failed to read {}", files_dir.display())),
+            };
+
+            // an entry is complete once its trashinfo file is written, which happens before the file is moved
+            let entries = self
+                .fs
+                .read_dir(&trash.info_dir())
+                .context("Failed to read info dir")?
+                .into_iter()
+                .collect::<FxHashSet<_>>();
+
+            for name in names {
+                let mut trashinfo = name.clone();
+                trashinfo.push(".trashinfo");
+                if entries.contains(&trashinfo) {
+                    continue;
+                }
+
+                let path = files_dir.join(&name);
+                let is_empty_dir = self.fs.stat(&path, false).is_ok_and(|x| x.is_dir())
+                    && self.fs.read_dir(&path).is_ok_and(|x| x.is_empty());
+                if is_empty_dir {
+                    log::info!("Removing stray directory {}", path.display());
+                    self.fs
+                        .remove_all(&path)
+                        .context(f!("Failed to remove {}", path.display()))?;
+                    removed += 1;
+                }
+            }
+        }
+
+        Ok(removed)
+    }
+}
+
+/// The sidecar is useless once the entry is gone, but not worth failing over
+fn remove_sidecar_or_warn(fs: &dyn TrashFs, info: &Trashinfo) {
+    if !fs.is_local() {
+        return;
+    }
+    if let Err(e) = xattrs::remove_sidecar(info) {
+        warn!(
+            "Failed to remove the stored xattrs of {}: {}",
+            info.original_filepath.display(),
+            e
+        );
+    }
+}
+
+/// Reports a corrupt trashinfo file at `path`, so that it doesn't stop
+/// the rest of the trash from being usable
+fn ok_or_warn(path: &Path, result: Result<Trashinfo>) -> Option<Trashinfo> {
+    match result {
+        Ok(v) => Some(v),
+        Err(e) => {
+            warn!(
+                "Ignoring corrupt trashinfo file {} ({:#}), run `trash repair` to fix it",
+                path.display(),
+                e
+            );
+            None
+        }
+    }
+}
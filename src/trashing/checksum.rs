@@ -0,0 +1,45 @@
+use std::{
+    fmt::Write,
+    fs::File,
+    io::{self, Read},
+    path::Path,
+};
+
+use sha2::{Digest, Sha256};
+
+use super::{contents::open_contents, encryption::Key, Trashinfo};
+
+/// The SHA-256 of the contents of the file at `path`, as lowercase hex
+pub fn sha256_file(path: &Path) -> io::Result<String> {
+    sha256(&mut File::open(path)?)
+}
+
+fn sha256(reader: &mut dyn Read) -> io::Result<String> {
+    let mut hasher = Sha256::new();
+    io::copy(reader, &mut hasher)?;
+
+    let mut hex = String::with_capacity(64);
+    for byte in hasher.finalize() {
+        let _ = write!(hex, "{:02x}", byte);
+    }
+    Ok(hex)
+}
+
+/// Compares the trashed file of `info` with the checksum that was recorded when it was trashed.
+///
+/// `None` if there is nothing to compare, because no checksum was recorded (directories,
+/// symlinks, files trashed without checksums or by other implementations) or the file
+/// can't be read.
+/// `key` is only needed if the file is encrypted.
+pub fn checksum_matches(info: &Trashinfo, key: Option<&Key>) -> Option<bool> {
+    let expected = info.metadata.sha256.as_ref()?;
+    let path = info.trash.files_dir().join(&info.trash_filename);
+
+    match open_contents(info, key).and_then(|mut x| sha256(&mut x)) {
+        Ok(actual) => Some(actual == *expected),
+        Err(e) => {
+            log::debug!("Failed to hash {}: {}", path.display(), e);
+            None
+        }
+    }
+}
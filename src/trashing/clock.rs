@@ -0,0 +1,27 @@
+use chrono::NaiveDateTime;
+
+/// The source of the "current time", e.g. for the `DeletionDate` of newly trashed files
+pub trait Clock: std::fmt::Debug + Send + Sync {
+    /// The current local time
+    fn now(&self) -> NaiveDateTime;
+}
+
+/// The actual system time
+#[derive(Debug, Clone, Copy, Default)]
+pub struct SystemClock;
+
+impl Clock for SystemClock {
+    fn now(&self) -> NaiveDateTime {
+        chrono::Local::now().naive_local()
+    }
+}
+
+/// Always returns the same time, useful for tests and reproducing time dependent bugs
+#[derive(Debug, Clone, Copy)]
+pub struct FixedClock(pub NaiveDateTime);
+
+impl Clock for FixedClock {
+    fn now(&self) -> NaiveDateTime {
+        self.0
+    }
+}
@@ -0,0 +1,240 @@
+use std::{
+    ffi::OsString,
+    fs::{self, OpenOptions},
+    io::{Read, Write},
+    os::unix::fs::{MetadataExt, OpenOptionsExt},
+    path::PathBuf,
+};
+
+use log::{debug, warn};
+use rustc_hash::FxHashMap;
+use serde::{Deserialize, Serialize};
+
+use super::{
+    cache_dir,
+    contents::open_contents,
+    error::{Context, Result},
+    Trashinfo, UnifiedTrash,
+};
+
+/// Limits that keep the content index from growing out of hand
+#[derive(Debug, Clone, Copy)]
+pub struct IndexLimits {
+    /// Files bigger than this (in bytes) are not indexed
+    pub max_file_size: u64,
+
+    /// Indexing stops once this many bytes of text are stored
+    pub max_index_size: u64,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct IndexedFile {
+    /// Path of the trash (not the `files` dir) the file is in
+    trash_path: OsString,
+
+    /// Name of the file in the `files` dir
+    trash_filename: OsString,
+
+    /// Used to tell if an entry needs to be re-read when updating the index
+    mtime: i64,
+    size: u64,
+
+    content: String,
+}
+
+/// An opt-in index over the contents of small, trashed text files.
+///
+/// The index only exists if the user explicitly builds it, it is stored in the cache dir
+/// and is never consulted by anything other than the content search.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct ContentIndex {
+    files: Vec<IndexedFile>,
+}
+
+impl ContentIndex {
+    /// Where the index is stored on disk
+    pub fn location() -> Result<PathBuf> {
+        Ok(cache_dir()?.join("content-index.json"))
+    }
+
+    /// Loads the index from disk, `None` if it was never built.
+    pub fn load() -> Result<Option<Self>> {
+        let location = Self::location()?;
+        let raw = match fs::read(&location) {
+            Ok(v) => v,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(None),
+            Err(e) => {
+                return Err(e).context(format!("Failed to read {}", location.display()));
+            }
+        };
+
+        Ok(Some(
+            serde_json::from_slice(&raw).context("Failed to parse content index")?,
+        ))
+    }
+
+    pub fn save(&self) -> Result<()> {
+        let location = Self::location()?;
+        if let Some(parent) = location.parent() {
+            fs::create_dir_all(parent).context("Failed to create cache dir")?;
+        }
+
+        let serialized = serde_json::to_vec(self).context("Failed to serialize content index")?;
+
+        // the index holds the contents of trashed files, so only the owner may read it.
+        // Written next to it and renamed so an interrupted save doesn't leave half an index
+        let mut partial = location.clone().into_os_string();
+        partial.push(".partial");
+        let partial = PathBuf::from(partial);
+        let _ = fs::remove_file(&partial);
+        OpenOptions::new()
+            .write(true)
+            .create_new(true)
+            .mode(0o600)
+            .open(&partial)
+            .and_then(|mut file| file.write_all(&serialized))
+            .context(format!("Failed to write {}", partial.display()))?;
+        fs::rename(&partial, &location).context(format!("Failed to write {}", location.display()))
+    }
+
+    /// Number of files in the index
+    pub fn len(&self) -> usize {
+        self.files.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.files.is_empty()
+    }
+
+    /// Indexes every trashed file that is valid utf-8 and within the `limits`.
+    ///
+    /// Entries from the `previous` index are reused if the trashed file has not changed since.
+    pub fn build(
+        trash: &UnifiedTrash,
+        limits: IndexLimits,
+        previous: Option<&ContentIndex>,
+    ) -> Result<Self> {
+        let previous = previous
+            .map(|x| {
+                x.files
+                    .iter()
+                    .map(|x| ((x.trash_path.clone(), x.trash_filename.clone()), x))
+                    .collect::<FxHashMap<_, _>>()
+            })
+            .unwrap_or_default();
+
+        let mut files = vec![];
+        let mut total_size = 0;
+        let mut limit_reached = false;
+
+        for info in trash.list().context("Failed to list trash")? {
+            let files_path = info.trash.files_dir().join(&info.trash_filename);
+
+            let meta = match fs::symlink_metadata(&files_path) {
+                Ok(v) => v,
+                Err(e) => {
+                    warn!("Failed to stat {}: {}", files_path.display(), e);
+                    continue;
+                }
+            };
+
+            // the index would keep the contents of encrypted files in plain text
+            if !meta.is_file() || meta.size() > limits.max_file_size || info.metadata.encrypted {
+                continue;
+            }
+
+            // smaller files may still fit
+            if total_size + meta.size() > limits.max_index_size {
+                if !limit_reached {
+                    warn!(
+                        "Content index reached its size limit of {} bytes, not indexing all files",
+                        limits.max_index_size
+                    );
+                }
+                limit_reached = true;
+                continue;
+            }
+
+            let key = (
+                info.trash.trash_path.clone().into_os_string(),
+                info.trash_filename.clone(),
+            );
+
+            let reusable = previous
+                .get(&key)
+                .filter(|x| x.mtime == meta.mtime() && x.size == meta.size());
+
+            let content = match reusable {
+                Some(v) => v.content.clone(),
+                None => match read_text(&info) {
+                    Some(v) => v,
+                    None => {
+                        debug!("Not indexing {}, not text", files_path.display());
+                        continue;
+                    }
+                },
+            };
+
+            total_size += meta.size();
+            files.push(IndexedFile {
+                trash_path: key.0,
+                trash_filename: key.1,
+                mtime: meta.mtime(),
+                size: meta.size(),
+                content,
+            });
+        }
+
+        Ok(Self { files })
+    }
+
+    /// Finds all `trashed` files containing the `query` (case insensitive),
+    /// returning each match together with the first line that contains the query.
+    ///
+    /// Files which are not (or no longer) in the index are never matched.
+    pub fn search<'a>(
+        &self,
+        trashed: &'a [Trashinfo],
+        query: &str,
+    ) -> Vec<(&'a Trashinfo, String)> {
+        let query = query.to_lowercase();
+        let files = self
+            .files
+            .iter()
+            .map(|x| ((x.trash_path.as_os_str(), x.trash_filename.as_os_str()), x))
+            .collect::<FxHashMap<_, _>>();
+
+        trashed
+            .iter()
+            .filter_map(|info| {
+                let indexed = files.get(&(
+                    info.trash.trash_path.as_os_str(),
+                    info.trash_filename.as_os_str(),
+                ))?;
+
+                let line = indexed
+                    .content
+                    .lines()
+                    .find(|x| x.to_lowercase().contains(&query))?;
+
+                Some((info, line.trim().to_owned()))
+            })
+            .collect()
+    }
+}
+
+/// Reads the original contents of the trashed file of `info` if they look like text
+fn read_text(info: &Trashinfo) -> Option<String> {
+    let mut content = String::new();
+    open_contents(info, None)
+        .ok()?
+        .read_to_string(&mut content)
+        .ok()?;
+
+    // valid utf-8 can still contain NUL bytes, which is a good sign of a binary file
+    if content.contains('\0') {
+        return None;
+    }
+
+    Some(content)
+}
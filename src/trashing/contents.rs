@@ -0,0 +1,63 @@
+use std::{
+    fs::{File, OpenOptions},
+    io::{self, Read, Write},
+    os::unix::fs::OpenOptionsExt,
+    path::Path,
+};
+
+use super::{
+    encryption::{DecryptReader, EncryptWriter, Key},
+    Trashinfo,
+};
+
+/// zstd's default, trashed files are rarely read again but compressing shouldn't slow down `put` much
+const LEVEL: i32 = 3;
+
+/// Writes the contents of the file at `from` to a new file at `to`,
+/// compressed and encrypted (in that order) if asked for
+pub fn store_file(from: &Path, to: &Path, compress: bool, key: Option<&Key>) -> io::Result<()> {
+    let input = File::open(from)?;
+    let output = OpenOptions::new()
+        .write(true)
+        .create_new(true)
+        .mode(0o600)
+        .open(to)?;
+
+    match key {
+        Some(key) => {
+            let mut writer = EncryptWriter::new(output, key)?;
+            copy(input, &mut writer, compress)?;
+            writer.finish().map(|_| ())
+        }
+        None => copy(input, output, compress),
+    }
+}
+
+fn copy(mut input: impl Read, mut output: impl Write, compress: bool) -> io::Result<()> {
+    match compress {
+        true => zstd::stream::copy_encode(input, output, LEVEL),
+        false => io::copy(&mut input, &mut output).map(|_| ()),
+    }
+}
+
+/// Opens the trashed file of `info` for reading its original contents, decrypting and
+/// decompressing them if that was done when it was trashed
+pub fn open_contents(info: &Trashinfo, key: Option<&Key>) -> io::Result<Box<dyn Read>> {
+    let file = File::open(info.trash.files_dir().join(&info.trash_filename))?;
+
+    let reader: Box<dyn Read> = match (info.metadata.encrypted, key) {
+        (false, _) => Box::new(file),
+        (true, Some(key)) => Box::new(DecryptReader::new(file, key)?),
+        (true, None) => {
+            return Err(io::Error::new(
+                io::ErrorKind::PermissionDenied,
+                "The file is encrypted, but there is no key",
+            ))
+        }
+    };
+
+    Ok(match info.metadata.compressed {
+        true => Box::new(zstd::Decoder::new(reader)?),
+        false => reader,
+    })
+}
@@ -0,0 +1,231 @@
+use std::{
+    ffi::OsStr,
+    fs,
+    os::unix::{
+        ffi::OsStrExt,
+        fs::{MetadataExt, PermissionsExt},
+    },
+    path::{Path, PathBuf},
+    sync::Arc,
+};
+
+use rustc_hash::FxHashSet;
+
+use super::{
+    error::{Context, Result},
+    list_mounts, responsive, trashinfo, Trash, UnifiedTrash,
+};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Severity {
+    /// Works, but is not how it should be
+    Warning,
+
+    /// Violates the spec in a way that breaks (parts of) the trash
+    Error,
+}
+
+/// A problem found by [`diagnose`]
+#[derive(Debug, Clone)]
+pub struct Finding {
+    pub severity: Severity,
+
+    /// The file or directory the problem is about
+    pub path: PathBuf,
+
+    pub problem: String,
+
+    /// What the user can do about it
+    pub fix: String,
+}
+
+/// Inspects every discovered trash (and every `$topdir/.Trash`, even invalid ones)
+/// for violations of the spec and other problems.
+pub fn diagnose(trash: &UnifiedTrash) -> Result<Vec<Finding>> {
+    let mut findings = vec![];
+    let uid = trash.uid();
+
+    for mount in responsive(list_mounts().context("Failed to list mounts")?) {
+        check_admin_dir(&mount.mount_point.join(".Trash"), &mut findings);
+
+        if mount.is_read_only() {
+            for trash in trash
+                .list_trashes()
+                .iter()
+                .filter(|x| x.dev_root == mount.mount_point)
+            {
+                findings.push(Finding {
+                    severity: Severity::Warning,
+                    path: trash.trash_path.clone(),
+                    problem: "is on a read-only mount, nothing can be trashed, restored or removed"
+                        .to_owned(),
+                    fix: format!(
+                        "Remount {} read-write if you want to use this trash",
+                        mount.mount_point.display()
+                    ),
+                });
+            }
+        }
+    }
+
+    for trash in trash.list_trashes() {
+        check_trash(trash, uid, &mut findings)
+            .context(format!("Failed to check {}", trash.trash_path.display()))?;
+    }
+
+    findings.sort_by_key(|x| std::cmp::Reverse(x.severity));
+
+    Ok(findings)
+}
+
+fn check_admin_dir(admin_dir: &Path, findings: &mut Vec<Finding>) {
+    let Ok(meta) = fs::symlink_metadata(admin_dir) else {
+        return;
+    };
+
+    let mut finding = |problem: &str, fix: String| {
+        findings.push(Finding {
+            severity: Severity::Error,
+            path: admin_dir.to_path_buf(),
+            problem: problem.to_owned(),
+            fix,
+        })
+    };
+
+    if meta.is_symlink() {
+        finding(
+            "is a symlink, the spec requires it to be ignored",
+            format!(
+                "Replace the symlink with a directory: rm '{0}' && mkdir -m 1777 '{0}'",
+                admin_dir.display()
+            ),
+        );
+    } else if !meta.is_dir() {
+        finding(
+            "is not a directory",
+            format!("Remove {}", admin_dir.display()),
+        );
+    } else if meta.permissions().mode() & 0o1000 == 0 {
+        finding(
+            "does not have the sticky bit set, the spec requires it to be ignored",
+            format!("chmod +t '{}'", admin_dir.display()),
+        );
+    }
+}
+
+fn check_trash(trash: &Arc<Trash>, uid: u32, findings: &mut Vec<Finding>) -> Result<()> {
+    let mut finding = |severity, path: &Path, problem: &str, fix: &str| {
+        findings.push(Finding {
+            severity,
+            path: path.to_path_buf(),
+            problem: problem.to_owned(),
+            fix: fix.to_owned(),
+        })
+    };
+
+    let trash_meta = fs::metadata(&trash.trash_path).context("Failed to stat trash")?;
+    if trash_meta.uid() != uid {
+        finding(
+            Severity::Error,
+            &trash.trash_path,
+            "is owned by a different user",
+            "Change the owner of the trash to your user",
+        );
+    }
+    // trashes on shared drives are the ones other users can easily get to
+    if !trash.is_home_trash && trash_meta.permissions().mode() & 0o077 != 0 {
+        finding(
+            Severity::Warning,
+            &trash.trash_path,
+            "is accessible by other users",
+            &format!("chmod 700 '{}'", trash.trash_path.display()),
+        );
+    }
+
+    let mut listed_files = FxHashSet::default();
+    for entry in fs::read_dir(trash.info_dir()).context("Failed to read info dir")? {
+        let entry = entry.context("Failed to read info dir")?;
+        let path = entry.path();
+
+        let Some(name) = path
+            .file_name()
+            .and_then(|x| x.as_bytes().strip_suffix(b".trashinfo"))
+        else {
+            finding(
+                Severity::Warning,
+                &path,
+                "does not end in .trashinfo and will be ignored",
+                "Rename or remove the file",
+            );
+            continue;
+        };
+        listed_files.insert(OsStr::from_bytes(name).to_os_string());
+
+        let meta = entry.metadata().context("Failed to stat info file")?;
+        if meta.permissions().mode() & 0o077 != 0 {
+            finding(
+                Severity::Warning,
+                &path,
+                "is accessible by other users",
+                &format!("chmod 600 '{}'", path.display()),
+            );
+        }
+
+        let info = match trashinfo::parse_trashinfo(&path, trash) {
+            Ok(v) => v,
+            Err(e) => {
+                finding(
+                    Severity::Error,
+                    &path,
+                    &format!("can not be parsed: {:#}", e),
+                    "Fix or remove the file",
+                );
+                continue;
+            }
+        };
+
+        if !trash.is_home_trash && raw_path_is_absolute(&path) {
+            finding(
+                Severity::Warning,
+                &path,
+                "contains an absolute path, trashes outside of $HOME should use paths relative to the mount point",
+                &format!("Make the path relative to {}", trash.dev_root.display()),
+            );
+        }
+
+        if fs::symlink_metadata(trash.files_dir().join(&info.trash_filename)).is_err() {
+            finding(
+                Severity::Warning,
+                &path,
+                "has no matching file in the trash",
+                "Run `trash remove-orphaned`",
+            );
+        }
+    }
+
+    for entry in fs::read_dir(trash.files_dir()).context("Failed to read files dir")? {
+        let entry = entry.context("Failed to read files dir")?;
+        if !listed_files.contains(&entry.file_name()) {
+            finding(
+                Severity::Warning,
+                &entry.path(),
+                "has no trashinfo file and is invisible to this program",
+                "Move the file out of the trash or delete it",
+            );
+        }
+    }
+
+    Ok(())
+}
+
+/// Whether the (first) `Path` key of the trashinfo file at `path` is absolute
+fn raw_path_is_absolute(path: &Path) -> bool {
+    fs::read_to_string(path)
+        .ok()
+        .and_then(|x| {
+            x.lines()
+                .find_map(|x| x.strip_prefix("Path="))
+                .map(|x| x.starts_with('/') || x.starts_with("%2F"))
+        })
+        .unwrap_or(false)
+}
@@ -0,0 +1,274 @@
+use std::{
+    fmt, fs,
+    io::{self, BufRead, BufReader, Read, Write},
+    os::unix::fs::PermissionsExt,
+    path::PathBuf,
+    process::Command,
+};
+
+use chacha20poly1305::{
+    aead::{
+        rand_core::RngCore,
+        stream::{DecryptorBE32, EncryptorBE32},
+        KeyInit, OsRng,
+    },
+    XChaCha20Poly1305,
+};
+
+use super::error::{Context, Result, TrashError};
+
+/// Start of every encrypted file, so other files are rejected instead of failing to decrypt
+const MAGIC: &[u8; 8] = b"TRASHENC";
+
+/// Size of the plaintext chunks. Every chunk is authenticated on its own, so big files
+/// never have to be held in memory
+const CHUNK_SIZE: usize = 64 * 1024;
+
+/// The poly1305 tag added to every chunk
+const TAG_SIZE: usize = 16;
+
+/// The STREAM construction uses 5 bytes of the 24 byte nonce for the chunk counter
+const NONCE_SIZE: usize = 19;
+
+/// A 256 bit key for encrypting trashed files
+#[derive(Clone)]
+pub struct Key([u8; 32]);
+
+impl fmt::Debug for Key {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("Key(..)")
+    }
+}
+
+impl Key {
+    /// Parses a key written as 64 hex characters, surrounding whitespace is ignored
+    pub fn from_hex(hex: &str) -> Result<Self> {
+        let hex = hex.trim();
+        if hex.len() != 64 || !hex.is_ascii() {
+            return Err(TrashError::Parse(
+                "A key has to be 64 hex characters".to_owned(),
+            ));
+        }
+
+        let mut key = [0; 32];
+        for (i, byte) in key.iter_mut().enumerate() {
+            *byte = u8::from_str_radix(&hex[i * 2..i * 2 + 2], 16)?;
+        }
+        Ok(Self(key))
+    }
+}
+
+/// Where the key for encrypting and decrypting trashed files comes from
+#[derive(Debug, Clone)]
+pub enum KeySource {
+    /// A file containing the key as hex
+    File(PathBuf),
+
+    /// The secret service keyring, the key is looked up as hex with
+    /// `secret-tool lookup application trash-cli`
+    Keyring,
+}
+
+impl KeySource {
+    pub fn load(&self) -> Result<Key> {
+        match self {
+            KeySource::File(path) => {
+                let content = fs::read_to_string(path)
+                    .context(format!("Failed to read key file {}", path.display()))?;
+                if let Ok(meta) = fs::metadata(path) {
+                    if meta.permissions().mode() & 0o077 != 0 {
+                        log::warn!("The key file {} is readable by others", path.display());
+                    }
+                }
+                Key::from_hex(&content).context(format!("Invalid key file {}", path.display()))
+            }
+            KeySource::Keyring => {
+                let output = Command::new("secret-tool")
+                    .args(["lookup", "application", "trash-cli"])
+                    .output()
+                    .context("Failed to run secret-tool")?;
+                if !output.status.success() || output.stdout.is_empty() {
+                    return Err(TrashError::NotFound(
+                        "No key for trash-cli found in the keyring".to_owned(),
+                    ));
+                }
+                Key::from_hex(&String::from_utf8(output.stdout)?)
+                    .context("Invalid key in the keyring")
+            }
+        }
+    }
+}
+
+/// Encrypts everything written to it with XChaCha20-Poly1305 in chunks,
+/// [`EncryptWriter::finish`] has to be called at the end
+pub struct EncryptWriter<W: Write> {
+    inner: W,
+    encryptor: EncryptorBE32<XChaCha20Poly1305>,
+    buf: Vec<u8>,
+}
+
+impl<W: Write> EncryptWriter<W> {
+    pub fn new(mut inner: W, key: &Key) -> io::Result<Self> {
+        let mut nonce = [0; NONCE_SIZE];
+        OsRng.fill_bytes(&mut nonce);
+        inner.write_all(MAGIC)?;
+        inner.write_all(&nonce)?;
+
+        let cipher = XChaCha20Poly1305::new((&key.0).into());
+        Ok(Self {
+            inner,
+            encryptor: EncryptorBE32::from_aead(cipher, (&nonce).into()),
+            buf: Vec::with_capacity(CHUNK_SIZE),
+        })
+    }
+
+    /// Writes the last chunk, which is marked as such so a truncated file fails to decrypt
+    pub fn finish(self) -> io::Result<W> {
+        let Self {
+            mut inner,
+            encryptor,
+            buf,
+        } = self;
+
+        let chunk = encryptor
+            .encrypt_last(buf.as_slice())
+            .map_err(|_| crypto_error())?;
+        inner.write_all(&chunk)?;
+        inner.flush()?;
+        Ok(inner)
+    }
+}
+
+impl<W: Write> Write for EncryptWriter<W> {
+    fn write(&mut self, data: &[u8]) -> io::Result<usize> {
+        if data.is_empty() {
+            return Ok(0);
+        }
+
+        // a full chunk is only written once it's known that it isn't the last one
+        if self.buf.len() == CHUNK_SIZE {
+            let chunk = self
+                .encryptor
+                .encrypt_next(self.buf.as_slice())
+                .map_err(|_| crypto_error())?;
+            self.inner.write_all(&chunk)?;
+            self.buf.clear();
+        }
+
+        let n = data.len().min(CHUNK_SIZE - self.buf.len());
+        self.buf.extend_from_slice(&data[..n]);
+        Ok(n)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.inner.flush()
+    }
+}
+
+/// Decrypts what was written by an [`EncryptWriter`]
+pub struct DecryptReader<R: Read> {
+    inner: BufReader<R>,
+
+    /// `None` once the last chunk was decrypted
+    decryptor: Option<DecryptorBE32<XChaCha20Poly1305>>,
+    plain: Vec<u8>,
+    pos: usize,
+}
+
+impl<R: Read> DecryptReader<R> {
+    pub fn new(inner: R, key: &Key) -> io::Result<Self> {
+        let mut inner = BufReader::new(inner);
+
+        let mut magic = [0; MAGIC.len()];
+        inner.read_exact(&mut magic)?;
+        if magic != *MAGIC {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                "Not an encrypted file",
+            ));
+        }
+        let mut nonce = [0; NONCE_SIZE];
+        inner.read_exact(&mut nonce)?;
+
+        let cipher = XChaCha20Poly1305::new((&key.0).into());
+        Ok(Self {
+            inner,
+            decryptor: Some(DecryptorBE32::from_aead(cipher, (&nonce).into())),
+            plain: vec![],
+            pos: 0,
+        })
+    }
+
+    fn next_chunk(&mut self) -> io::Result<()> {
+        let mut chunk = Vec::with_capacity(CHUNK_SIZE + TAG_SIZE);
+        (&mut self.inner)
+            .take((CHUNK_SIZE + TAG_SIZE) as u64)
+            .read_to_end(&mut chunk)?;
+        let last = self.inner.fill_buf()?.is_empty();
+
+        self.plain = match (last, self.decryptor.take()) {
+            (_, None) => Ok(vec![]),
+            (true, Some(decryptor)) => decryptor.decrypt_last(chunk.as_slice()),
+            (false, Some(mut decryptor)) => {
+                let plain = decryptor.decrypt_next(chunk.as_slice());
+                self.decryptor = Some(decryptor);
+                plain
+            }
+        }
+        .map_err(|_| crypto_error())?;
+        self.pos = 0;
+
+        Ok(())
+    }
+}
+
+impl<R: Read> Read for DecryptReader<R> {
+    fn read(&mut self, out: &mut [u8]) -> io::Result<usize> {
+        while self.pos == self.plain.len() {
+            if self.decryptor.is_none() {
+                return Ok(0);
+            }
+            self.next_chunk()?;
+        }
+
+        let n = out.len().min(self.plain.len() - self.pos);
+        out[..n].copy_from_slice(&self.plain[self.pos..self.pos + n]);
+        self.pos += n;
+        Ok(n)
+    }
+}
+
+fn crypto_error() -> io::Error {
+    io::Error::new(
+        io::ErrorKind::InvalidData,
+        "Failed to decrypt, the key is wrong or the file is corrupted",
+    )
+}
+
+#[test]
+fn test_roundtrip() {
+    let key = Key::from_hex(&"ab".repeat(32)).unwrap();
+
+    for len in [0, 1, CHUNK_SIZE, CHUNK_SIZE * 2 + 7] {
+        let data = (0..len).map(|x| x as u8).collect::<Vec<_>>();
+
+        let mut writer = EncryptWriter::new(vec![], &key).unwrap();
+        writer.write_all(&data).unwrap();
+        let encrypted = writer.finish().unwrap();
+
+        let mut decrypted = vec![];
+        DecryptReader::new(encrypted.as_slice(), &key)
+            .unwrap()
+            .read_to_end(&mut decrypted)
+            .unwrap();
+        assert_eq!(decrypted, data);
+
+        // a truncated file must not decrypt to a shorter file
+        let truncated = &encrypted[..encrypted.len() - 1];
+        let mut out = vec![];
+        assert!(DecryptReader::new(truncated, &key)
+            .unwrap()
+            .read_to_end(&mut out)
+            .is_err());
+    }
+}
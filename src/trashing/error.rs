@@ -0,0 +1,125 @@
+use std::{fmt::Display, io};
+
+/// Everything that can go wrong when working with the trash.
+///
+/// Errors usually carry context about what was being done (see [`TrashError::Context`]),
+/// use [`TrashError::root`] to get to the error that actually happened.
+#[derive(Debug, thiserror::Error)]
+pub enum TrashError {
+    /// Nothing in the trash (or on the filesystem) matches what was asked for
+    #[error("{0}")]
+    NotFound(String),
+
+    /// The operation clashes with something that already exists
+    #[error("{0}")]
+    Conflict(String),
+
+    /// A trash or trashinfo file doesn't follow the trash spec
+    #[error("{0}")]
+    SpecViolation(String),
+
+    /// The operation isn't possible on this file (system paths, read-only filesystems, ..)
+    #[error("{0}")]
+    Unsupported(String),
+
+    /// Trashing would exceed the quota of the trash
+    #[error("{0}")]
+    QuotaExceeded(String),
+
+    /// A callback (usually a prompt) declined the operation
+    #[error("Aborted by user")]
+    Aborted,
+
+    #[error("{0}")]
+    Parse(String),
+
+    #[error(transparent)]
+    Io(#[from] io::Error),
+
+    #[error(transparent)]
+    Json(#[from] serde_json::Error),
+
+    #[error(transparent)]
+    Portal(#[from] zbus::Error),
+
+    /// Something that none of the other kinds fit
+    #[error("{0}")]
+    Other(String),
+
+    /// `source` happened while doing `context`
+    #[error("{context}")]
+    Context {
+        context: String,
+        #[source]
+        source: Box<TrashError>,
+    },
+}
+
+impl TrashError {
+    /// The innermost error, without any context around it
+    pub fn root(&self) -> &TrashError {
+        match self {
+            TrashError::Context { source, .. } => source.root(),
+            other => other,
+        }
+    }
+}
+
+impl From<chrono::ParseError> for TrashError {
+    fn from(value: chrono::ParseError) -> Self {
+        Self::Parse(value.to_string())
+    }
+}
+
+impl From<std::num::ParseIntError> for TrashError {
+    fn from(value: std::num::ParseIntError) -> Self {
+        Self::Parse(value.to_string())
+    }
+}
+
+/// Only happens for paths outside of the top directory of a trash
+impl From<std::path::StripPrefixError> for TrashError {
+    fn from(value: std::path::StripPrefixError) -> Self {
+        Self::SpecViolation(value.to_string())
+    }
+}
+
+impl From<std::string::FromUtf8Error> for TrashError {
+    fn from(value: std::string::FromUtf8Error) -> Self {
+        Self::Parse(value.to_string())
+    }
+}
+
+pub type Result<T, E = TrashError> = std::result::Result<T, E>;
+
+/// Adds context to errors, like `anyhow::Context` does
+pub trait Context<T> {
+    fn context(self, context: impl Display) -> Result<T>;
+}
+
+impl<T, E: Into<TrashError>> Context<T> for std::result::Result<T, E> {
+    fn context(self, context: impl Display) -> Result<T> {
+        self.map_err(|e| TrashError::Context {
+            context: context.to_string(),
+            source: Box::new(e.into()),
+        })
+    }
+}
+
+/// A missing value is turned into [`TrashError::NotFound`] with the context as message
+impl<T> Context<T> for Option<T> {
+    fn context(self, context: impl Display) -> Result<T> {
+        self.ok_or_else(|| TrashError::NotFound(context.to_string()))
+    }
+}
+
+#[test]
+fn test_root() {
+    let err: Result<()> = Err(io::Error::from(io::ErrorKind::NotFound))
+        .context("Failed to read")
+        .context("Failed to list");
+
+    let err = err.unwrap_err();
+    assert_eq!(err.to_string(), "Failed to list");
+    assert!(matches!(err.root(), TrashError::Io(e) if e.kind() == io::ErrorKind::NotFound));
+}
@@ -0,0 +1,44 @@
+use std::path::Path;
+
+use glob::Pattern;
+use serde::Deserialize;
+
+/// What `put` does with files on a device it must not create a trash on
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ExclusionFallback {
+    /// Copy them into the home trash
+    #[default]
+    Home,
+
+    /// Don't trash them
+    Skip,
+}
+
+/// Devices `put` never creates a `.Trash-$uid` on, by mount point or filesystem UUID.
+/// Trashes that already exist on them are still used.
+#[derive(Debug, Clone, Default)]
+pub struct DeviceExclusions {
+    /// Globs matched against the mount point, like `/run/media/*/*`
+    pub mounts: Vec<Pattern>,
+
+    /// Filesystem UUIDs as in `/dev/disk/by-uuid`, compared case insensitively
+    pub uuids: Vec<String>,
+
+    pub fallback: ExclusionFallback,
+}
+
+impl DeviceExclusions {
+    /// Whether the device mounted at `mount_point` is excluded.
+    /// `uuid` is only asked for if there are UUIDs to compare against, looking it up isn't free
+    pub fn excludes(&self, mount_point: &Path, uuid: impl FnOnce() -> Option<String>) -> bool {
+        if self.mounts.iter().any(|x| x.matches_path(mount_point)) {
+            return true;
+        }
+        if self.uuids.is_empty() {
+            return false;
+        }
+
+        uuid().is_some_and(|uuid| self.uuids.iter().any(|x| x.eq_ignore_ascii_case(&uuid)))
+    }
+}
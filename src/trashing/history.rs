@@ -0,0 +1,246 @@
+use std::{
+    ffi::OsString,
+    fs,
+    path::{Path, PathBuf},
+};
+
+use rustc_hash::FxHashMap;
+
+use super::{
+    error::{Result, TrashError},
+    journal::{Action, JournalEntry, Via},
+    trashinfo::parse_trashinfo,
+    UnifiedTrash,
+};
+
+/// The journal lines of one operation that can be reverted (puts and restores), oldest first
+#[derive(Debug, Clone)]
+pub struct Operation {
+    pub entries: Vec<JournalEntry>,
+}
+
+/// What the journal says can be undone and redone, most recent last.
+///
+/// Removing and emptying can't be reverted and don't show up here. Reverting
+/// an operation on an entry that was removed since fails its checks instead.
+#[derive(Debug, Clone, Default)]
+pub struct History {
+    pub done: Vec<Operation>,
+    pub undone: Vec<Operation>,
+}
+
+impl History {
+    /// Replays the journal: undoing moves the last done operation to `undone`,
+    /// redoing moves it back and anything else that is done clears `undone`.
+    pub fn from_journal(entries: Vec<JournalEntry>) -> Self {
+        // lines of operations that ran at the same time can be mixed up
+        let mut ops: Vec<(Option<Via>, Vec<JournalEntry>)> = vec![];
+        let mut by_id: FxHashMap<String, usize> = FxHashMap::default();
+        for entry in entries {
+            if !matches!(entry.action, Action::Put | Action::Restore) {
+                continue;
+            }
+
+            match by_id.get(&entry.op).filter(|_| !entry.op.is_empty()) {
+                Some(i) => ops[*i].1.push(entry),
+                None => {
+                    by_id.insert(entry.op.clone(), ops.len());
+                    ops.push((entry.via, vec![entry]));
+                }
+            }
+        }
+
+        let mut history = Self::default();
+        for (via, entries) in ops {
+            let entries = cancel_rollbacks(entries);
+            if entries.is_empty() {
+                continue;
+            }
+
+            let op = Operation { entries };
+            match via {
+                None => {
+                    history.done.push(op);
+                    history.undone.clear();
+                }
+                Some(Via::Undo) => {
+                    take_reverted(&mut history.done, &op);
+                    history.undone.push(op);
+                }
+                Some(Via::Redo) => {
+                    take_reverted(&mut history.undone, &op);
+                    history.done.push(op);
+                }
+            }
+        }
+
+        history
+    }
+}
+
+/// Removes what `reverting` reverted from the last operation of `stack`, and the operation
+/// itself once nothing of it is left. An undo or redo that failed halfway only reverted some
+/// of its entries, the rest can still be undone (or redone) later
+fn take_reverted(stack: &mut Vec<Operation>, reverting: &Operation) {
+    let Some(last) = stack.last_mut() else {
+        return;
+    };
+    for entry in &reverting.entries {
+        // a file is restored to and trashed from its original path
+        if let Some(i) = last
+            .entries
+            .iter()
+            .rposition(|x| x.original_path == entry.original_path)
+        {
+            last.entries.remove(i);
+        }
+    }
+    if last.entries.is_empty() {
+        stack.pop();
+    }
+}
+
+/// A batch that failed halfway restores what it already trashed, those entries cancel out
+fn cancel_rollbacks(mut entries: Vec<JournalEntry>) -> Vec<JournalEntry> {
+    let mut i = 0;
+    while i < entries.len() {
+        let current = &entries[i];
+        let put = entries[..i].iter().rposition(|x| {
+            x.action == Action::Put
+                && x.trash == current.trash
+                && x.trash_filename == current.trash_filename
+        });
+
+        match put.filter(|_| current.action == Action::Restore) {
+            Some(put) => {
+                entries.remove(i);
+                entries.remove(put);
+                i -= 1;
+            }
+            None => i += 1,
+        }
+    }
+    entries
+}
+
+/// Reverts `op`, newest entry first: trashed files are restored and restored files are
+/// trashed again. Returns the paths that were restored and trashed (in that order).
+///
+/// Nothing is touched unless every entry is still how the operation left it.
+pub fn revert(trash: &UnifiedTrash, op: &Operation) -> Result<(Vec<PathBuf>, Vec<PathBuf>)> {
+    for entry in &op.entries {
+        check(trash, entry)?;
+    }
+
+    let (mut restored, mut trashed) = (vec![], vec![]);
+    for entry in op.entries.iter().rev() {
+        let original = Path::new(&entry.original_path);
+        if entry.action == Action::Put {
+            trash.restore(
+                |info| {
+                    info.trash.trash_path == Path::new(&entry.trash)
+                        && info.trash_filename == entry.trash_filename
+                },
+                // names are unique within a trash
+                |matched| &matched[0],
+                |_| false,
+            )?;
+            restored.push(original.to_path_buf());
+        } else {
+            trash.put(original, false, |_, _| false)?;
+            trashed.push(original.to_path_buf());
+        }
+    }
+
+    Ok((restored, trashed))
+}
+
+/// Makes sure `entry` can be reverted: a trashed entry has to be in the trash still
+/// with nothing at its original path, a restored file has to be where it was restored to
+fn check(trash: &UnifiedTrash, entry: &JournalEntry) -> Result<()> {
+    let original = Path::new(&entry.original_path);
+
+    if entry.action == Action::Restore {
+        if fs::symlink_metadata(original).is_err() {
+            return Err(TrashError::Conflict(format!(
+                "{} is gone since it was restored",
+                original.display()
+            )));
+        }
+        return Ok(());
+    }
+
+    // another file can have gotten the same name in the meantime
+    let mut name = entry.trash_filename.clone();
+    name.push(OsString::from(".trashinfo"));
+    let still_trashed = trash
+        .list_trashes()
+        .iter()
+        .find(|x| x.trash_path == Path::new(&entry.trash))
+        .and_then(|x| parse_trashinfo(&x.info_dir().join(&name), x).ok())
+        .is_some_and(|x| x.original_filepath == original);
+    if !still_trashed {
+        return Err(TrashError::Conflict(format!(
+            "{} is no longer in the trash",
+            original.display()
+        )));
+    }
+
+    if fs::symlink_metadata(original).is_ok() {
+        return Err(TrashError::Conflict(format!(
+            "Something exists at {} again",
+            original.display()
+        )));
+    }
+
+    Ok(())
+}
+
+#[test]
+fn test_history() {
+    let line = |op: &str, action, via, name: &str| JournalEntry {
+        at: Default::default(),
+        action,
+        op: op.to_owned(),
+        via,
+        uid: 1000,
+        original_path: OsString::from(format!("/home/user/{}", name)),
+        trash: OsString::from("/home/user/.local/share/Trash"),
+        trash_filename: OsString::from(name),
+    };
+
+    let history = History::from_journal(vec![
+        line("1", Action::Put, None, "a"),
+        line("1", Action::Put, None, "b"),
+        // a batch that was rolled back
+        line("2", Action::Put, None, "c"),
+        line("2", Action::Restore, None, "c"),
+        line("3", Action::Put, None, "d"),
+        line("4", Action::Restore, Some(Via::Undo), "d"),
+        line("5", Action::Remove, None, "e"),
+    ]);
+    assert_eq!(history.done.len(), 1);
+    assert_eq!(history.done[0].entries.len(), 2);
+    assert_eq!(history.undone.len(), 1);
+
+    // doing something new drops what could be redone
+    let mut lines = vec![
+        line("1", Action::Put, None, "a"),
+        line("2", Action::Restore, Some(Via::Undo), "a"),
+    ];
+    lines.push(line("3", Action::Put, None, "b"));
+    let history = History::from_journal(lines);
+    assert_eq!(history.done.len(), 1);
+    assert!(history.undone.is_empty());
+
+    // an undo that failed after restoring one of two files
+    let history = History::from_journal(vec![
+        line("1", Action::Put, None, "a"),
+        line("1", Action::Put, None, "b"),
+        line("2", Action::Restore, Some(Via::Undo), "b"),
+    ]);
+    assert_eq!(history.done.len(), 1);
+    assert_eq!(history.done[0].entries.len(), 1);
+    assert_eq!(history.done[0].entries[0].trash_filename, "a");
+    assert_eq!(history.undone.len(), 1);
+}
@@ -0,0 +1,435 @@
+use std::{
+    ffi::{OsStr, OsString},
+    fs::{self, File},
+    io::{self, Read},
+    path::{Component, Path, PathBuf},
+    sync::Arc,
+};
+
+use rustc_hash::FxHashMap;
+
+use super::{
+    copy_all,
+    error::{Context, Result, TrashError},
+    find_fs_root, move_path, remove_all,
+    trashinfo::{parse_trashinfo, parse_trashinfo_content},
+    LocalFs, Trash, Trashinfo, UnifiedTrash,
+};
+
+#[derive(Debug, Clone, Default)]
+pub struct ImportSummary {
+    pub imported: usize,
+
+    /// Entries that were incomplete or couldn't be parsed
+    pub skipped: usize,
+}
+
+/// Adds the entries of a trash directory or of an archive written by `export` (`.tar` or `.tar.zst`)
+/// to the trash they would have been trashed to originally. Entries get new names if theirs are taken.
+///
+/// The entries of a directory are copied, unless `move_entries` is set.
+pub fn import(trash: &UnifiedTrash, source: &Path, move_entries: bool) -> Result<ImportSummary> {
+    let meta = fs::metadata(source).context(format!("Failed to stat {}", source.display()))?;
+
+    if meta.is_dir() {
+        let source = source
+            .canonicalize()
+            .context("Failed to resolve source path")?;
+        if trash.list_trashes().iter().any(|x| x.trash_path == source) {
+            return Err(TrashError::Conflict(format!(
+                "{} is already in use as a trash",
+                source.display()
+            )));
+        }
+        import_dir(trash, &source, move_entries)
+    } else {
+        if move_entries {
+            return Err(TrashError::Unsupported(
+                "Only entries of a directory can be moved".to_owned(),
+            ));
+        }
+        import_archive(trash, source)
+    }
+}
+
+fn import_dir(trash: &UnifiedTrash, source: &Path, move_entries: bool) -> Result<ImportSummary> {
+    let source_trash = Arc::new(Trash {
+        is_home_trash: false,
+        is_admin_trash: false,
+        dev_root: find_fs_root(source).context("Failed to find mount point")?,
+        trash_path: source.to_path_buf(),
+        device: 0,
+    });
+
+    let mut summary = ImportSummary::default();
+    for entry in fs::read_dir(source_trash.info_dir()).context("Failed to read info dir")? {
+        let path = entry.context("Failed to read info dir")?.path();
+        if path.extension() != Some(OsStr::new("trashinfo")) {
+            continue;
+        }
+
+        let info = match parse_trashinfo(&path, &source_trash) {
+            Ok(v) => v,
+            Err(e) => {
+                log::warn!("Skipping {}: {:#}", path.display(), e);
+                summary.skipped += 1;
+                continue;
+            }
+        };
+
+        let files_path = source_trash.files_dir().join(&info.trash_filename);
+        if fs::symlink_metadata(&files_path).is_err() {
+            log::warn!("Skipping {}: no matching file", path.display());
+            summary.skipped += 1;
+            continue;
+        }
+
+        let target = trash.trash_for(&info.original_filepath);
+        let new_info = add_entry(&target, &info)?;
+        let target_path = target.files_dir().join(&new_info.trash_filename);
+
+        // both clean up after themselves, without touching anything that was at the target
+        let result = if move_entries {
+            move_path(&files_path, &target_path)
+        } else {
+            copy_new(&files_path, &target_path)
+        };
+
+        if let Err(e) = result {
+            // don't leave behind a trashinfo file without a file
+            let _ = fs::remove_file(target.info_dir().join(&new_info.trash_filename_trashinfo));
+            return Err(e).context(format!("Failed to import {}", files_path.display()));
+        }
+
+        if move_entries {
+            fs::remove_file(&path).context("Failed to remove imported trashinfo file")?;
+        }
+
+        summary.imported += 1;
+    }
+
+    Ok(summary)
+}
+
+fn import_archive(trash: &UnifiedTrash, source: &Path) -> Result<ImportSummary> {
+    // the trashinfo files in exported archives are always absolute
+    let source_trash = Arc::new(Trash {
+        is_home_trash: true,
+        is_admin_trash: false,
+        dev_root: PathBuf::from("/"),
+        trash_path: source.to_path_buf(),
+        device: 0,
+    });
+
+    // the trashinfo files come after the files, but they are needed to know where the files go,
+    // so the archive is read twice
+    let mut infos = FxHashMap::default();
+    let mut unparsable = 0;
+    for entry in open_archive(source)?
+        .entries()
+        .context("Failed to read archive")?
+    {
+        let mut entry = entry.context("Failed to read archive")?;
+        let path = entry
+            .path()
+            .context("Invalid path in archive")?
+            .into_owned();
+        let Ok(info_name) = path.strip_prefix("info") else {
+            continue;
+        };
+
+        let mut content = String::new();
+        entry
+            .read_to_string(&mut content)
+            .context(format!("Failed to read {}", path.display()))?;
+
+        match parse_trashinfo_content(&content, info_name, &source_trash) {
+            Ok(v) => {
+                infos.insert(v.trash_filename.clone(), v);
+            }
+            Err(e) => {
+                log::warn!("Skipping {}: {:#}", path.display(), e);
+                unparsable += 1;
+            }
+        }
+    }
+
+    let mut summary = ImportSummary {
+        skipped: infos.len() + unparsable,
+        ..Default::default()
+    };
+
+    // archive name -> where it is unpacked to and its trashinfo file
+    let mut targets: FxHashMap<OsString, (PathBuf, PathBuf)> = FxHashMap::default();
+    for entry in open_archive(source)?
+        .entries()
+        .context("Failed to read archive")?
+    {
+        let mut entry = entry.context("Failed to read archive")?;
+        let path = entry
+            .path()
+            .context("Invalid path in archive")?
+            .into_owned();
+        let Ok(rest) = path.strip_prefix("files") else {
+            continue;
+        };
+
+        let mut components = rest.components();
+        let Some(Component::Normal(name)) = components.next() else {
+            continue;
+        };
+        let inner = components.as_path().to_path_buf();
+
+        let (target, info_path) = match targets.get(name) {
+            Some(v) => v.clone(),
+            None => {
+                let Some(info) = infos.get(name) else {
+                    log::warn!("Skipping {}: no matching trashinfo file", path.display());
+                    continue;
+                };
+
+                let target_trash = trash.trash_for(&info.original_filepath);
+                let new_info = add_entry(&target_trash, info)?;
+                let target = target_trash.files_dir().join(&new_info.trash_filename);
+                let info_path = target_trash
+                    .info_dir()
+                    .join(&new_info.trash_filename_trashinfo);
+
+                // only what was unpacked here is removed again if unpacking fails
+                if fs::symlink_metadata(&target).is_ok() {
+                    let _ = fs::remove_file(&info_path);
+                    return Err(TrashError::Conflict(format!(
+                        "{} is in the way",
+                        target.display()
+                    )));
+                }
+
+                targets.insert(name.to_os_string(), (target.clone(), info_path.clone()));
+                summary.imported += 1;
+                summary.skipped -= 1;
+                (target, info_path)
+            }
+        };
+
+        if let Err(e) = unpack_entry(&mut entry, &target, &inner, &path) {
+            // don't leave behind a trashinfo file without a (complete) file
+            let _ = remove_all(&target);
+            let _ = fs::remove_file(&info_path);
+            return Err(e);
+        }
+    }
+
+    Ok(summary)
+}
+
+/// Copies `from` to `to`, which has to be free, and removes the partial copy if that fails
+fn copy_new(from: &Path, to: &Path) -> io::Result<()> {
+    if fs::symlink_metadata(to).is_ok() {
+        return Err(io::ErrorKind::AlreadyExists.into());
+    }
+    copy_all(from, to).inspect_err(|_| {
+        let _ = remove_all(to);
+    })
+}
+
+/// Unpacks one member of the files of an entry to `inner` below the entry's `target`.
+/// Only what `export` writes is unpacked, and never through a symlink that an earlier
+/// member of the archive created, which could point anywhere
+fn unpack_entry(
+    entry: &mut tar::Entry<Box<dyn Read>>,
+    target: &Path,
+    inner: &Path,
+    path: &Path,
+) -> Result<()> {
+    let supported = matches!(
+        entry.header().entry_type(),
+        tar::EntryType::Regular | tar::EntryType::Directory | tar::EntryType::Symlink
+    );
+    let normal = inner
+        .components()
+        .all(|x| matches!(x, Component::Normal(_)));
+    if !supported || !normal {
+        return Err(TrashError::Unsupported(format!(
+            "Refusing to unpack {}",
+            path.display()
+        )));
+    }
+
+    let dest = match inner.as_os_str().is_empty() {
+        true => target.to_path_buf(),
+        false => target.join(inner),
+    };
+    let through_symlink = dest
+        .ancestors()
+        .take_while(|x| x.starts_with(target))
+        .any(|x| fs::symlink_metadata(x).is_ok_and(|x| x.is_symlink()));
+    if through_symlink {
+        return Err(TrashError::Unsupported(format!(
+            "Refusing to unpack {} through a symlink",
+            path.display()
+        )));
+    }
+
+    if let Some(parent) = dest.parent() {
+        fs::create_dir_all(parent).context("Failed to create directory")?;
+    }
+    entry
+        .unpack(&dest)
+        .context(format!("Failed to unpack {}", path.display()))?;
+    Ok(())
+}
+
+/// Writes a trashinfo file for `info` into the `target` trash
+fn add_entry(target: &Arc<Trash>, info: &Trashinfo) -> Result<Trashinfo> {
+    let mut new_info = Trashinfo {
+        trash: Arc::clone(target),
+        trash_filename: info.trash_filename.clone(),
+        trash_filename_trashinfo: info.trash_filename_trashinfo.clone(),
+        deleted_at: info.deleted_at,
+        original_filepath: info.original_filepath.clone(),
+        metadata: info.metadata.clone(),
+    };
+
+    target
+        .add_trashinfo(&LocalFs, &mut new_info)
+        .context(format!("Failed to add to {}", target.trash_path.display()))?;
+
+    Ok(new_info)
+}
+
+fn open_archive(path: &Path) -> Result<tar::Archive<Box<dyn Read>>> {
+    let file = File::open(path).context(format!("Failed to open {}", path.display()))?;
+
+    let reader: Box<dyn Read> = if path.extension() == Some(OsStr::new("zst")) {
+        Box::new(zstd::Decoder::new(file).context("Failed to start decompression")?)
+    } else {
+        Box::new(file)
+    };
+
+    Ok(tar::Archive::new(reader))
+}
+
+#[test]
+fn test_import_dir() {
+    let dir = crate::test::TempDir::new("import-dir");
+    let source = dir.join("source");
+    fs::create_dir_all(source.join("files")).unwrap();
+    fs::create_dir_all(source.join("info")).unwrap();
+    fs::write(source.join("files/a"), "content").unwrap();
+    let trashinfo = format!(
+        "[Trash Info]\nPath={}\nDeletionDate=2024-01-01T00:00:00\n",
+        dir.join("a").display()
+    );
+    fs::write(source.join("info/a.trashinfo"), &trashinfo).unwrap();
+    // without a file
+    fs::write(source.join("info/b.trashinfo"), &trashinfo).unwrap();
+
+    let trash = UnifiedTrash::in_dir(&dir.join("trash")).unwrap();
+    let summary = import_dir(&trash, &source, false).unwrap();
+    assert_eq!((summary.imported, summary.skipped), (1, 1));
+    assert!(source.join("files/a").exists());
+
+    let summary = import_dir(&trash, &source, true).unwrap();
+    assert_eq!((summary.imported, summary.skipped), (1, 1));
+    assert!(!source.join("files/a").exists());
+    assert!(!source.join("info/a.trashinfo").exists());
+
+    let entries = trash.list().unwrap();
+    assert_eq!(entries.len(), 2);
+    for entry in entries {
+        assert_eq!(entry.original_filepath, dir.join("a"));
+        let files_path = entry.trash.files_dir().join(&entry.trash_filename);
+        assert_eq!(fs::read_to_string(files_path).unwrap(), "content");
+    }
+}
+
+#[test]
+fn test_import_archive() {
+    let dir = crate::test::TempDir::new("import-archive");
+    fs::create_dir_all(dir.join("a/nested")).unwrap();
+    fs::write(dir.join("a/nested/file"), "content").unwrap();
+    std::os::unix::fs::symlink("nested/file", dir.join("a/link")).unwrap();
+
+    let trash = UnifiedTrash::in_dir(&dir.join("trash")).unwrap();
+    trash.put(&dir.join("a"), false, |_, _| false).unwrap();
+    let output = dir.join("export.tar.zst");
+    super::export(
+        &trash.list().unwrap(),
+        &super::ExportOptions {
+            output: output.clone(),
+            volume_size: None,
+            rate_limit: None,
+            resume: false,
+            now: trash.now(),
+        },
+    )
+    .unwrap();
+
+    let other = UnifiedTrash::in_dir(&dir.join("other")).unwrap();
+    let summary = import_archive(&other, &output).unwrap();
+    assert_eq!((summary.imported, summary.skipped), (1, 0));
+
+    let entries = other.list().unwrap();
+    assert_eq!(entries.len(), 1);
+    assert_eq!(entries[0].original_filepath, dir.join("a"));
+    let files_path = entries[0]
+        .trash
+        .files_dir()
+        .join(&entries[0].trash_filename);
+    assert_eq!(
+        fs::read_to_string(files_path.join("nested/file")).unwrap(),
+        "content"
+    );
+    assert_eq!(
+        fs::read_link(files_path.join("link")).unwrap(),
+        Path::new("nested/file")
+    );
+}
+
+#[test]
+fn test_import_archive_through_symlink() {
+    let dir = crate::test::TempDir::new("import-symlink");
+    let outside = dir.join("outside");
+    fs::create_dir_all(&outside).unwrap();
+
+    // a symlink out of the entry, followed by a file that would be written through it
+    let output = dir.join("crafted.tar");
+    let mut builder = tar::Builder::new(File::create(&output).unwrap());
+    let mut header = tar::Header::new_gnu();
+    header.set_entry_type(tar::EntryType::Symlink);
+    header.set_size(0);
+    builder
+        .append_link(&mut header, "files/evil/link", &outside)
+        .unwrap();
+    let mut header = tar::Header::new_gnu();
+    header.set_size(5);
+    header.set_mode(0o644);
+    builder
+        .append_data(&mut header, "files/evil/link/file", &b"owned"[..])
+        .unwrap();
+    let trashinfo = format!(
+        "[Trash Info]\nPath={}\nDeletionDate=2024-01-01T00:00:00\n",
+        dir.join("evil").display()
+    );
+    let mut header = tar::Header::new_gnu();
+    header.set_size(trashinfo.len() as u64);
+    header.set_mode(0o600);
+    builder
+        .append_data(&mut header, "info/evil.trashinfo", trashinfo.as_bytes())
+        .unwrap();
+    builder.finish().unwrap();
+    drop(builder);
+
+    let trash = UnifiedTrash::in_dir(&dir.join("trash")).unwrap();
+    assert!(matches!(
+        import_archive(&trash, &output),
+        Err(TrashError::Unsupported(_))
+    ));
+
+    assert!(!outside.join("file").exists());
+    assert!(trash.list().unwrap().is_empty());
+    assert!(fs::read_dir(dir.join("trash/files"))
+        .unwrap()
+        .next()
+        .is_none());
+}
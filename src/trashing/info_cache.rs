@@ -0,0 +1,137 @@
+use std::{
+    ffi::OsString,
+    fs::{self, Metadata},
+    os::unix::{ffi::OsStrExt, fs::MetadataExt},
+    path::{Path, PathBuf},
+    sync::Arc,
+};
+
+use chrono::NaiveDateTime;
+use rustc_hash::{FxHashMap, FxHashSet};
+use serde::{Deserialize, Serialize};
+
+use super::{
+    cache_dir,
+    error::{Context, Result},
+    trashinfo, OriginalMetadata, Trash, Trashinfo,
+};
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct CachedInfo {
+    /// Used to tell if the trashinfo file changed since it was parsed
+    mtime: i64,
+    mtime_nsec: i64,
+    size: u64,
+
+    deleted_at: NaiveDateTime,
+    original_filepath: OsString,
+    #[serde(default)]
+    metadata: OriginalMetadata,
+}
+
+/// Parsed trashinfo files, keyed by the (url encoded) path of the trashinfo file.
+///
+/// Listing a big trash is dominated by reading and parsing every single trashinfo file,
+/// with the cache only a stat is needed for files that didn't change since the last run.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct InfoCache {
+    entries: FxHashMap<String, CachedInfo>,
+
+    #[serde(skip)]
+    seen: FxHashSet<String>,
+
+    #[serde(skip)]
+    dirty: bool,
+}
+
+impl InfoCache {
+    /// Where the cache is stored on disk
+    pub fn location() -> Result<PathBuf> {
+        Ok(cache_dir()?.join("trashinfo-cache.json"))
+    }
+
+    /// Loads the cache from disk. A missing or broken cache is just an empty one.
+    pub fn load() -> Self {
+        let cache = Self::location().and_then(|location| {
+            let raw = fs::read(location)?;
+            Ok(serde_json::from_slice::<Self>(&raw)?)
+        });
+
+        match cache {
+            Ok(v) => v,
+            Err(e) => {
+                log::debug!("Not using trashinfo cache: {:#}", e);
+                Self::default()
+            }
+        }
+    }
+
+    /// Writes the cache back to disk if anything changed, dropping entries that
+    /// were not looked up since it was loaded.
+    pub fn save(&mut self) -> Result<()> {
+        let before = self.entries.len();
+        self.entries.retain(|k, _| self.seen.contains(k));
+        if !self.dirty && before == self.entries.len() {
+            return Ok(());
+        }
+
+        let location = Self::location()?;
+        if let Some(parent) = location.parent() {
+            fs::create_dir_all(parent).context("Failed to create cache dir")?;
+        }
+
+        // other processes may read the cache at any time, so it is replaced atomically
+        let mut tmp_path = location.clone().into_os_string();
+        tmp_path.push(format!(".{}.tmp", std::process::id()));
+
+        let serialized = serde_json::to_vec(self).context("Failed to serialize trashinfo cache")?;
+        fs::write(&tmp_path, serialized).context("Failed to write trashinfo cache")?;
+        fs::rename(&tmp_path, &location)
+            .context(format!("Failed to replace {}", location.display()))?;
+
+        self.dirty = false;
+        Ok(())
+    }
+
+    /// Parses the trashinfo file at `path`, unless an up to date result is cached.
+    /// `meta` is the (not followed) metadata of that file.
+    pub fn parse(&mut self, path: &Path, meta: &Metadata, trash: &Arc<Trash>) -> Result<Trashinfo> {
+        let key = urlencoding::encode_binary(path.as_os_str().as_bytes()).into_owned();
+
+        if let Some(cached) = self.entries.get(&key) {
+            if cached.mtime == meta.mtime()
+                && cached.mtime_nsec == meta.mtime_nsec()
+                && cached.size == meta.size()
+            {
+                let trash_filename_trashinfo = path.file_name().context("No file name")?;
+                let info = Trashinfo {
+                    trash: Arc::clone(trash),
+                    trash_filename: path.file_stem().context("no file name")?.into(),
+                    trash_filename_trashinfo: trash_filename_trashinfo.to_os_string(),
+                    deleted_at: cached.deleted_at,
+                    original_filepath: PathBuf::from(&cached.original_filepath),
+                    metadata: cached.metadata.clone(),
+                };
+                self.seen.insert(key);
+                return Ok(info);
+            }
+        }
+
+        let info = trashinfo::parse_trashinfo(path, trash)?;
+        self.entries.insert(
+            key.clone(),
+            CachedInfo {
+                mtime: meta.mtime(),
+                mtime_nsec: meta.mtime_nsec(),
+                size: meta.size(),
+                deleted_at: info.deleted_at,
+                original_filepath: info.original_filepath.clone().into_os_string(),
+                metadata: info.metadata.clone(),
+            },
+        );
+        self.seen.insert(key);
+        self.dirty = true;
+
+        Ok(info)
+    }
+}
@@ -0,0 +1,247 @@
+use std::{
+    env,
+    ffi::OsString,
+    fmt::Display,
+    fs::{self, OpenOptions},
+    io::{self, Write},
+    os::unix::fs::OpenOptionsExt,
+    path::PathBuf,
+    sync::atomic::{AtomicU64, Ordering},
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+use chrono::{NaiveDateTime, SubsecRound};
+use serde::{Deserialize, Serialize};
+
+use super::{
+    error::{Context, Result},
+    Trashinfo,
+};
+
+/// What was done to an entry
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Action {
+    Put,
+    Restore,
+    Remove,
+    Empty,
+}
+
+impl Display for Action {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(match self {
+            Action::Put => "put",
+            Action::Restore => "restore",
+            Action::Remove => "remove",
+            Action::Empty => "empty",
+        })
+    }
+}
+
+/// Why an operation was done, if it wasn't asked for directly
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Via {
+    Undo,
+    Redo,
+}
+
+/// One line of the journal. Paths are plain strings, unless they aren't valid UTF-8
+/// (see [`lossless`])
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct JournalEntry {
+    /// Local time
+    pub at: NaiveDateTime,
+    pub action: Action,
+
+    /// Lines with the same id belong to the same operation (like trashing several files at once)
+    #[serde(default)]
+    pub op: String,
+
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub via: Option<Via>,
+
+    /// The user that did it, which isn't the owner of the trash with `--uid`
+    pub uid: u32,
+
+    #[serde(with = "lossless")]
+    pub original_path: OsString,
+
+    /// Path of the trash (not the `files` dir) the entry is in, or was in
+    #[serde(with = "lossless")]
+    pub trash: OsString,
+
+    /// Name of the entry inside the trash
+    #[serde(with = "lossless")]
+    pub trash_filename: OsString,
+}
+
+/// Unique for every operation, even several in the same process
+fn new_op_id() -> String {
+    static COUNTER: AtomicU64 = AtomicU64::new(0);
+
+    let nanos = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map_or(0, |x| x.as_nanos());
+    format!(
+        "{:x}-{}-{}",
+        nanos,
+        std::process::id(),
+        COUNTER.fetch_add(1, Ordering::Relaxed)
+    )
+}
+
+/// Keeps the journal readable (and greppable) while still being able to store any path:
+/// valid UTF-8 is written as a string, anything else as `{"bytes": [..]}`
+mod lossless {
+    use std::{
+        ffi::{OsStr, OsString},
+        os::unix::ffi::OsStrExt,
+    };
+
+    use serde::{Deserialize, Deserializer, Serialize, Serializer};
+
+    #[derive(Serialize, Deserialize)]
+    #[serde(untagged)]
+    enum Repr<'a> {
+        Utf8(&'a str),
+        Bytes { bytes: Vec<u8> },
+        // strings with escapes can't be borrowed
+        Owned(String),
+    }
+
+    pub fn serialize<S: Serializer>(value: &OsString, serializer: S) -> Result<S::Ok, S::Error> {
+        match value.to_str() {
+            Some(v) => Repr::Utf8(v),
+            None => Repr::Bytes {
+                bytes: value.as_bytes().to_vec(),
+            },
+        }
+        .serialize(serializer)
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(deserializer: D) -> Result<OsString, D::Error> {
+        Ok(match Repr::deserialize(deserializer)? {
+            Repr::Utf8(v) => v.into(),
+            Repr::Owned(v) => v.into(),
+            Repr::Bytes { bytes } => OsStr::from_bytes(&bytes).to_os_string(),
+        })
+    }
+}
+
+/// An append-only log of every put, restore, remove and empty, one JSON object per line
+#[derive(Debug, Clone)]
+pub struct Journal {
+    path: PathBuf,
+
+    /// Id of the operation everything recorded belongs to
+    op: String,
+    via: Option<Via>,
+}
+
+impl Journal {
+    pub fn new(path: PathBuf) -> Self {
+        Self {
+            path,
+            op: new_op_id(),
+            via: None,
+        }
+    }
+
+    /// The same journal, but recording into a new operation that was done because of `via`
+    pub fn next_op(&self, via: Option<Via>) -> Self {
+        Self {
+            path: self.path.clone(),
+            op: new_op_id(),
+            via,
+        }
+    }
+
+    /// The journal in `$XDG_STATE_HOME/trash-cli/journal.jsonl`
+    pub fn open_default() -> Result<Self> {
+        let state_home = match env::var("XDG_STATE_HOME") {
+            Ok(v) => PathBuf::from(v),
+            Err(_) => PathBuf::from(env::var_os("HOME").context("No home dir set!")?)
+                .join(".local")
+                .join("state"),
+        };
+        Ok(Self::new(
+            state_home.join("trash-cli").join("journal.jsonl"),
+        ))
+    }
+
+    pub fn path(&self) -> &PathBuf {
+        &self.path
+    }
+
+    /// Appends a line for `action` on `info`. Lines are written with a single `write`
+    /// on a file opened with `O_APPEND`, so concurrent writers don't mix up lines.
+    pub fn record(&self, action: Action, info: &Trashinfo, at: NaiveDateTime) -> Result<()> {
+        let entry = JournalEntry {
+            // trashinfo files don't have more than seconds either
+            at: at.trunc_subsecs(0),
+            action,
+            op: self.op.clone(),
+            via: self.via,
+            uid: rustix::process::getuid().as_raw(),
+            original_path: info.original_filepath.clone().into_os_string(),
+            trash: info.trash.trash_path.clone().into_os_string(),
+            trash_filename: info.trash_filename.clone(),
+        };
+        let mut line = serde_json::to_vec(&entry)?;
+        line.push(b'\n');
+
+        if let Some(parent) = self.path.parent() {
+            fs::create_dir_all(parent).context("Failed to create state dir")?;
+        }
+        OpenOptions::new()
+            .append(true)
+            .create(true)
+            .mode(0o600)
+            .open(&self.path)
+            .and_then(|mut x| x.write_all(&line))
+            .context(format!("Failed to write to {}", self.path.display()))
+    }
+
+    /// Every entry of the journal, oldest first. Lines that can't be parsed are skipped
+    pub fn read(&self) -> Result<Vec<JournalEntry>> {
+        let content = match fs::read_to_string(&self.path) {
+            Ok(v) => v,
+            Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(vec![]),
+            Err(e) => return Err(e).context(format!("Failed to read {}", self.path.display())),
+        };
+
+        let mut entries = vec![];
+        for (i, line) in content.lines().enumerate() {
+            match serde_json::from_str(line) {
+                Ok(v) => entries.push(v),
+                Err(e) => log::warn!("Skipping line {} of the journal: {}", i + 1, e),
+            }
+        }
+        Ok(entries)
+    }
+}
+
+#[test]
+fn test_lossless_paths() {
+    use std::os::unix::ffi::OsStrExt;
+
+    let entry = JournalEntry {
+        at: NaiveDateTime::default(),
+        action: Action::Put,
+        op: String::new(),
+        via: None,
+        uid: 1000,
+        original_path: OsString::from("/home/user/\"quoted\""),
+        trash: std::ffi::OsStr::from_bytes(b"/mnt/\xff").to_os_string(),
+        trash_filename: OsString::from("quoted"),
+    };
+
+    let json = serde_json::to_string(&entry).unwrap();
+    assert!(json.contains(r#""trash":{"bytes":[47,109,110,116,47,255]}"#));
+
+    let parsed: JournalEntry = serde_json::from_str(&json).unwrap();
+    assert_eq!(parsed.original_path, entry.original_path);
+    assert_eq!(parsed.trash, entry.trash);
+}
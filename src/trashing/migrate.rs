@@ -0,0 +1,248 @@
+use std::{
+    ffi::{OsStr, OsString},
+    fs::{self, OpenOptions},
+    io::Write,
+    os::unix::{
+        ffi::{OsStrExt, OsStringExt},
+        fs::OpenOptionsExt,
+    },
+    path::{Path, PathBuf},
+    sync::Arc,
+};
+
+use chrono::NaiveDateTime;
+
+use super::{
+    error::{Context, Result, TrashError},
+    trashinfo::{parse_deletion_date, DELETION_DATE_FORMAT},
+    OriginalMetadata, Trash, Trashinfo, UnifiedTrash,
+};
+
+/// What was changed about a single trashinfo file
+#[derive(Debug, Clone)]
+pub struct Adjustment {
+    pub path: PathBuf,
+    pub changes: Vec<String>,
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct Migration {
+    /// Number of trashinfo files that were looked at
+    pub checked: usize,
+    pub adjusted: Vec<Adjustment>,
+}
+
+/// Finds trashinfo files with quirks of the python trash-cli (or other older implementations)
+/// and rewrites them in the form this program writes them.
+///
+/// This covers unencoded paths, absolute paths in trashes outside of `$HOME`, other datetime
+/// formats, CRLF line endings, duplicate keys and info files missing the `.trashinfo` extension.
+/// Unknown keys (other than the ones of [`OriginalMetadata`]) are dropped from adjusted files. Nothing is written if `dry_run` is set.
+pub fn migrate_trash_cli(trash: &UnifiedTrash, dry_run: bool) -> Result<Migration> {
+    let mut migration = Migration::default();
+
+    for trash in trash.list_trashes() {
+        for entry in fs::read_dir(trash.info_dir()).context("Failed to read info dir")? {
+            let path = entry.context("Failed to read info dir")?.path();
+            let Some(file_name) = path.file_name().map(OsStr::to_os_string) else {
+                continue;
+            };
+
+            let mut changes = vec![];
+            let info_name = match file_name.as_bytes().strip_suffix(b".trashinfo") {
+                Some(v) => OsStr::from_bytes(v).to_os_string(),
+                None if trash.files_dir().join(&file_name).exists() => {
+                    changes.push("added missing .trashinfo extension".to_owned());
+                    file_name
+                }
+                // not a trashinfo file
+                None => continue,
+            };
+
+            migration.checked += 1;
+
+            let raw = fs::read(&path).context(format!("Failed to read {}", path.display()))?;
+            let info = match normalize(&raw, trash, &info_name, &mut changes) {
+                Ok(v) => v,
+                Err(e) => {
+                    log::warn!("Not migrating {}: {:#}", path.display(), e);
+                    continue;
+                }
+            };
+
+            if changes.is_empty() {
+                continue;
+            }
+
+            if !dry_run {
+                replace_trashinfo(&path, &info)
+                    .context(format!("Failed to rewrite {}", path.display()))?;
+            }
+
+            migration.adjusted.push(Adjustment { path, changes });
+        }
+    }
+
+    Ok(migration)
+}
+
+/// Leniently parses the `raw` trashinfo file, recording every difference from the
+/// canonical form in `changes`
+fn normalize(
+    raw: &[u8],
+    trash: &Arc<Trash>,
+    info_name: &OsStr,
+    changes: &mut Vec<String>,
+) -> Result<Trashinfo> {
+    if raw.contains(&b'\r') {
+        changes.push("converted CRLF line endings".to_owned());
+    }
+
+    let mut lines = raw
+        .split(|x| *x == b'\n')
+        .map(|x| x.strip_suffix(b"\r").unwrap_or(x))
+        .filter(|x| !x.is_empty());
+
+    if lines.next() != Some(b"[Trash Info]".as_slice()) {
+        return Err(TrashError::SpecViolation("invalid first line".to_owned()));
+    }
+
+    let mut path = None;
+    let mut deletion_date = None;
+    let mut metadata = OriginalMetadata::default();
+    for line in lines {
+        let (key, value) = match line.iter().position(|x| *x == b'=') {
+            Some(i) => (&line[..i], &line[i + 1..]),
+            None => continue,
+        };
+
+        let slot = match key {
+            b"Path" => &mut path,
+            b"DeletionDate" => &mut deletion_date,
+            _ => {
+                metadata.parse_key(
+                    &String::from_utf8_lossy(key),
+                    &String::from_utf8_lossy(value),
+                );
+                continue;
+            }
+        };
+
+        if slot.is_some() {
+            changes.push(format!(
+                "removed duplicate {} key",
+                String::from_utf8_lossy(key)
+            ));
+        } else {
+            *slot = Some(value);
+        }
+    }
+
+    let raw_path = path.context("no Path entry")?;
+    let path = if is_unencoded(raw_path) {
+        changes.push("url encoded Path".to_owned());
+        raw_path.to_vec()
+    } else {
+        urlencoding::decode_binary(raw_path).into_owned()
+    };
+    let path = PathBuf::from(OsString::from_vec(path));
+
+    let original_filepath = if path.is_relative() {
+        trash.dev_root.join(path)
+    } else {
+        if !trash.is_home_trash && path.starts_with(&trash.dev_root) {
+            changes.push(format!(
+                "made Path relative to {}",
+                trash.dev_root.display()
+            ));
+        }
+        path
+    };
+
+    let raw_date = String::from_utf8_lossy(deletion_date.context("no DeletionDate entry")?);
+    let deleted_at = parse_deletion_date(&raw_date)
+        .or_else(|_| parse_legacy_date(&raw_date))
+        .context("invalid DeletionDate")?;
+
+    let canonical_date = deleted_at.format(DELETION_DATE_FORMAT).to_string();
+    if raw_date != canonical_date {
+        changes.push(format!(
+            "changed DeletionDate from {} to {}",
+            raw_date, canonical_date
+        ));
+    }
+
+    let mut trash_filename_trashinfo = info_name.to_os_string();
+    trash_filename_trashinfo.push(".trashinfo");
+
+    Ok(Trashinfo {
+        trash: Arc::clone(trash),
+        trash_filename: info_name.to_os_string(),
+        trash_filename_trashinfo,
+        deleted_at,
+        original_filepath,
+        metadata,
+    })
+}
+
+/// Replaces the trashinfo file at `old_path` with the canonical form of `info`
+pub fn replace_trashinfo(old_path: &Path, info: &Trashinfo) -> Result<()> {
+    let content = match info.trashinfo_file_relative(&info.trash.dev_root) {
+        Ok(v) if !info.trash.is_home_trash => v,
+        _ => info.trashinfo_file_abs(),
+    };
+
+    let new_path = info.trash.info_dir().join(&info.trash_filename_trashinfo);
+
+    // write to a file that is ignored by every implementation first, so that the
+    // trashinfo file is never seen half written
+    let mut tmp_name = OsString::from(".");
+    tmp_name.push(&info.trash_filename_trashinfo);
+    tmp_name.push(".tmp");
+    let tmp_path = info.trash.info_dir().join(tmp_name);
+
+    let mut file = OpenOptions::new()
+        .write(true)
+        .create_new(true)
+        .mode(0o600)
+        .open(&tmp_path)
+        .context("Failed to create temporary file")?;
+    file.write_all(content.as_bytes())
+        .context("Failed to write temporary file")?;
+
+    fs::rename(&tmp_path, &new_path).context("Failed to replace trashinfo file")?;
+    if old_path != new_path {
+        fs::remove_file(old_path).context("Failed to remove old trashinfo file")?;
+    }
+
+    Ok(())
+}
+
+/// Whether the `Path` value was written without url encoding
+fn is_unencoded(raw: &[u8]) -> bool {
+    let invalid_escape = raw.iter().enumerate().any(|(i, x)| {
+        *x == b'%'
+            && !(raw.get(i + 1).is_some_and(u8::is_ascii_hexdigit)
+                && raw.get(i + 2).is_some_and(u8::is_ascii_hexdigit))
+    });
+
+    invalid_escape
+        || raw
+            .iter()
+            .any(|x| *x == b' ' || x.is_ascii_control() || !x.is_ascii())
+}
+
+/// Formats written by older versions of various implementations
+fn parse_legacy_date(input: &str) -> Result<NaiveDateTime, chrono::ParseError> {
+    NaiveDateTime::parse_from_str(input.trim(), "%Y-%m-%d %H:%M:%S%.f")
+        .or_else(|_| NaiveDateTime::parse_from_str(input.trim(), "%Y-%m-%dT%H:%M:%S%.f"))
+}
+
+#[test]
+fn test_is_unencoded() {
+    assert!(!is_unencoded(b"%2Fhome%2Fuser%2Ffile%20name"));
+    assert!(!is_unencoded(b"/home/user/file"));
+    assert!(is_unencoded(b"/home/user/file name"));
+    assert!(is_unencoded(b"/home/user/100%"));
+    assert!(is_unencoded("/home/user/fïle".as_bytes()));
+}
@@ -1,30 +1,96 @@
-use anyhow::Context;
+use error::{Context, Result};
 use std::{
     env,
-    ffi::OsStr,
-    fs,
-    os::unix::{ffi::OsStrExt, fs::MetadataExt},
+    ffi::{CStr, OsStr, OsString},
+    fs, io,
+    os::unix::{
+        ffi::OsStrExt,
+        fs::{symlink, MetadataExt},
+    },
     path::{Component, Path, PathBuf},
 };
 
+/// Checks an internal invariant of the library.
+///
+/// A violation is returned as an error, unless the `debug-internal` feature is enabled,
+/// in which case it panics so the problem can be caught during development.
+macro_rules! invariant {
+    ($cond:expr, $($arg:tt)+) => {
+        if !$cond {
+            log::debug!("Invariant violated: {}", stringify!($cond));
+            if cfg!(feature = "debug-internal") {
+                panic!($($arg)+);
+            }
+            return Err($crate::trashing::TrashError::SpecViolation(format!($($arg)+)));
+        }
+    };
+}
+
+mod archive;
+mod backend;
+mod checksum;
+// for applications embedding the trash, the cli itself doesn't need it
+#[cfg(feature = "async")]
+mod async_trash;
+mod clock;
+mod content_index;
+mod contents;
+mod doctor;
+mod encryption;
+mod error;
+mod exclusions;
+mod history;
+mod import;
+mod info_cache;
+mod journal;
+mod migrate;
+mod mount;
+mod open_files;
+mod original_metadata;
+pub mod portal;
+mod quota;
+mod repair;
+#[cfg(feature = "selinux")]
+mod selinux;
+mod shred;
+mod snapshot;
+mod sparse;
 mod trash;
+mod trash_fs;
 mod trashinfo;
 mod unified_trash;
+mod verify;
+mod watch;
+mod xattrs;
 
+pub use archive::{export, ExportOptions};
+#[cfg(feature = "async")]
+pub use async_trash::AsyncUnifiedTrash;
+pub use backend::{TrashBackend, XdgBackend};
+pub use clock::FixedClock;
+pub use content_index::{ContentIndex, IndexLimits};
+pub use doctor::{diagnose, Severity};
+pub use encryption::KeySource;
+pub use error::TrashError;
+pub use exclusions::{DeviceExclusions, ExclusionFallback};
+pub use history::{revert, History, Operation};
+pub use import::import;
+pub use journal::{Action, Journal, Via};
+pub use migrate::migrate_trash_cli;
+pub use mount::is_removable;
+pub use open_files::OpenFiles;
+pub use original_metadata::OriginalMetadata;
+pub use quota::{Quota, QuotaPolicy, QuotaUsage, Retention, UsageWarning};
+pub use repair::{find_corrupt, repair, CorruptEntry, Repair};
+pub use snapshot::create_snapshot;
 pub use trash::Trash;
+pub use trash_fs::{FileStat, LocalFs};
 pub use trashinfo::Trashinfo;
-pub use unified_trash::UnifiedTrash;
-
-pub fn list_mounts() -> Result<Vec<PathBuf>, anyhow::Error> {
-    Ok(fs::read("/proc/mounts")
-        .context("Failed to read /proc/mounts, are you perhaps not running linux?")?
-        .split(|x| *x as char == '\n')
-        .filter(|x| !x.is_empty())
-        .map(|x| x.split(|x| *x == b' ').nth(1).unwrap())
-        .map(OsStr::from_bytes)
-        .map(PathBuf::from)
-        .collect())
-}
+pub use unified_trash::{EmptyOptions, EmptySummary, StuckTrash, UnifiedTrash};
+pub use verify::verify;
+pub use watch::{watch, WatchEvent};
+
+use mount::{list_mounts, responsive};
 
 /// Does some basic checks to determine if the given path is a system path,
 /// i.e. a place where trashing a file (and later restoring it) would probably
@@ -34,7 +100,7 @@ pub fn is_sys_path(path: &Path) -> bool {
         return false;
     };
 
-    if path == PathBuf::from("/") {
+    if path == Path::new("/") {
         return true;
     }
 
@@ -43,33 +109,37 @@ pub fn is_sys_path(path: &Path) -> bool {
     };
     let first_component = first_component.as_os_str();
 
-    match first_component.to_string_lossy().to_string().as_str() {
-        "boot" => true,
-        "dev" => true,
-        "proc" => true,
-        "lost+found" => true,
-        "sys" => true,
-        _ => false,
-    }
+    matches!(
+        first_component.to_string_lossy().to_string().as_str(),
+        "boot" | "dev" | "proc" | "lost+found" | "sys"
+    )
 }
 
 /// Find the root (mountpoint) of the filesystem in which the `path` resides
-pub fn find_fs_root(path: &Path) -> anyhow::Result<PathBuf> {
+pub fn find_fs_root(path: &Path) -> Result<PathBuf> {
     let path = path.canonicalize().context("Failed to resolve path")?;
     let root_dev = fs::metadata(&path).context("Failed to get metadata")?.dev();
-    Ok(path
-        .ancestors() // trust the metadata call won't fail
-        .take_while(|x| fs::metadata(x).unwrap().dev() == root_dev)
-        .collect())
+
+    let mut root = path.as_path();
+    for ancestor in path.ancestors().skip(1) {
+        let meta = fs::metadata(ancestor)
+            .context(format!("Failed to get metadata of {}", ancestor.display()))?;
+        if meta.dev() != root_dev {
+            break;
+        }
+        root = ancestor;
+    }
+    Ok(root.to_path_buf())
 }
 
-pub fn find_home_trash() -> anyhow::Result<Trash> {
-    let home_dir = PathBuf::from(env::var("HOME").context("No home dir set!")?);
+pub fn find_home_trash() -> Result<Trash> {
+    let home_dir = PathBuf::from(env::var_os("HOME").context("No home dir set!")?);
     let xdg_data_dir = env::var("XDG_DATA_HOME")
         .map(PathBuf::from)
         .unwrap_or(home_dir.join(".local").join("share"));
     let xdg_data_dir_meta = fs::metadata(&xdg_data_dir).context("Failed to get metadata")?;
     Trash::new_with_ensure(
+        &LocalFs,
         xdg_data_dir.join("Trash"),
         xdg_data_dir,
         xdg_data_dir_meta.dev(),
@@ -78,7 +148,192 @@ pub fn find_home_trash() -> anyhow::Result<Trash> {
     )
 }
 
-fn lexical_absolute(p: &Path) -> std::io::Result<PathBuf> {
+/// The home trash of another user in their `home`, assuming the default `$XDG_DATA_HOME`.
+/// Unlike [`find_home_trash`], nothing is created.
+pub fn home_trash_of(home: &Path) -> Result<Trash> {
+    let data_dir = home.join(".local").join("share");
+    let trash_path = data_dir.join("Trash");
+    let meta =
+        fs::metadata(&trash_path).context(format!("No trash at {}", trash_path.display()))?;
+
+    Ok(Trash {
+        is_home_trash: true,
+        is_admin_trash: false,
+        dev_root: data_dir,
+        trash_path,
+        device: meta.dev(),
+    })
+}
+
+/// The home dir of the user with `uid` according to the user database
+pub fn home_of(uid: u32) -> Result<PathBuf> {
+    let mut buf = vec![0; 16 * 1024];
+    let mut passwd: libc::passwd = unsafe { std::mem::zeroed() };
+    let mut result = std::ptr::null_mut();
+
+    let err =
+        unsafe { libc::getpwuid_r(uid, &mut passwd, buf.as_mut_ptr(), buf.len(), &mut result) };
+    if err != 0 {
+        return Err(io::Error::from_raw_os_error(err).into());
+    }
+    if result.is_null() {
+        return Err(error::TrashError::NotFound(format!(
+            "No user with uid {}",
+            uid
+        )));
+    }
+
+    let home = unsafe { CStr::from_ptr(passwd.pw_dir) };
+    Ok(PathBuf::from(OsStr::from_bytes(home.to_bytes())))
+}
+
+/// The user who started the program, if it runs as someone else: the `SUDO_UID` under sudo,
+/// the `PKEXEC_UID` under pkexec or the real uid for a setuid binary.
+/// `None` if the effective user started it.
+pub fn invoking_uid() -> Option<u32> {
+    let euid = rustix::process::geteuid();
+    let ruid = rustix::process::getuid();
+
+    // under a setuid binary the environment comes from whoever started it, only sudo
+    // and pkexec (which run us as real root) set these variables themselves
+    let sudo_uid = ["SUDO_UID", "PKEXEC_UID"]
+        .into_iter()
+        .find_map(|x| std::env::var(x).ok()?.parse().ok())
+        .filter(|_| euid.is_root() && ruid.is_root());
+    let uid = sudo_uid.unwrap_or(ruid.as_raw());
+
+    (uid != euid.as_raw()).then_some(uid)
+}
+
+/// Appends `n` to the stem of `name`: `somefile.txt` -> `somefile1.txt`
+///
+/// We try to preserve the extension in case a user wants to manually recover a file
+/// (so it still has the proper extension)
+pub fn numbered_filename(name: &OsStr, n: usize) -> OsString {
+    // somefile.txt
+    let old_name = Path::new(name);
+
+    // somefile
+    let mut stem = old_name.file_stem().unwrap_or(name).to_os_string();
+
+    // somefile1
+    stem.push(n.to_string());
+
+    // txt
+    if let Some(ext) = old_name.extension() {
+        // somefile1.txt
+        stem.push(".");
+        stem.push(ext);
+    }
+
+    stem
+}
+
+/// The apparent size of the file or directory at `path`, symlinks are not followed.
+pub fn path_size(path: &Path) -> std::io::Result<u64> {
+    let meta = fs::symlink_metadata(path)?;
+    if !meta.is_dir() {
+        return Ok(meta.len());
+    }
+
+    let mut size = meta.len();
+    for entry in fs::read_dir(path)? {
+        size += path_size(&entry?.path())?;
+    }
+    Ok(size)
+}
+
+/// Renames `from` to `to`, copying and then removing it if they are on different devices
+pub fn move_path(from: &Path, to: &Path) -> io::Result<()> {
+    match fs::rename(from, to) {
+        Err(e) if e.raw_os_error() == Some(libc::EXDEV) => {
+            log::debug!("{} is on another device, copying it", from.display());
+            // copied next to `to` and renamed into place, so cleaning up after a failure
+            // never touches anything that was at `to` already
+            let partial = partial_path(to);
+            if let Err(e) = copy_all(from, &partial).and_then(|_| fs::rename(&partial, to)) {
+                let _ = remove_all(&partial);
+                return Err(e);
+            }
+            remove_all(from)
+        }
+        other => other,
+    }
+}
+
+/// An unused path next to `to`, for copying something there before it's renamed to `to`
+fn partial_path(to: &Path) -> PathBuf {
+    let mut name = to.file_name().unwrap_or_default().to_os_string();
+    name.push(".partial");
+
+    let mut partial = to.with_file_name(&name);
+    for i in 1.. {
+        if fs::symlink_metadata(&partial).is_err() {
+            break;
+        }
+        partial = to.with_file_name(numbered_filename(&name, i));
+    }
+    partial
+}
+
+/// Copies `from` to `to` recursively, without following symlinks or filling in holes
+pub fn copy_all(from: &Path, to: &Path) -> io::Result<()> {
+    let meta = fs::symlink_metadata(from)?;
+    if meta.is_symlink() {
+        symlink(fs::read_link(from)?, to)
+    } else if meta.is_dir() {
+        fs::create_dir(to)?;
+        for entry in fs::read_dir(from)? {
+            let entry = entry?;
+            copy_all(&entry.path(), &to.join(entry.file_name()))?;
+        }
+        fs::set_permissions(to, meta.permissions())
+    } else {
+        sparse::copy_file(from, to)
+    }
+}
+
+pub fn remove_all(path: &Path) -> io::Result<()> {
+    if fs::symlink_metadata(path)?.is_dir() {
+        fs::remove_dir_all(path)
+    } else {
+        fs::remove_file(path)
+    }
+}
+
+/// Whether the filesystem containing `path` is mounted read-only
+fn is_read_only(path: &Path) -> std::io::Result<bool> {
+    Ok(statvfs(path)?.f_flag & libc::ST_RDONLY != 0)
+}
+
+/// The total size of the filesystem `path` is on, in bytes
+fn fs_capacity(path: &Path) -> std::io::Result<u64> {
+    let stat = statvfs(path)?;
+    Ok(stat.f_blocks as u64 * stat.f_frsize as u64)
+}
+
+fn statvfs(path: &Path) -> std::io::Result<libc::statvfs> {
+    let c_path = std::ffi::CString::new(path.as_os_str().as_bytes())?;
+    let mut stat = std::mem::MaybeUninit::<libc::statvfs>::uninit();
+
+    // SAFETY: c_path is a valid nul terminated string and stat is only read after statvfs succeeded
+    if unsafe { libc::statvfs(c_path.as_ptr(), stat.as_mut_ptr()) } != 0 {
+        return Err(std::io::Error::last_os_error());
+    }
+    Ok(unsafe { stat.assume_init() })
+}
+
+/// The directory where this program may keep cached data (`$XDG_CACHE_HOME/trash-cli`)
+pub fn cache_dir() -> Result<PathBuf> {
+    let cache_home = match env::var("XDG_CACHE_HOME") {
+        Ok(v) => PathBuf::from(v),
+        Err(_) => PathBuf::from(env::var_os("HOME").context("No home dir set!")?).join(".cache"),
+    };
+    Ok(cache_home.join("trash-cli"))
+}
+
+/// Makes `p` absolute without resolving symlinks (`..` removes the previous component)
+pub fn lexical_absolute(p: &Path) -> std::io::Result<PathBuf> {
     let mut absolute = if p.is_absolute() {
         PathBuf::new()
     } else {
@@ -121,3 +376,35 @@ fn test_is_sys_path4() {
 
     assert!(is_sys_path(&p));
 }
+
+#[test]
+fn test_copy_all_keeps_links() {
+    let dir = crate::test::TempDir::new("copy");
+    let from = dir.join("from");
+    fs::create_dir_all(from.join("nested/deeper")).unwrap();
+    fs::write(from.join("nested/file"), "content").unwrap();
+    symlink("../file", from.join("nested/deeper/relative")).unwrap();
+    symlink(from.join("nested"), from.join("to_dir")).unwrap();
+    symlink("missing", from.join("nested/dangling")).unwrap();
+
+    let to = dir.join("to");
+    copy_all(&from, &to).unwrap();
+
+    assert_eq!(
+        fs::read_link(to.join("nested/deeper/relative")).unwrap(),
+        Path::new("../file")
+    );
+    // not followed, so the directory isn't duplicated and keeps pointing at the original
+    assert_eq!(
+        fs::read_link(to.join("to_dir")).unwrap(),
+        from.join("nested")
+    );
+    assert_eq!(
+        fs::read_link(to.join("nested/dangling")).unwrap(),
+        Path::new("missing")
+    );
+    assert_eq!(
+        fs::read_to_string(to.join("nested/file")).unwrap(),
+        "content"
+    );
+}
@@ -0,0 +1,298 @@
+#[cfg(target_os = "linux")]
+use std::{ffi::OsString, os::unix::ffi::OsStringExt};
+use std::{
+    fs,
+    os::unix::fs::MetadataExt,
+    path::{Path, PathBuf},
+    sync::mpsc,
+    thread,
+    time::{Duration, Instant},
+};
+
+use rustc_hash::FxHashSet;
+
+#[cfg(target_os = "linux")]
+use super::error::Context;
+use super::error::Result;
+
+/// A mounted filesystem, from `/proc/self/mountinfo` on linux or `getmntinfo` on the BSDs
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Mount {
+    pub mount_point: PathBuf,
+
+    /// The directory of the filesystem that is mounted, `/` unless it's a bind mount
+    /// (or a btrfs subvolume). Always `/` on the BSDs
+    pub root: PathBuf,
+
+    /// For example `ext4`, `btrfs` or `nfs4`
+    pub fs_type: String,
+
+    /// Per-mount options followed by the options of the superblock.
+    /// On the BSDs only `ro` or `rw` is known
+    pub options: Vec<String>,
+}
+
+/// How long a remote mount gets to answer a stat before it's considered hung
+const PROBE_TIMEOUT: Duration = Duration::from_secs(2);
+
+impl Mount {
+    pub fn is_read_only(&self) -> bool {
+        self.options.iter().any(|x| x == "ro")
+    }
+
+    /// Whether the filesystem lives behind a network or a userspace process,
+    /// so that accessing it can hang when the other side is gone
+    pub fn is_remote(&self) -> bool {
+        let fs_type = self.fs_type.as_str();
+        fs_type.starts_with("fuse")
+            || fs_type.starts_with("nfs")
+            || matches!(
+                fs_type,
+                "cifs" | "smb3" | "smbfs" | "9p" | "ceph" | "afs" | "glusterfs" | "davfs" | "sshfs"
+            )
+    }
+}
+
+/// The `mounts` that are usable without hanging. Remote mounts are probed in parallel,
+/// the ones that don't answer a stat within [`PROBE_TIMEOUT`] are skipped with a warning.
+/// Their probe threads stay stuck until the filesystem answers (or the program exits).
+pub fn responsive(mounts: Vec<Mount>) -> Vec<Mount> {
+    let (tx, rx) = mpsc::channel();
+    let mut probing = FxHashSet::default();
+    for (i, mount) in mounts.iter().enumerate().filter(|(_, x)| x.is_remote()) {
+        let tx = tx.clone();
+        let mount_point = mount.mount_point.clone();
+        let spawned = thread::Builder::new()
+            .name("mount-probe".to_owned())
+            .spawn(move || {
+                let _ = fs::metadata(mount_point);
+                let _ = tx.send(i);
+            });
+        match spawned {
+            Ok(_) => {
+                probing.insert(i);
+            }
+            // not knowing is no reason to skip it
+            Err(e) => log::debug!("Failed to probe {}: {}", mount.mount_point.display(), e),
+        }
+    }
+
+    let deadline = Instant::now() + PROBE_TIMEOUT;
+    let mut answered = FxHashSet::default();
+    while answered.len() < probing.len() {
+        match rx.recv_timeout(deadline.saturating_duration_since(Instant::now())) {
+            Ok(i) => answered.insert(i),
+            Err(_) => break,
+        };
+    }
+
+    mounts
+        .into_iter()
+        .enumerate()
+        .filter(|(i, mount)| {
+            let hung = probing.contains(i) && !answered.contains(i);
+            if hung {
+                log::warn!(
+                    "Skipping {} ({}), it didn't respond within {}s",
+                    mount.mount_point.display(),
+                    mount.fs_type,
+                    PROBE_TIMEOUT.as_secs()
+                );
+            }
+            !hung
+        })
+        .map(|(_, x)| x)
+        .collect()
+}
+
+#[cfg(target_os = "linux")]
+pub fn list_mounts() -> Result<Vec<Mount>> {
+    let raw = fs::read("/proc/self/mountinfo")
+        .context("Failed to read /proc/self/mountinfo, are you perhaps not running linux?")?;
+
+    raw.split(|x| *x == b'\n')
+        .filter(|x| !x.is_empty())
+        .map(|line| {
+            parse_mountinfo_line(line).context(format!(
+                "Unexpected format of /proc/self/mountinfo: {}",
+                String::from_utf8_lossy(line)
+            ))
+        })
+        .collect()
+}
+
+#[cfg(target_os = "linux")]
+/// Parses a line like
+/// `36 35 98:0 /mnt1 /mnt/parent rw,noatime master:1 - ext3 /dev/root rw,errors=continue`,
+/// see proc(5) for the meaning of every field
+fn parse_mountinfo_line(line: &[u8]) -> Option<Mount> {
+    let mut fields = line.split(|x| *x == b' ');
+
+    // mount id, parent id, major:minor
+    let mut fields = fields.by_ref().skip(3);
+    let root = fields.next()?;
+    let mount_point = fields.next()?;
+    let mount_options = fields.next()?;
+
+    // a variable number of optional fields, terminated by a single hyphen
+    let mut fields = fields.skip_while(|x| *x != b"-").skip(1);
+    let fs_type = fields.next()?;
+    let _source = fields.next()?;
+    let super_options = fields.next()?;
+
+    let options = [mount_options, super_options]
+        .iter()
+        .flat_map(|x| x.split(|x| *x == b','))
+        .map(|x| String::from_utf8_lossy(&unescape(x)).into_owned())
+        .collect();
+
+    Some(Mount {
+        mount_point: PathBuf::from(OsString::from_vec(unescape(mount_point))),
+        root: PathBuf::from(OsString::from_vec(unescape(root))),
+        fs_type: String::from_utf8_lossy(&unescape(fs_type)).into_owned(),
+        options,
+    })
+}
+
+#[cfg(target_os = "linux")]
+/// The kernel escapes spaces, tabs, newlines and backslashes as octal (`\040` for a space)
+fn unescape(raw: &[u8]) -> Vec<u8> {
+    let mut out = Vec::with_capacity(raw.len());
+    let mut i = 0;
+    while i < raw.len() {
+        let escaped = raw
+            .get(i + 1..i + 4)
+            .filter(|x| raw[i] == b'\\' && x.iter().all(|x| (b'0'..=b'7').contains(x)))
+            .map(|x| x.iter().fold(0u16, |acc, x| acc * 8 + u16::from(x - b'0')));
+
+        match escaped {
+            Some(v) if v <= 0xff => {
+                out.push(v as u8);
+                i += 4;
+            }
+            _ => {
+                out.push(raw[i]);
+                i += 1;
+            }
+        }
+    }
+    out
+}
+
+#[cfg(any(target_os = "freebsd", target_os = "openbsd", target_os = "dragonfly"))]
+pub fn list_mounts() -> Result<Vec<Mount>> {
+    use std::{
+        ffi::{CStr, OsStr},
+        io,
+        os::unix::ffi::OsStrExt,
+    };
+
+    let mut buf: *mut libc::statfs = std::ptr::null_mut();
+    // SAFETY: getmntinfo points buf at an array of as many entries as it returns, owned by libc
+    let count = unsafe { libc::getmntinfo(&mut buf, libc::MNT_NOWAIT) };
+    if count <= 0 {
+        return Err(io::Error::last_os_error().into());
+    }
+
+    // SAFETY: see above, the array stays valid until the next call of getmntinfo
+    let entries = unsafe { std::slice::from_raw_parts(buf, count as usize) };
+    let mounts = entries
+        .iter()
+        .map(|x| {
+            // SAFETY: both names are nul-terminated by the kernel
+            let (mount_point, fs_type) = unsafe {
+                (
+                    CStr::from_ptr(x.f_mntonname.as_ptr()),
+                    CStr::from_ptr(x.f_fstypename.as_ptr()),
+                )
+            };
+
+            let read_only = x.f_flags as u64 & libc::MNT_RDONLY as u64 != 0;
+            Mount {
+                mount_point: PathBuf::from(OsStr::from_bytes(mount_point.to_bytes())),
+                root: PathBuf::from("/"),
+                fs_type: fs_type.to_string_lossy().into_owned(),
+                options: vec![if read_only { "ro" } else { "rw" }.to_owned()],
+            }
+        })
+        .collect();
+
+    Ok(mounts)
+}
+
+const BY_UUID: &str = "/dev/disk/by-uuid";
+
+/// The UUID of the filesystem on the device `dev` (the `st_dev` of a file on it), as udev
+/// lists it in `/dev/disk/by-uuid`. Filesystems without one (tmpfs, nfs, ...) have none
+pub fn fs_uuid(dev: u64) -> Option<String> {
+    fs::read_dir(BY_UUID)
+        .ok()?
+        .filter_map(|x| x.ok())
+        .find(|x| fs::metadata(x.path()).is_ok_and(|x| x.rdev() == dev))
+        .and_then(|x| x.file_name().into_string().ok())
+}
+
+/// Where the filesystem with `uuid` is mounted right now, bind mounts of only a part of it don't count
+pub fn mount_point_of_uuid(uuid: &str) -> Option<PathBuf> {
+    let dev = fs::metadata(Path::new(BY_UUID).join(uuid)).ok()?.rdev();
+
+    list_mounts()
+        .ok()?
+        .into_iter()
+        .filter(|x| x.root == Path::new("/"))
+        .find(|x| fs::metadata(&x.mount_point).is_ok_and(|x| x.dev() == dev))
+        .map(|x| x.mount_point)
+}
+
+/// Whether the filesystem `path` is on lives on removable media like USB sticks and SD cards,
+/// going by sysfs. Partitions don't say, the disk they are on does
+#[cfg(target_os = "linux")]
+pub fn is_removable(path: &Path) -> bool {
+    let Ok(dev) = fs::metadata(path).map(|x| x.dev()) else {
+        return false;
+    };
+    let sys_path = format!("/sys/dev/block/{}:{}", libc::major(dev), libc::minor(dev));
+    let Ok(sys_path) = fs::canonicalize(sys_path) else {
+        return false;
+    };
+
+    // USB disks don't always claim to be removable, their place on the bus gives them away
+    let on_usb = sys_path
+        .components()
+        .any(|x| x.as_os_str().to_str().is_some_and(|x| x.starts_with("usb")));
+    on_usb
+        || sys_path
+            .ancestors()
+            .take(2)
+            .any(|x| fs::read_to_string(x.join("removable")).is_ok_and(|x| x.trim() == "1"))
+}
+
+#[cfg(any(target_os = "freebsd", target_os = "openbsd", target_os = "dragonfly"))]
+pub fn is_removable(_path: &Path) -> bool {
+    false
+}
+
+#[cfg(target_os = "linux")]
+#[test]
+fn test_unescape() {
+    assert_eq!(unescape(br"/mnt/my\040drive"), b"/mnt/my drive");
+    assert_eq!(unescape(br"/a\134b\011c"), b"/a\\b\tc");
+    assert_eq!(unescape(br"/not\9escaped\04"), br"/not\9escaped\04");
+}
+
+#[cfg(target_os = "linux")]
+#[test]
+fn test_parse_mountinfo_line() {
+    let mount = parse_mountinfo_line(
+        br"36 35 98:0 /mnt1 /mnt/my\040drive ro,noatime master:1 shared:2 - ext4 /dev/sdb1 rw,errors=continue",
+    )
+    .unwrap();
+
+    assert_eq!(mount.mount_point, PathBuf::from("/mnt/my drive"));
+    assert_eq!(mount.root, PathBuf::from("/mnt1"));
+    assert_eq!(mount.fs_type, "ext4");
+    assert_eq!(mount.options, ["ro", "noatime", "rw", "errors=continue"]);
+    assert!(mount.is_read_only());
+
+    assert!(parse_mountinfo_line(b"36 35 98:0 /mnt1 /mnt").is_none());
+}
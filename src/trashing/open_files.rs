@@ -0,0 +1,77 @@
+use format as f;
+use std::{
+    fs,
+    path::{Path, PathBuf},
+};
+
+/// A process and the files it has open, as far as we are allowed to see them
+#[derive(Debug, Clone)]
+pub struct Process {
+    pub pid: u32,
+
+    /// The name of its executable, from `/proc/$pid/comm`
+    pub name: String,
+
+    /// Its open file descriptors and working directory
+    paths: Vec<PathBuf>,
+}
+
+/// The files every process has open at the time of the scan, from `/proc/$pid/fd`.
+///
+/// The fds of processes of other users can only be read by root,
+/// so for everyone else those processes don't show up.
+#[derive(Debug, Clone, Default)]
+pub struct OpenFiles {
+    processes: Vec<Process>,
+}
+
+impl OpenFiles {
+    pub fn scan() -> Self {
+        let Ok(proc) = fs::read_dir("/proc") else {
+            return Self::default();
+        };
+
+        let processes = proc
+            .filter_map(|x| x.ok())
+            .filter_map(|x| x.file_name().to_str()?.parse::<u32>().ok())
+            .filter(|pid| *pid != std::process::id())
+            .filter_map(|pid| {
+                let dir = PathBuf::from(f!("/proc/{}", pid));
+                let mut paths = fs::read_dir(dir.join("fd"))
+                    .ok()?
+                    .filter_map(|x| fs::read_link(x.ok()?.path()).ok())
+                    .collect::<Vec<_>>();
+                paths.extend(fs::read_link(dir.join("cwd")));
+
+                let name = fs::read_to_string(dir.join("comm")).unwrap_or_default();
+                Some(Process {
+                    pid,
+                    name: name.trim_end().to_owned(),
+                    paths,
+                })
+            })
+            .collect();
+
+        Self { processes }
+    }
+
+    /// The processes that have `path` or anything inside of it open, or are working in it
+    pub fn users_of(&self, path: &Path) -> Vec<&Process> {
+        // the kernel resolves symlinks for the fd links, but a symlink itself is trashed, not its target
+        let (Some(parent), Some(name)) = (path.parent(), path.file_name()) else {
+            return vec![];
+        };
+        let parent = match parent.as_os_str().is_empty() {
+            true => Path::new("."),
+            false => parent,
+        };
+        let Ok(path) = parent.canonicalize().map(|x| x.join(name)) else {
+            return vec![];
+        };
+
+        self.processes
+            .iter()
+            .filter(|x| x.paths.iter().any(|x| x.starts_with(&path)))
+            .collect()
+    }
+}
@@ -0,0 +1,309 @@
+use std::{
+    ffi::CString,
+    fmt::Write,
+    fs, io,
+    os::unix::{ffi::OsStrExt, fs::MetadataExt},
+    path::Path,
+};
+
+use serde::{Deserialize, Serialize};
+
+use super::FileStat;
+
+/// Metadata of a file from before it was trashed, so it can be reapplied when restoring.
+///
+/// It is stored in extra keys of the trashinfo file, which other implementations ignore.
+/// Everything is optional, since files trashed by other implementations don't have these keys.
+#[derive(Debug, Clone, Default, PartialEq, PartialOrd, Serialize, Deserialize)]
+pub struct OriginalMetadata {
+    /// `Mtime`, seconds and nanoseconds since the epoch
+    pub mtime: Option<(i64, i64)>,
+
+    /// `Atime`, seconds and nanoseconds since the epoch
+    pub atime: Option<(i64, i64)>,
+
+    /// `Mode`, permission bits (including setuid, setgid and sticky) in octal
+    pub mode: Option<u32>,
+
+    /// `Uid`
+    pub uid: Option<u32>,
+
+    /// `Gid`
+    pub gid: Option<u32>,
+
+    /// `SecurityContext`, the SELinux label. Only recorded and reapplied with the `selinux` feature,
+    /// since the label can change when a file is copied into (or out of) a trash on another device
+    pub security_context: Option<String>,
+
+    /// `Sha256`, the checksum of the contents in lowercase hex. Only recorded for regular files,
+    /// it is used to detect entries that were corrupted or changed while they were in the trash
+    pub sha256: Option<String>,
+
+    /// `FsUuid`, the UUID of the filesystem a trash with relative paths is on. The paths are
+    /// relative to where it was mounted, so it is used to find where it's mounted now when restoring
+    pub fs_uuid: Option<String>,
+
+    /// `Compression=zstd`, the file in the trash holds the zstd compressed contents of the
+    /// original file. Other implementations don't know about this and restore it as it is
+    #[serde(default)]
+    pub compressed: bool,
+
+    /// `Encryption=xchacha20poly1305`, the file in the trash is encrypted
+    /// (after compressing it, if it is compressed too)
+    #[serde(default)]
+    pub encrypted: bool,
+}
+
+impl OriginalMetadata {
+    pub fn capture(meta: &FileStat) -> Self {
+        Self {
+            mtime: Some(meta.mtime),
+            atime: Some(meta.atime),
+            mode: Some(meta.mode & 0o7777),
+            uid: Some(meta.uid),
+            gid: Some(meta.gid),
+            security_context: None,
+            sha256: None,
+            fs_uuid: None,
+            compressed: false,
+            encrypted: false,
+        }
+    }
+
+    /// Also records the SELinux context of the file at `path`
+    #[cfg(feature = "selinux")]
+    pub fn capture_security_context(&mut self, path: &Path) {
+        match super::selinux::context(path) {
+            Ok(v) => self.security_context = v,
+            Err(e) => log::debug!("Failed to read SELinux context: {}", e),
+        }
+    }
+
+    /// Sets the key `key` from a trashinfo file, unknown keys and invalid values are ignored
+    pub fn parse_key(&mut self, key: &str, val: &str) {
+        match key {
+            "Mtime" => self.mtime = parse_time(val).or(self.mtime),
+            "Atime" => self.atime = parse_time(val).or(self.atime),
+            "Mode" => {
+                self.mode = u32::from_str_radix(val, 8)
+                    .ok()
+                    .filter(|x| *x <= 0o7777)
+                    .or(self.mode)
+            }
+            "Uid" => self.uid = val.parse().ok().or(self.uid),
+            "Gid" => self.gid = val.parse().ok().or(self.gid),
+            // contexts look like `user:role:type:level`, anything else can't be one
+            "SecurityContext" if val.contains(':') && !val.contains(char::is_whitespace) => {
+                self.security_context = Some(val.to_owned())
+            }
+            "Sha256"
+                if val.len() == 64
+                    && val.bytes().all(|x| matches!(x, b'0'..=b'9' | b'a'..=b'f')) =>
+            {
+                self.sha256 = Some(val.to_owned())
+            }
+            // ext4 and btrfs use real UUIDs, vfat and ntfs serial numbers like `1A2B-3C4D`
+            "FsUuid"
+                if !val.is_empty()
+                    && val.len() <= 36
+                    && val.bytes().all(|x| x.is_ascii_hexdigit() || x == b'-') =>
+            {
+                self.fs_uuid = Some(val.to_owned())
+            }
+            "Compression" if val == "zstd" => self.compressed = true,
+            "Encryption" if val == "xchacha20poly1305" => self.encrypted = true,
+            _ => {}
+        }
+    }
+
+    /// Appends the keys for everything that is known to the trashinfo file `out`
+    pub fn write_keys(&self, out: &mut String) {
+        if let Some((secs, nsecs)) = self.mtime {
+            let _ = write!(out, "\nMtime={}.{:09}", secs, nsecs);
+        }
+        if let Some((secs, nsecs)) = self.atime {
+            let _ = write!(out, "\nAtime={}.{:09}", secs, nsecs);
+        }
+        if let Some(mode) = self.mode {
+            let _ = write!(out, "\nMode={:o}", mode);
+        }
+        if let Some(uid) = self.uid {
+            let _ = write!(out, "\nUid={}", uid);
+        }
+        if let Some(gid) = self.gid {
+            let _ = write!(out, "\nGid={}", gid);
+        }
+        if let Some(context) = &self.security_context {
+            let _ = write!(out, "\nSecurityContext={}", context);
+        }
+        if let Some(sha256) = &self.sha256 {
+            let _ = write!(out, "\nSha256={}", sha256);
+        }
+        if let Some(uuid) = &self.fs_uuid {
+            let _ = write!(out, "\nFsUuid={}", uuid);
+        }
+        if self.compressed {
+            out.push_str("\nCompression=zstd");
+        }
+        if self.encrypted {
+            out.push_str("\nEncryption=xchacha20poly1305");
+        }
+    }
+
+    /// Reapplies everything that is known to the restored file at `path` (without following symlinks).
+    ///
+    /// Ownership can usually only be changed by root, so failing to do so is not an error.
+    /// The keys come from a file the user can edit, so the file is never given to anyone but
+    /// `owner` (whose trash it is) and keeps no setuid or setgid bits if it belongs to someone else.
+    /// Running as root for another user, neither the ownership nor the label are touched.
+    pub fn apply(&self, path: &Path, owner: u32) -> io::Result<()> {
+        let c_path = CString::new(path.as_os_str().as_bytes())?;
+        let meta = fs::symlink_metadata(path)?;
+        let privileged = is_privileged_for(owner);
+
+        if !privileged && (self.uid.is_some() || self.gid.is_some()) {
+            let uid = self.uid.filter(|x| *x == owner).unwrap_or(meta.uid());
+            let gid = self.gid.unwrap_or(meta.gid());
+            if (uid, gid) != (meta.uid(), meta.gid()) {
+                // SAFETY: c_path is nul terminated
+                if unsafe { libc::lchown(c_path.as_ptr(), uid, gid) } != 0 {
+                    log::info!(
+                        "Could not restore the ownership of {}: {}",
+                        path.display(),
+                        io::Error::last_os_error()
+                    );
+                }
+            }
+        }
+
+        #[cfg(feature = "selinux")]
+        if let Some(context) = self.security_context.as_ref().filter(|_| !privileged) {
+            if let Err(e) = super::selinux::set_context(path, context) {
+                log::warn!(
+                    "Could not restore the SELinux context of {}: {}",
+                    path.display(),
+                    e
+                );
+            }
+        }
+
+        // symlinks don't have permissions of their own on linux.
+        // This is done after chown, since changing the owner clears setuid and setgid
+        if let Some(mut mode) = self.mode.filter(|_| !meta.is_symlink()) {
+            if fs::symlink_metadata(path)?.uid() != owner {
+                mode &= !(libc::S_ISUID | libc::S_ISGID);
+            }
+            // set_permissions follows symlinks, which the path may have been swapped for by now
+            // SAFETY: c_path is nul terminated
+            let res = unsafe {
+                libc::fchmodat(
+                    libc::AT_FDCWD,
+                    c_path.as_ptr(),
+                    mode,
+                    libc::AT_SYMLINK_NOFOLLOW,
+                )
+            };
+            if res != 0 {
+                return Err(io::Error::last_os_error());
+            }
+        }
+
+        if self.mtime.is_none() && self.atime.is_none() {
+            return Ok(());
+        }
+
+        let omit = libc::timespec {
+            tv_sec: 0,
+            tv_nsec: libc::UTIME_OMIT,
+        };
+        let to_timespec = |x: Option<(i64, i64)>| {
+            x.map_or(omit, |(secs, nsecs)| libc::timespec {
+                tv_sec: secs as libc::time_t,
+                tv_nsec: nsecs as _,
+            })
+        };
+        let times = [to_timespec(self.atime), to_timespec(self.mtime)];
+
+        // SAFETY: c_path is nul terminated and times has the two entries utimensat expects
+        let res = unsafe {
+            libc::utimensat(
+                libc::AT_FDCWD,
+                c_path.as_ptr(),
+                times.as_ptr(),
+                libc::AT_SYMLINK_NOFOLLOW,
+            )
+        };
+        if res != 0 {
+            return Err(io::Error::last_os_error());
+        }
+
+        Ok(())
+    }
+}
+
+/// Whether this runs as root on the trash of another user (sudo, pkexec, `--uid`),
+/// where whatever a trash says about its files can't be trusted with root's rights
+pub fn is_privileged_for(owner: u32) -> bool {
+    let euid = rustix::process::geteuid();
+    euid.is_root() && euid.as_raw() != owner
+}
+
+fn parse_time(val: &str) -> Option<(i64, i64)> {
+    let (secs, nsecs) = match val.split_once('.') {
+        Some((secs, nsecs)) if nsecs.len() == 9 => (secs, nsecs.parse().ok()?),
+        Some(_) => return None,
+        None => (val, 0),
+    };
+    Some((secs.parse().ok()?, nsecs))
+}
+
+#[test]
+fn test_time_keys() {
+    let meta = OriginalMetadata {
+        mtime: Some((1705932195, 5)),
+        mode: Some(0o4755),
+        ..Default::default()
+    };
+    let mut out = String::new();
+    meta.write_keys(&mut out);
+    assert_eq!(out, "\nMtime=1705932195.000000005\nMode=4755");
+
+    let mut parsed = OriginalMetadata::default();
+    parsed.parse_key("Mtime", "1705932195.000000005");
+    parsed.parse_key("Mode", "4755");
+    parsed.parse_key("Uid", "-1");
+    parsed.parse_key("Atime", "garbage");
+    parsed.parse_key("SecurityContext", "not a context");
+    parsed.parse_key("Sha256", "ABC");
+    parsed.parse_key("FsUuid", "../../etc");
+    assert_eq!(parsed, meta);
+
+    parsed.parse_key("SecurityContext", "unconfined_u:object_r:user_home_t:s0");
+    let mut out = String::new();
+    parsed.write_keys(&mut out);
+    assert!(out.ends_with("\nSecurityContext=unconfined_u:object_r:user_home_t:s0"));
+
+    parsed.parse_key("FsUuid", "1A2B-3C4D");
+    assert_eq!(parsed.fs_uuid.as_deref(), Some("1A2B-3C4D"));
+}
+
+#[test]
+fn test_apply_to_someone_else() {
+    let dir = crate::test::TempDir::new("apply");
+    let path = dir.join("file");
+    fs::write(&path, "").unwrap();
+    let euid = rustix::process::geteuid().as_raw();
+
+    // an edited trashinfo file asking for a setuid root binary
+    let meta = OriginalMetadata {
+        mode: Some(0o6755),
+        uid: Some(0),
+        gid: Some(0),
+        ..Default::default()
+    };
+    meta.apply(&path, euid.wrapping_add(1)).unwrap();
+
+    let applied = fs::symlink_metadata(&path).unwrap();
+    assert_eq!(applied.mode() & 0o7777, 0o755);
+    assert_eq!(applied.uid(), euid);
+}
@@ -0,0 +1,56 @@
+use std::{
+    env,
+    fs::OpenOptions,
+    os::{fd::AsFd, unix::fs::OpenOptionsExt},
+    path::Path,
+};
+
+use zbus::{blocking::Connection, zvariant::Fd};
+
+use super::error::{Context, Result, TrashError};
+
+/// Whether we run inside a Flatpak or Snap sandbox, where the mounts and the trash
+/// we see are not the ones of the host
+pub fn is_sandboxed() -> bool {
+    Path::new("/.flatpak-info").exists() || env::var_os("SNAP").is_some()
+}
+
+/// Trashes the `file` through `org.freedesktop.portal.Trash`, which puts it into the
+/// trash of the host. The portal decides which trash that is and how the file is named.
+pub fn trash_file(file: &Path, follow_links: bool) -> Result<()> {
+    // the portal only accepts O_PATH file descriptors
+    let mut flags = libc::O_PATH | libc::O_CLOEXEC;
+    if !follow_links {
+        flags |= libc::O_NOFOLLOW;
+    }
+    let file = OpenOptions::new()
+        .read(true)
+        .custom_flags(flags)
+        .open(file)
+        .context("Failed to open file")?;
+
+    let connection = Connection::session().context("Failed to connect to the session bus")?;
+    let reply = connection
+        .call_method(
+            Some("org.freedesktop.portal.Desktop"),
+            "/org/freedesktop/portal/desktop",
+            Some("org.freedesktop.portal.Trash"),
+            "TrashFile",
+            &(Fd::from(file.as_fd()),),
+        )
+        .context("Failed to call the trash portal")?;
+
+    let result: u32 = reply
+        .body()
+        .deserialize()
+        .context("Invalid reply from the trash portal")?;
+
+    // 0 is failure, 1 is success
+    if result != 1 {
+        return Err(TrashError::Other(
+            "The trash portal failed to trash the file".to_owned(),
+        ));
+    }
+
+    Ok(())
+}
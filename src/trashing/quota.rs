@@ -0,0 +1,97 @@
+use std::path::PathBuf;
+
+use rustc_hash::FxHashMap;
+use serde::Deserialize;
+
+use super::Trash;
+
+/// What to do when trashing a file would make a trash exceed its quota
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum QuotaPolicy {
+    /// Don't trash the file
+    #[default]
+    Refuse,
+
+    /// Ask the user whether the file should be trashed anyway
+    Prompt,
+
+    /// Permanently delete the oldest entries in the trash until the file fits
+    Evict,
+}
+
+/// Limits on how big a single trash may grow
+#[derive(Debug, Clone)]
+pub struct Quota {
+    /// Maximum size of a trash in bytes
+    pub max_size: u64,
+
+    pub policy: QuotaPolicy,
+
+    /// Overrides `max_size` for the trash on the device mounted at the given path
+    pub mounts: FxHashMap<PathBuf, u64>,
+}
+
+/// The state of a trash that is about to exceed its quota
+#[derive(Debug, Clone, Copy)]
+pub struct QuotaUsage {
+    /// Bytes currently used by the trash
+    pub used: u64,
+
+    /// Size of the file that is about to be trashed
+    pub incoming: u64,
+
+    /// The quota of the trash
+    pub limit: u64,
+}
+
+/// When to warn that a trash is getting too big, after files were put into it.
+/// Unlike a [`Quota`], this never stops anything from being trashed
+#[derive(Debug, Clone, Default)]
+pub struct UsageWarning {
+    /// Share of the size of its filesystem, in percent
+    pub max_percent: Option<f64>,
+
+    /// Size in bytes
+    pub max_size: Option<u64>,
+}
+
+impl UsageWarning {
+    /// Whether `used` bytes are too much for a trash on a filesystem of `capacity` bytes
+    pub fn exceeded(&self, used: u64, capacity: u64) -> bool {
+        let too_big = self.max_size.is_some_and(|x| used > x);
+        let too_much = self
+            .max_percent
+            .is_some_and(|x| capacity > 0 && used as f64 * 100.0 / capacity as f64 > x);
+        too_big || too_much
+    }
+
+    /// The most bytes a trash on a filesystem of `capacity` bytes may use without a warning
+    pub fn limit(&self, capacity: u64) -> Option<u64> {
+        let percent = self
+            .max_percent
+            .filter(|_| capacity > 0)
+            .map(|x| (capacity as f64 * x / 100.0) as u64);
+        [self.max_size, percent].into_iter().flatten().min()
+    }
+}
+
+/// How long entries are kept and how big a trash may get before `empty --policy` removes
+/// the oldest entries. Only the entries count, files without a trashinfo file are left alone
+#[derive(Debug, Clone, Default)]
+pub struct Retention {
+    pub max_age: Option<chrono::Duration>,
+
+    /// Size in bytes, for every trash on its own
+    pub max_size: Option<u64>,
+}
+
+impl Quota {
+    /// The maximum size for the given `trash`
+    pub fn limit_for(&self, trash: &Trash) -> u64 {
+        self.mounts
+            .get(&trash.dev_root)
+            .copied()
+            .unwrap_or(self.max_size)
+    }
+}
@@ -0,0 +1,250 @@
+use std::{
+    ffi::{OsStr, OsString},
+    fs,
+    os::unix::{
+        ffi::{OsStrExt, OsStringExt},
+        fs::MetadataExt,
+    },
+    path::PathBuf,
+    sync::Arc,
+    time::{Duration, SystemTime, UNIX_EPOCH},
+};
+
+use chrono::{DateTime, Local, NaiveDateTime, Timelike};
+
+use super::{
+    error::{Context, Result, TrashError},
+    migrate::replace_trashinfo,
+    numbered_filename,
+    trashinfo::{parse_deletion_date, parse_trashinfo},
+    OriginalMetadata, Trash, Trashinfo, UnifiedTrash,
+};
+
+/// A trashinfo file that can't be parsed, along with everything that could still be salvaged from it
+#[derive(Debug, Clone)]
+pub struct CorruptEntry {
+    pub trash: Arc<Trash>,
+    pub info_path: PathBuf,
+
+    /// Name of the entry in the `files` directory (without `.trashinfo`)
+    pub trash_filename: OsString,
+
+    /// Why parsing failed
+    pub error: String,
+
+    /// The `Path` key, if it was present
+    pub original_filepath: Option<PathBuf>,
+
+    /// The `DeletionDate` key, if it was present and valid
+    pub deleted_at: Option<NaiveDateTime>,
+
+    /// The extra keys that could be read. Compressed and encrypted entries can't be restored
+    /// without theirs, so they are kept when the file is rewritten
+    pub metadata: OriginalMetadata,
+
+    /// When the info file was last changed (its ctime), which is when the file was trashed
+    /// unless something rewrote it since. Moving a file doesn't change its modification time,
+    /// so that of the trashed file says nothing about when it was trashed
+    pub changed_at: Option<NaiveDateTime>,
+}
+
+impl CorruptEntry {
+    pub fn files_path(&self) -> PathBuf {
+        self.trash.files_dir().join(&self.trash_filename)
+    }
+}
+
+/// How to deal with a [`CorruptEntry`]
+#[derive(Debug, Clone)]
+pub enum Repair {
+    /// Replace the trashinfo file with a valid one
+    Rewrite {
+        original_filepath: PathBuf,
+        deleted_at: NaiveDateTime,
+    },
+
+    /// Move the trashinfo file and the trashed file into `$trash/recovered`,
+    /// out of the way of every implementation
+    Recover,
+}
+
+/// Finds all trashinfo files across all trashes that fail to parse
+pub fn find_corrupt(trash: &UnifiedTrash) -> Result<Vec<CorruptEntry>> {
+    let mut corrupt = vec![];
+
+    for trash in trash.list_trashes() {
+        for entry in fs::read_dir(trash.info_dir()).context("Failed to read info dir")? {
+            let info_path = entry.context("Failed to read info dir")?.path();
+            let Some(trash_filename) = info_path
+                .file_name()
+                .and_then(|x| x.as_bytes().strip_suffix(b".trashinfo"))
+                .map(|x| OsStr::from_bytes(x).to_os_string())
+            else {
+                continue;
+            };
+
+            let Err(error) = parse_trashinfo(&info_path, trash) else {
+                continue;
+            };
+
+            let (original_filepath, deleted_at, metadata) = match fs::read(&info_path) {
+                Ok(raw) => salvage(&raw, trash),
+                Err(_) => (None, None, OriginalMetadata::default()),
+            };
+
+            let changed_at = fs::symlink_metadata(&info_path)
+                .ok()
+                .map(|x| UNIX_EPOCH + Duration::new(x.ctime() as u64, x.ctime_nsec() as u32))
+                .map(local_time);
+
+            corrupt.push(CorruptEntry {
+                trash: Arc::clone(trash),
+                info_path,
+                trash_filename,
+                error: format!("{:#}", error),
+                original_filepath,
+                deleted_at,
+                metadata,
+                changed_at,
+            });
+        }
+    }
+
+    Ok(corrupt)
+}
+
+/// Applies `repair` to the `entry`
+pub fn repair(entry: &CorruptEntry, repair: Repair) -> Result<()> {
+    match repair {
+        Repair::Rewrite {
+            original_filepath,
+            deleted_at,
+        } => {
+            if !original_filepath.is_absolute() {
+                return Err(TrashError::SpecViolation(format!(
+                    "{} is not an absolute path",
+                    original_filepath.display()
+                )));
+            }
+
+            let mut trash_filename_trashinfo = entry.trash_filename.clone();
+            trash_filename_trashinfo.push(".trashinfo");
+
+            let info = Trashinfo {
+                trash: Arc::clone(&entry.trash),
+                trash_filename: entry.trash_filename.clone(),
+                trash_filename_trashinfo,
+                deleted_at,
+                original_filepath,
+                metadata: entry.metadata.clone(),
+            };
+
+            replace_trashinfo(&entry.info_path, &info)
+        }
+        Repair::Recover => {
+            let recovered_dir = entry.trash.trash_path.join("recovered");
+            fs::create_dir_all(&recovered_dir).context("Failed to create recovered dir")?;
+
+            // recovered entries are never cleaned up, so names can clash with earlier runs
+            let mut name = entry.trash_filename.clone();
+            for i in 1.. {
+                let mut info_name = name.clone();
+                info_name.push(".trashinfo");
+                if !recovered_dir.join(&name).exists() && !recovered_dir.join(&info_name).exists() {
+                    break;
+                }
+                name = numbered_filename(&entry.trash_filename, i);
+            }
+
+            let files_path = entry.files_path();
+            if fs::symlink_metadata(&files_path).is_ok() {
+                fs::rename(&files_path, recovered_dir.join(&name))
+                    .context("Failed to move trashed file")?;
+            }
+
+            name.push(".trashinfo");
+            fs::rename(&entry.info_path, recovered_dir.join(name))
+                .context("Failed to move trashinfo file")?;
+
+            Ok(())
+        }
+    }
+}
+
+/// Extracts whatever is usable from a trashinfo file that the regular parser rejected
+fn salvage(
+    raw: &[u8],
+    trash: &Trash,
+) -> (Option<PathBuf>, Option<NaiveDateTime>, OriginalMetadata) {
+    let mut path = None;
+    let mut deletion_date = None;
+    let mut metadata = OriginalMetadata::default();
+
+    for line in raw.split(|x| *x == b'\n') {
+        let line = line.strip_suffix(b"\r").unwrap_or(line);
+        if let Some(v) = line.strip_prefix(b"Path=") {
+            path.get_or_insert(v);
+        } else if let Some(v) = line.strip_prefix(b"DeletionDate=") {
+            deletion_date.get_or_insert(v);
+        } else if let Some((key, val)) = std::str::from_utf8(line)
+            .ok()
+            .and_then(|x| x.split_once('='))
+        {
+            metadata.parse_key(key, val);
+        }
+    }
+
+    let path = path.filter(|x| !x.is_empty()).map(|x| {
+        let path = PathBuf::from(OsString::from_vec(
+            urlencoding::decode_binary(x).into_owned(),
+        ));
+        if path.is_relative() {
+            trash.dev_root.join(path)
+        } else {
+            path
+        }
+    });
+
+    let deleted_at =
+        deletion_date.and_then(|x| parse_deletion_date(&String::from_utf8_lossy(x)).ok());
+
+    (path, deleted_at, metadata)
+}
+
+fn local_time(time: SystemTime) -> NaiveDateTime {
+    let time = DateTime::<Local>::from(time).naive_local();
+    // trashinfo files don't store fractional seconds
+    time.with_nanosecond(0).unwrap_or(time)
+}
+
+#[test]
+fn test_salvage() {
+    let trash = Trash {
+        is_home_trash: false,
+        is_admin_trash: false,
+        dev_root: PathBuf::from("/mnt/usb"),
+        trash_path: PathBuf::from("/mnt/usb/.Trash-1000"),
+        device: 0,
+    };
+
+    let (path, date, metadata) = salvage(
+        b"Path=some%20dir/file\nDeletionDate=yesterday\nCompression=zstd\nEncryption=xchacha20poly1305\n",
+        &trash,
+    );
+    assert_eq!(
+        path.as_deref(),
+        Some(std::path::Path::new("/mnt/usb/some dir/file"))
+    );
+    assert_eq!(date, None);
+    assert!(metadata.compressed && metadata.encrypted);
+
+    let (path, date, _) = salvage(
+        b"[Trash Info]\r\nDeletionDate=2024-01-22T14:03:15\r\n",
+        &trash,
+    );
+    assert_eq!(path, None);
+    assert_eq!(
+        date,
+        NaiveDateTime::parse_from_str("2024-01-22T14:03:15", "%Y-%m-%dT%H:%M:%S").ok()
+    );
+}
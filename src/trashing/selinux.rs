@@ -0,0 +1,27 @@
+use std::{io, path::Path};
+
+const ATTR: &str = "security.selinux";
+
+/// The SELinux context of `path` (not following symlinks), `None` if it has none
+/// (SELinux is disabled or the filesystem doesn't support labels)
+pub fn context(path: &Path) -> io::Result<Option<String>> {
+    let Some(raw) = xattr::get(path, ATTR)? else {
+        return Ok(None);
+    };
+
+    // the kernel includes the terminating nul
+    let raw = raw.strip_suffix(b"\0").unwrap_or(&raw);
+    Ok(Some(String::from_utf8_lossy(raw).into_owned()))
+}
+
+/// Relabels `path` with `context` if it has a different one, like `restorecon` would
+/// with the context it had before it was trashed
+pub fn set_context(path: &Path, context: &str) -> io::Result<()> {
+    if self::context(path)?.as_deref() == Some(context) {
+        return Ok(());
+    }
+
+    let mut raw = context.as_bytes().to_vec();
+    raw.push(0);
+    xattr::set(path, ATTR, &raw)
+}
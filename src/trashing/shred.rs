@@ -0,0 +1,59 @@
+use std::{
+    fs::{self, File, OpenOptions},
+    io::{self, Read, Write},
+    os::unix::fs::{MetadataExt, OpenOptionsExt, PermissionsExt},
+    path::Path,
+};
+
+const CHUNK_SIZE: usize = 64 * 1024;
+
+/// Overwrites the contents of every regular file at or below `path` with random data,
+/// without following symlinks. Nothing is unlinked.
+///
+/// This is no guarantee that the data is gone: copy-on-write filesystems (btrfs, zfs),
+/// snapshots and the wear leveling of SSDs all keep old blocks around.
+/// Files with more than one hard link are skipped, since the data is still in use elsewhere.
+pub fn shred(path: &Path) -> io::Result<()> {
+    let meta = fs::symlink_metadata(path)?;
+
+    if meta.is_dir() {
+        for entry in fs::read_dir(path)? {
+            shred(&entry?.path())?;
+        }
+    } else if meta.is_file() {
+        if meta.nlink() > 1 {
+            log::warn!("Not shredding {}, it has other hard links", path.display());
+            return Ok(());
+        }
+        // read-only files can still be deleted, so they are shredded as well
+        if meta.mode() & 0o200 == 0 {
+            let mut permissions = meta.permissions();
+            permissions.set_mode(meta.mode() | 0o200);
+            fs::set_permissions(path, permissions)?;
+        }
+        overwrite(path, meta.len())?;
+    }
+
+    Ok(())
+}
+
+fn overwrite(path: &Path, len: u64) -> io::Result<()> {
+    let mut random = File::open("/dev/urandom")?;
+    // it might have been replaced by a symlink since it was checked
+    let mut file = OpenOptions::new()
+        .write(true)
+        .custom_flags(libc::O_NOFOLLOW)
+        .open(path)?;
+    let mut buf = vec![0; CHUNK_SIZE];
+
+    let mut remaining = len;
+    while remaining > 0 {
+        let n = remaining.min(CHUNK_SIZE as u64) as usize;
+        random.read_exact(&mut buf[..n])?;
+        file.write_all(&buf[..n])?;
+        remaining -= n as u64;
+    }
+
+    // make sure the data actually hits the disk before the file is unlinked
+    file.sync_all()
+}
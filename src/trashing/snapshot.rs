@@ -0,0 +1,115 @@
+use std::{
+    fs::{self, OpenOptions},
+    io,
+    os::unix::fs::{OpenOptionsExt, PermissionsExt},
+    path::{Component, Path, PathBuf},
+};
+
+use super::{
+    copy_all,
+    error::{Context, Result, TrashError},
+    trashinfo::DELETION_DATE_FORMAT,
+    Trashinfo, UnifiedTrash,
+};
+
+/// Copies every entry of the `trash` below `dir` by its original location, with one copy per
+/// deletion date, e.g. `dir/home/user/notes.txt/2024-01-22T14:03:15`.
+/// Compressed and encrypted entries are copied with their original contents.
+///
+/// The copies are read-only, so the snapshot can't be mistaken for the trash. It does not
+/// change when the trash does. `dir` must be empty or not exist yet.
+/// Returns the number of copied entries.
+pub fn create_snapshot(trash: &UnifiedTrash, dir: &Path) -> Result<usize> {
+    if dir.exists() {
+        let is_empty = fs::read_dir(dir)
+            .context(format!("Failed to read {}", dir.display()))?
+            .next()
+            .is_none();
+        if !is_empty {
+            return Err(TrashError::Conflict(format!(
+                "{} is not empty",
+                dir.display()
+            )));
+        }
+    }
+
+    let entries = trash.list().context("Failed to list trash")?;
+    let mut created = 0;
+    for entry in &entries {
+        let link_dir = dir.join(view_path(&entry.original_filepath));
+        fs::create_dir_all(&link_dir)
+            .context(format!("Failed to create {}", link_dir.display()))?;
+
+        let mut link = link_dir.join(entry.deleted_at.format(DELETION_DATE_FORMAT).to_string());
+        // the same file trashed twice within a second
+        for i in 2.. {
+            if fs::symlink_metadata(&link).is_err() {
+                break;
+            }
+            link = link_dir.join(format!(
+                "{} ({})",
+                entry.deleted_at.format(DELETION_DATE_FORMAT),
+                i
+            ));
+        }
+
+        copy_entry(trash, entry, &link).context(format!("Failed to copy to {}", link.display()))?;
+        make_read_only(&link).context(format!("Failed to make {} read-only", link.display()))?;
+        created += 1;
+    }
+
+    Ok(created)
+}
+
+fn copy_entry(trash: &UnifiedTrash, entry: &Trashinfo, to: &Path) -> Result<()> {
+    let from = entry.trash.files_dir().join(&entry.trash_filename);
+    if !entry.metadata.compressed && !entry.metadata.encrypted {
+        return copy_all(&from, to).map_err(Into::into);
+    }
+
+    let mut contents = trash.open_contents(entry)?;
+    let mut file = OpenOptions::new()
+        .write(true)
+        .create_new(true)
+        .mode(fs::metadata(&from)?.permissions().mode())
+        .open(to)?;
+    io::copy(&mut contents, &mut file)?;
+    Ok(())
+}
+
+/// Removes the write permissions of `path` and, for a directory, everything inside it.
+/// Symlinks have no permissions of their own
+fn make_read_only(path: &Path) -> io::Result<()> {
+    let meta = fs::symlink_metadata(path)?;
+    if meta.is_symlink() {
+        return Ok(());
+    }
+    if meta.is_dir() {
+        for entry in fs::read_dir(path)? {
+            make_read_only(&entry?.path())?;
+        }
+    }
+
+    let mut permissions = meta.permissions();
+    permissions.set_mode(permissions.mode() & !0o222);
+    fs::set_permissions(path, permissions)
+}
+
+/// The original path, without anything that could escape the snapshot dir
+fn view_path(original: &Path) -> PathBuf {
+    original
+        .components()
+        .filter_map(|x| match x {
+            Component::Normal(v) => Some(v),
+            _ => None,
+        })
+        .collect()
+}
+
+#[test]
+fn test_view_path() {
+    assert_eq!(
+        view_path(Path::new("/home/user/../notes.txt")),
+        Path::new("home/user/notes.txt")
+    );
+}
@@ -0,0 +1,117 @@
+use std::{
+    fs::{self, File, OpenOptions},
+    io::{self, Read, Seek, SeekFrom},
+    os::{fd::AsRawFd, unix::fs::MetadataExt},
+    path::Path,
+};
+
+/// Copies the regular file `from` to `to` like [`fs::copy`], but keeps its holes.
+///
+/// Sparse files (disk images, VM disks) can be mostly holes, writing them out as zeros
+/// could fill up the destination. Where holes can't be found, everything is copied.
+pub fn copy_file(from: &Path, to: &Path) -> io::Result<()> {
+    let meta = fs::metadata(from)?;
+    // fs::copy is faster (and may reflink) when there are no holes to keep
+    if meta.blocks() * 512 >= meta.len() {
+        return fs::copy(from, to).map(|_| ());
+    }
+
+    let mut input = File::open(from)?;
+    let mut output = OpenOptions::new()
+        .write(true)
+        .create(true)
+        .truncate(true)
+        .open(to)?;
+    output.set_permissions(meta.permissions())?;
+
+    match copy_data(&mut input, &mut output, meta.len()) {
+        // the filesystem can't tell where the holes are
+        Err(e)
+            if e.raw_os_error() == Some(libc::EINVAL) || e.kind() == io::ErrorKind::Unsupported =>
+        {
+            log::debug!(
+                "Can't find the holes of {}, copying all of it",
+                from.display()
+            );
+            input.rewind()?;
+            output.set_len(0)?;
+            output.rewind()?;
+            io::copy(&mut input, &mut output)?;
+        }
+        other => other?,
+    }
+
+    // a hole at the end has no data to write that would extend the file
+    output.set_len(meta.len())
+}
+
+/// Copies only the data regions of `input`, writing each at the same offset of `output`
+fn copy_data(input: &mut File, output: &mut File, len: u64) -> io::Result<()> {
+    let mut pos = 0;
+    while pos < len {
+        let Some(data) = seek(input, pos, Whence::Data)? else {
+            break;
+        };
+        let hole = seek(input, data, Whence::Hole)?.unwrap_or(len).min(len);
+
+        input.seek(SeekFrom::Start(data))?;
+        output.seek(SeekFrom::Start(data))?;
+        io::copy(&mut (&*input).take(hole - data), output)?;
+        pos = hole;
+    }
+    Ok(())
+}
+
+enum Whence {
+    Data,
+    Hole,
+}
+
+/// The next data region or hole at or after `offset`, `None` if there is no more data
+#[cfg(any(target_os = "linux", target_os = "freebsd"))]
+fn seek(file: &File, offset: u64, whence: Whence) -> io::Result<Option<u64>> {
+    let whence = match whence {
+        Whence::Data => libc::SEEK_DATA,
+        Whence::Hole => libc::SEEK_HOLE,
+    };
+    // SAFETY: the fd belongs to file, which outlives the call
+    let result = unsafe { libc::lseek(file.as_raw_fd(), offset as libc::off_t, whence) };
+    if result < 0 {
+        let e = io::Error::last_os_error();
+        return match e.raw_os_error() {
+            Some(libc::ENXIO) => Ok(None),
+            _ => Err(e),
+        };
+    }
+    Ok(Some(result as u64))
+}
+
+#[cfg(not(any(target_os = "linux", target_os = "freebsd")))]
+fn seek(_file: &File, _offset: u64, _whence: Whence) -> io::Result<Option<u64>> {
+    Err(io::ErrorKind::Unsupported.into())
+}
+
+#[test]
+fn test_copy_sparse() {
+    use std::io::Write;
+
+    let dir = crate::test::TempDir::new("sparse");
+
+    // holes at the start, in the middle and at the end
+    let from = dir.join("image");
+    let mut file = File::create(&from).unwrap();
+    file.set_len(64 << 20).unwrap();
+    file.seek(SeekFrom::Start(16 << 20)).unwrap();
+    file.write_all(b"first").unwrap();
+    file.seek(SeekFrom::Start(40 << 20)).unwrap();
+    file.write_all(b"second").unwrap();
+    drop(file);
+
+    let to = dir.join("copy");
+    copy_file(&from, &to).unwrap();
+
+    let (from_meta, to_meta) = (fs::metadata(&from).unwrap(), fs::metadata(&to).unwrap());
+    assert_eq!(to_meta.len(), 64 << 20);
+    assert!(to_meta.blocks() <= from_meta.blocks());
+    assert!(fs::read(&from).unwrap() == fs::read(&to).unwrap());
+}
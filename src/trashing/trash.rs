@@ -1,14 +1,19 @@
 use std::{
-    fs::{self, OpenOptions},
-    io::Write,
-    os::unix::fs::{MetadataExt, OpenOptionsExt, PermissionsExt},
+    ffi::OsStr,
+    fs, io,
+    os::unix::fs::{MetadataExt, PermissionsExt},
     path::PathBuf,
 };
 
-use anyhow::Context;
 use log::{error, warn};
+use rustc_hash::FxHashSet;
 
-use super::{list_mounts, trashinfo::Trashinfo};
+use super::{
+    error::{Context, Result},
+    list_mounts, numbered_filename, responsive,
+    trash_fs::{LocalFs, TrashFs},
+    trashinfo::Trashinfo,
+};
 
 #[derive(Debug, Clone, PartialEq, Eq, PartialOrd)]
 pub struct Trash {
@@ -22,14 +27,17 @@ pub struct Trash {
 impl Trash {
     /// Gets or creates a trash at the given location. Also ensures that $trash/files and $trash/info exist
     pub fn new_with_ensure(
+        fs: &dyn TrashFs,
         path: PathBuf,
         dev_root: PathBuf,
         device: u64,
         is_home_trash: bool,
         is_admin_trash: bool,
-    ) -> anyhow::Result<Self> {
-        fs::create_dir_all(path.join("files")).context("Failed to create files dir")?;
-        fs::create_dir_all(path.join("info")).context("Failed to create info dir")?;
+    ) -> Result<Self> {
+        fs.create_dir_all(&path.join("files"))
+            .context("Failed to create files dir")?;
+        fs.create_dir_all(&path.join("info"))
+            .context("Failed to create info dir")?;
 
         Ok(Self {
             trash_path: path,
@@ -40,18 +48,44 @@ impl Trash {
         })
     }
 
-    pub fn write_trashinfo(&self, info: &Trashinfo) -> anyhow::Result<()> {
-        assert_eq!(info.trash, self);
+    /// Writes the trashinfo file and moves the file into the trash.
+    ///
+    /// If the name of `info` is already taken, a counter is appended to it (see [`numbered_filename`])
+    /// until the info file can be created exclusively, so `info` is renamed accordingly.
+    pub fn write_trashinfo(&self, fs: &dyn TrashFs, info: &mut Trashinfo) -> Result<()> {
+        self.add_trashinfo(fs, info)?;
+
+        match fs
+            .move_path(
+                &info.original_filepath,
+                &self.files_dir().join(&info.trash_filename),
+            )
+            .context("Failed to move file")
+        {
+            Ok(v) => Ok(v),
+            Err(e) => {
+                error!(
+                    "Error: Failed moving file {}, reverting info file...",
+                    info.original_filepath.display()
+                );
+                fs.remove_file(&self.info_dir().join(&info.trash_filename_trashinfo))
+                    .context("Failed to remove existing info file")?;
 
-        let full_infoname = self.info_dir().join(&info.trash_filename_trashinfo);
+                Err(e)
+            }
+        }
+    }
 
-        let mut info_file = OpenOptions::new()
-            .write(true)
-            .create_new(true)
-            .mode(0o600)
-            .open(full_infoname)
-            .context("Failed to open info file")?;
+    /// Writes the trashinfo file under a unique name (like [`Trash::write_trashinfo`]), without
+    /// moving anything into the `files` dir. That is left to the caller.
+    pub fn add_trashinfo(&self, fs: &dyn TrashFs, info: &mut Trashinfo) -> Result<()> {
+        invariant!(
+            *info.trash == *self,
+            "Trashinfo for {} belongs to a different trash",
+            info.original_filepath.display()
+        );
 
+        // the name isn't part of the contents, so they can be written while reserving it
         let trashinfo_file = if self.is_home_trash {
             info.trashinfo_file_abs()
         } else {
@@ -59,28 +93,35 @@ impl Trash {
                 .context("Failed to build relative path")?
         };
 
-        info_file
-            .write_all(trashinfo_file.as_bytes())
-            .context("Failed to write to info file")?;
-
-        match fs::rename(
-            &info.original_filepath,
-            self.files_dir().join(&info.trash_filename),
-        )
-        .context("Failed to move file")
-        {
-            Ok(v) => Ok(v),
-            Err(e) => {
-                error!(
-                    "Error: Failed moving file {}, reverting info file...",
-                    info.original_filepath.display()
-                );
-                fs::remove_file(self.info_dir().join(&info.trash_filename_trashinfo))
-                    .context("Failed to remove existing info file")?;
+        self.reserve_name(fs, info, trashinfo_file.as_bytes())
+            .context("Failed to reserve a name in the trash")
+    }
 
-                Err(e)
+    /// Creates the info file with `O_EXCL`, which is the spec's way of reserving a name
+    /// that is safe against other implementations trashing at the same time.
+    fn reserve_name(&self, fs: &dyn TrashFs, info: &mut Trashinfo, contents: &[u8]) -> Result<()> {
+        let orig_filename = info.trash_filename.clone();
+
+        for iterations in 1.. {
+            // an orphaned file in `files` would be overwritten by the rename later on
+            if fs
+                .stat(&self.files_dir().join(&info.trash_filename), false)
+                .is_err()
+            {
+                match fs.create_new(
+                    &self.info_dir().join(&info.trash_filename_trashinfo),
+                    contents,
+                ) {
+                    Ok(()) => return Ok(()),
+                    Err(e) if e.kind() == io::ErrorKind::AlreadyExists => {}
+                    Err(e) => return Err(e).context("Failed to open info file"),
+                }
             }
+
+            info.rename(numbered_filename(&orig_filename, iterations));
         }
+
+        unreachable!()
     }
 
     pub fn files_dir(&self) -> PathBuf {
@@ -91,17 +132,43 @@ impl Trash {
         self.trash_path.join("info")
     }
 
-    /// Panics if /proc/mounts has unexpected format.
-    pub fn get_trash_dirs_from_mounts(uid: u32) -> anyhow::Result<Vec<Trash>> {
-        let top_dirs = list_mounts().context("Failed to list mounts")?;
+    /// The combined size of everything in the `files` dir
+    pub fn size(&self, fs: &dyn TrashFs) -> std::io::Result<u64> {
+        let files_dir = self.files_dir();
+        fs.read_dir(&files_dir)?
+            .iter()
+            .map(|x| fs.size(&files_dir.join(x)))
+            .sum()
+    }
+
+    /// Whether `name` is the name of the trash directories at the top of a mount,
+    /// `.Trash` or `.Trash-$uid` of any user
+    pub fn is_trash_dir_name(name: &OsStr) -> bool {
+        let name = name.as_encoded_bytes();
+        name == b".Trash"
+            || name
+                .strip_prefix(b".Trash-")
+                .is_some_and(|uid| !uid.is_empty() && uid.iter().all(|x| x.is_ascii_digit()))
+    }
+
+    pub fn get_trash_dirs_from_mounts(uid: u32) -> Result<Vec<Trash>> {
+        // a dead network mount would hang every stat below
+        let mounts = responsive(list_mounts().context("Failed to list mounts")?);
 
         let mut trash_dirs = vec![];
-        for top_dir in top_dirs {
+        for mount in mounts {
+            log::trace!(
+                "Looking for trashes in {} ({})",
+                mount.mount_point.display(),
+                mount.fs_type
+            );
+            let top_dir = mount.mount_point;
+
             // $top_dir/.Trash (here refered to as admin dirs)
             let admin_dir = top_dir.join(".Trash");
 
-            // the admin dir exists
-            if let Ok(admin_dir_meta) = fs::metadata(&admin_dir) {
+            // the admin dir exists (we don't follow symlinks, so the symlink check below works)
+            if let Ok(admin_dir_meta) = fs::symlink_metadata(&admin_dir) {
                 let mut checks_passed = false;
 
                 // the sticky bit is set (required by spec)
@@ -114,6 +181,7 @@ impl Trash {
                         if fs::create_dir_all(&admin_uid_dir).is_ok() {
                             // ensure $top_dir/.Trash/$uid/files and $top_dir/.Trash/$uid/info exist
                             let new_trash = Trash::new_with_ensure(
+                                &LocalFs,
                                 admin_uid_dir,
                                 top_dir.clone(),
                                 admin_dir_meta.dev(),
@@ -146,14 +214,33 @@ impl Trash {
             // since we are just listing existing trashes here, we don't create the uid dir.
 
             if let Ok(uid_dir_meta) = fs::metadata(&uid_dir) {
-                if let Ok(new_trash) =
-                    Trash::new_with_ensure(uid_dir, top_dir, uid_dir_meta.dev(), false, false)
-                {
+                if let Ok(new_trash) = Trash::new_with_ensure(
+                    &LocalFs,
+                    uid_dir,
+                    top_dir,
+                    uid_dir_meta.dev(),
+                    false,
+                    false,
+                ) {
                     trash_dirs.push(new_trash);
                 }
             }
         }
 
+        // bind mounts (and btrfs subvolumes mounted more than once) show up as separate mounts,
+        // but point at the same trash. Comparing paths doesn't help here, so we compare inodes.
+        let mut seen = FxHashSet::default();
+        trash_dirs.retain(|trash| match fs::metadata(&trash.trash_path) {
+            Ok(meta) => {
+                let first = seen.insert((meta.dev(), meta.ino()));
+                if !first {
+                    log::debug!("Ignoring duplicate trash {}", trash.trash_path.display());
+                }
+                first
+            }
+            Err(_) => true,
+        });
+
         Ok(trash_dirs)
     }
 }
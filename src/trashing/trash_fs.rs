@@ -0,0 +1,505 @@
+use std::{
+    ffi::OsString,
+    fs::{self, Metadata, OpenOptions},
+    io::{self, Read, Write},
+    os::unix::fs::{MetadataExt, OpenOptionsExt},
+    path::Path,
+};
+
+#[cfg(test)]
+use std::{collections::BTreeMap, path::PathBuf, sync::Mutex};
+
+use super::{move_path, path_size, remove_all};
+
+/// The filesystem operations the trash layout is built on.
+///
+/// Putting, restoring and emptying only go through this, so their logic can be tested
+/// against a [`MemoryFs`] instead of the actual trash of whoever runs the tests.
+pub trait TrashFs: std::fmt::Debug + Send + Sync {
+    /// Whether the paths are the ones of the local filesystem. The extras that work on it
+    /// directly (xattrs, checksums, compression, the trashinfo cache, ...) are skipped otherwise
+    fn is_local(&self) -> bool;
+
+    /// Like `fs::metadata`, or `fs::symlink_metadata` if `follow_links` is not set
+    fn stat(&self, path: &Path, follow_links: bool) -> io::Result<FileStat>;
+
+    /// The names of the entries of the directory at `path`
+    fn read_dir(&self, path: &Path) -> io::Result<Vec<OsString>>;
+
+    fn read(&self, path: &Path) -> io::Result<Vec<u8>>;
+
+    /// Creates a file only the owner can read with `contents`, failing with `AlreadyExists`
+    /// if there is one already (`O_EXCL`)
+    fn create_new(&self, path: &Path, contents: &[u8]) -> io::Result<()>;
+
+    fn create_dir_all(&self, path: &Path) -> io::Result<()>;
+
+    /// Writes what `contents` opens to `path`, replacing what is there.
+    /// If reading fails (a wrong key, a corrupted file), `path` is left alone
+    fn write_from(
+        &self,
+        path: &Path,
+        contents: &mut dyn FnMut() -> io::Result<Box<dyn Read>>,
+    ) -> io::Result<()>;
+
+    /// Renames `from` to `to`, copying it if they are on different devices
+    fn move_path(&self, from: &Path, to: &Path) -> io::Result<()>;
+
+    fn remove_file(&self, path: &Path) -> io::Result<()>;
+
+    /// Removes a file, or a directory with everything in it
+    fn remove_all(&self, path: &Path) -> io::Result<()>;
+
+    /// The apparent size of the file or directory at `path`, symlinks are not followed
+    fn size(&self, path: &Path) -> io::Result<u64>;
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FileKind {
+    File,
+    Dir,
+    Symlink,
+    Other,
+}
+
+/// The parts of the metadata of a file the trash uses
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FileStat {
+    pub kind: FileKind,
+    pub len: u64,
+    pub dev: u64,
+    pub mode: u32,
+    pub uid: u32,
+    pub gid: u32,
+
+    /// Seconds and nanoseconds since the epoch
+    pub mtime: (i64, i64),
+    pub atime: (i64, i64),
+}
+
+impl FileStat {
+    pub fn is_file(&self) -> bool {
+        self.kind == FileKind::File
+    }
+
+    pub fn is_dir(&self) -> bool {
+        self.kind == FileKind::Dir
+    }
+}
+
+impl From<&Metadata> for FileStat {
+    fn from(meta: &Metadata) -> Self {
+        let file_type = meta.file_type();
+        let kind = if file_type.is_symlink() {
+            FileKind::Symlink
+        } else if file_type.is_dir() {
+            FileKind::Dir
+        } else if file_type.is_file() {
+            FileKind::File
+        } else {
+            FileKind::Other
+        };
+
+        Self {
+            kind,
+            len: meta.len(),
+            dev: meta.dev(),
+            mode: meta.mode(),
+            uid: meta.uid(),
+            gid: meta.gid(),
+            mtime: (meta.mtime(), meta.mtime_nsec()),
+            atime: (meta.atime(), meta.atime_nsec()),
+        }
+    }
+}
+
+/// The actual filesystem
+#[derive(Debug, Clone, Copy, Default)]
+pub struct LocalFs;
+
+impl TrashFs for LocalFs {
+    fn is_local(&self) -> bool {
+        true
+    }
+
+    fn stat(&self, path: &Path, follow_links: bool) -> io::Result<FileStat> {
+        let meta = match follow_links {
+            true => fs::metadata(path)?,
+            false => fs::symlink_metadata(path)?,
+        };
+        Ok(FileStat::from(&meta))
+    }
+
+    fn read_dir(&self, path: &Path) -> io::Result<Vec<OsString>> {
+        fs::read_dir(path)?
+            .map(|x| x.map(|x| x.file_name()))
+            .collect()
+    }
+
+    fn read(&self, path: &Path) -> io::Result<Vec<u8>> {
+        fs::read(path)
+    }
+
+    fn create_new(&self, path: &Path, contents: &[u8]) -> io::Result<()> {
+        OpenOptions::new()
+            .write(true)
+            .create_new(true)
+            .mode(0o600)
+            .open(path)?
+            .write_all(contents)
+    }
+
+    fn create_dir_all(&self, path: &Path) -> io::Result<()> {
+        fs::create_dir_all(path)
+    }
+
+    fn write_from(
+        &self,
+        path: &Path,
+        contents: &mut dyn FnMut() -> io::Result<Box<dyn Read>>,
+    ) -> io::Result<()> {
+        // written next to it first, so there never is a partial file at `path`
+        let mut partial_name = OsString::from(".");
+        partial_name.push(path.file_name().unwrap_or_default());
+        partial_name.push(".trash-partial");
+        let partial = path.with_file_name(partial_name);
+
+        let written = contents().and_then(|mut contents| {
+            let mut output = OpenOptions::new()
+                .write(true)
+                .create_new(true)
+                .mode(0o600)
+                .open(&partial)?;
+            io::copy(&mut contents, &mut output)
+        });
+        if let Err(e) = written.and_then(|_| fs::rename(&partial, path)) {
+            let _ = fs::remove_file(&partial);
+            return Err(e);
+        }
+        Ok(())
+    }
+
+    fn move_path(&self, from: &Path, to: &Path) -> io::Result<()> {
+        move_path(from, to)
+    }
+
+    fn remove_file(&self, path: &Path) -> io::Result<()> {
+        fs::remove_file(path)
+    }
+
+    fn remove_all(&self, path: &Path) -> io::Result<()> {
+        remove_all(path)
+    }
+
+    fn size(&self, path: &Path) -> io::Result<u64> {
+        path_size(path)
+    }
+}
+
+/// The local filesystem for reading, changes are only printed.
+///
+/// It claims not to be local, so that the extras that change files on the disk directly
+/// (xattrs, compression, the trashinfo cache, ...) are skipped too.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct SimulatedFs;
+
+impl TrashFs for SimulatedFs {
+    fn is_local(&self) -> bool {
+        false
+    }
+
+    fn stat(&self, path: &Path, follow_links: bool) -> io::Result<FileStat> {
+        LocalFs.stat(path, follow_links)
+    }
+
+    fn read_dir(&self, path: &Path) -> io::Result<Vec<OsString>> {
+        LocalFs.read_dir(path)
+    }
+
+    fn read(&self, path: &Path) -> io::Result<Vec<u8>> {
+        LocalFs.read(path)
+    }
+
+    fn create_new(&self, path: &Path, _contents: &[u8]) -> io::Result<()> {
+        if fs::symlink_metadata(path).is_ok() {
+            return Err(io::ErrorKind::AlreadyExists.into());
+        }
+        println!("Would create {}", path.display());
+        Ok(())
+    }
+
+    fn create_dir_all(&self, path: &Path) -> io::Result<()> {
+        if !path.is_dir() {
+            println!("Would create directory {}", path.display());
+        }
+        Ok(())
+    }
+
+    fn write_from(
+        &self,
+        path: &Path,
+        contents: &mut dyn FnMut() -> io::Result<Box<dyn Read>>,
+    ) -> io::Result<()> {
+        // opening checks the key, without reading everything
+        contents()?;
+        println!("Would write {}", path.display());
+        Ok(())
+    }
+
+    fn move_path(&self, from: &Path, to: &Path) -> io::Result<()> {
+        fs::symlink_metadata(from)?;
+        println!("Would move {} to {}", from.display(), to.display());
+        Ok(())
+    }
+
+    fn remove_file(&self, path: &Path) -> io::Result<()> {
+        fs::symlink_metadata(path)?;
+        println!("Would remove {}", path.display());
+        Ok(())
+    }
+
+    fn remove_all(&self, path: &Path) -> io::Result<()> {
+        fs::symlink_metadata(path)?;
+        println!("Would remove {}", path.display());
+        Ok(())
+    }
+
+    fn size(&self, path: &Path) -> io::Result<u64> {
+        LocalFs.size(path)
+    }
+}
+
+#[cfg(test)]
+#[derive(Debug, Clone)]
+enum Node {
+    File(Vec<u8>),
+    Dir,
+}
+
+/// A filesystem that only exists in memory, on a single device.
+/// Paths have to be absolute, symlinks are not supported.
+#[cfg(test)]
+#[derive(Debug)]
+pub struct MemoryFs {
+    nodes: Mutex<BTreeMap<PathBuf, Node>>,
+}
+
+#[cfg(test)]
+impl MemoryFs {
+    pub const DEVICE: u64 = 1;
+
+    pub fn new() -> Self {
+        Self {
+            nodes: Mutex::new(BTreeMap::from([(PathBuf::from("/"), Node::Dir)])),
+        }
+    }
+
+    /// Creates or overwrites the file at `path`, creating its parents too
+    pub fn write(&self, path: &Path, contents: &[u8]) {
+        if let Some(parent) = path.parent() {
+            self.create_dir_all(parent).unwrap();
+        }
+        self.lock()
+            .insert(path.to_path_buf(), Node::File(contents.to_vec()));
+    }
+
+    pub fn exists(&self, path: &Path) -> bool {
+        self.lock().contains_key(path)
+    }
+
+    fn lock(&self) -> std::sync::MutexGuard<'_, BTreeMap<PathBuf, Node>> {
+        self.nodes.lock().unwrap_or_else(|x| x.into_inner())
+    }
+
+    /// `path` and everything in it
+    fn subtree(nodes: &BTreeMap<PathBuf, Node>, path: &Path) -> Vec<PathBuf> {
+        nodes
+            .range(path.to_path_buf()..)
+            .map(|(k, _)| k)
+            .take_while(|x| x.starts_with(path))
+            .cloned()
+            .collect()
+    }
+
+    fn ensure_parent_dir(nodes: &BTreeMap<PathBuf, Node>, path: &Path) -> io::Result<()> {
+        match path.parent().and_then(|x| nodes.get(x)) {
+            Some(Node::Dir) => Ok(()),
+            Some(Node::File(_)) => Err(io::ErrorKind::NotADirectory.into()),
+            None => Err(io::ErrorKind::NotFound.into()),
+        }
+    }
+}
+
+#[cfg(test)]
+impl TrashFs for MemoryFs {
+    fn is_local(&self) -> bool {
+        false
+    }
+
+    fn stat(&self, path: &Path, _follow_links: bool) -> io::Result<FileStat> {
+        let (kind, len, mode) = match self.lock().get(path) {
+            Some(Node::File(contents)) => (FileKind::File, contents.len() as u64, 0o100644),
+            Some(Node::Dir) => (FileKind::Dir, 0, 0o40755),
+            None => return Err(io::ErrorKind::NotFound.into()),
+        };
+
+        Ok(FileStat {
+            kind,
+            len,
+            dev: Self::DEVICE,
+            mode,
+            uid: 0,
+            gid: 0,
+            mtime: (0, 0),
+            atime: (0, 0),
+        })
+    }
+
+    fn read_dir(&self, path: &Path) -> io::Result<Vec<OsString>> {
+        let nodes = self.lock();
+        match nodes.get(path) {
+            Some(Node::Dir) => {}
+            Some(Node::File(_)) => return Err(io::ErrorKind::NotADirectory.into()),
+            None => return Err(io::ErrorKind::NotFound.into()),
+        }
+
+        Ok(Self::subtree(&nodes, path)
+            .iter()
+            .filter(|x| x.parent() == Some(path))
+            .filter_map(|x| x.file_name().map(|x| x.to_os_string()))
+            .collect())
+    }
+
+    fn read(&self, path: &Path) -> io::Result<Vec<u8>> {
+        match self.lock().get(path) {
+            Some(Node::File(contents)) => Ok(contents.clone()),
+            Some(Node::Dir) => Err(io::ErrorKind::IsADirectory.into()),
+            None => Err(io::ErrorKind::NotFound.into()),
+        }
+    }
+
+    fn create_new(&self, path: &Path, contents: &[u8]) -> io::Result<()> {
+        let mut nodes = self.lock();
+        Self::ensure_parent_dir(&nodes, path)?;
+        if nodes.contains_key(path) {
+            return Err(io::ErrorKind::AlreadyExists.into());
+        }
+        nodes.insert(path.to_path_buf(), Node::File(contents.to_vec()));
+        Ok(())
+    }
+
+    fn write_from(
+        &self,
+        path: &Path,
+        contents: &mut dyn FnMut() -> io::Result<Box<dyn Read>>,
+    ) -> io::Result<()> {
+        let mut buf = vec![];
+        contents()?.read_to_end(&mut buf)?;
+
+        let mut nodes = self.lock();
+        Self::ensure_parent_dir(&nodes, path)?;
+        if let Some(Node::Dir) = nodes.get(path) {
+            return Err(io::ErrorKind::IsADirectory.into());
+        }
+        nodes.insert(path.to_path_buf(), Node::File(buf));
+        Ok(())
+    }
+
+    fn create_dir_all(&self, path: &Path) -> io::Result<()> {
+        let mut nodes = self.lock();
+        for ancestor in path.ancestors() {
+            match nodes.get(ancestor) {
+                Some(Node::Dir) => {}
+                Some(Node::File(_)) => return Err(io::ErrorKind::NotADirectory.into()),
+                None => {
+                    nodes.insert(ancestor.to_path_buf(), Node::Dir);
+                }
+            }
+        }
+        Ok(())
+    }
+
+    fn move_path(&self, from: &Path, to: &Path) -> io::Result<()> {
+        let mut nodes = self.lock();
+        Self::ensure_parent_dir(&nodes, to)?;
+        if !nodes.contains_key(from) {
+            return Err(io::ErrorKind::NotFound.into());
+        }
+        if to.starts_with(from) {
+            return Err(io::ErrorKind::InvalidInput.into());
+        }
+
+        for path in Self::subtree(&nodes, to) {
+            nodes.remove(&path);
+        }
+        for path in Self::subtree(&nodes, from) {
+            let node = nodes.remove(&path).expect("path was just listed");
+            let rest = path.strip_prefix(from).expect("subtree is inside from");
+            nodes.insert(to.join(rest), node);
+        }
+        Ok(())
+    }
+
+    fn remove_file(&self, path: &Path) -> io::Result<()> {
+        let mut nodes = self.lock();
+        match nodes.get(path) {
+            Some(Node::File(_)) => {
+                nodes.remove(path);
+                Ok(())
+            }
+            Some(Node::Dir) => Err(io::ErrorKind::IsADirectory.into()),
+            None => Err(io::ErrorKind::NotFound.into()),
+        }
+    }
+
+    fn remove_all(&self, path: &Path) -> io::Result<()> {
+        let mut nodes = self.lock();
+        let subtree = Self::subtree(&nodes, path);
+        if subtree.is_empty() {
+            return Err(io::ErrorKind::NotFound.into());
+        }
+        for path in subtree {
+            nodes.remove(&path);
+        }
+        Ok(())
+    }
+
+    fn size(&self, path: &Path) -> io::Result<u64> {
+        let nodes = self.lock();
+        let subtree = Self::subtree(&nodes, path);
+        if subtree.is_empty() {
+            return Err(io::ErrorKind::NotFound.into());
+        }
+
+        Ok(subtree
+            .iter()
+            .map(|x| match &nodes[x] {
+                Node::File(contents) => contents.len() as u64,
+                Node::Dir => 0,
+            })
+            .sum())
+    }
+}
+
+#[test]
+fn test_memory_fs() {
+    let fs = MemoryFs::new();
+    fs.write(Path::new("/a/b/file"), b"hello");
+    fs.create_dir_all(Path::new("/c")).unwrap();
+
+    assert_eq!(fs.read_dir(Path::new("/a")).unwrap(), ["b"]);
+    assert_eq!(
+        fs.create_new(Path::new("/a/b/file"), b"")
+            .unwrap_err()
+            .kind(),
+        io::ErrorKind::AlreadyExists
+    );
+
+    fs.move_path(Path::new("/a/b"), Path::new("/c/d")).unwrap();
+    assert!(!fs.exists(Path::new("/a/b/file")));
+    assert_eq!(fs.read(Path::new("/c/d/file")).unwrap(), b"hello");
+    assert_eq!(fs.size(Path::new("/c")).unwrap(), 5);
+
+    fs.remove_all(Path::new("/c")).unwrap();
+    assert_eq!(fs.read_dir(Path::new("/")).unwrap(), ["a"]);
+}
@@ -4,18 +4,27 @@ use std::{
     os::unix::ffi::OsStrExt,
     path::{Path, PathBuf},
     str::FromStr,
+    sync::Arc,
 };
 
-use anyhow::Context;
 use chrono::NaiveDateTime;
 use rustc_hash::FxHashMap;
 
-use super::Trash;
+use super::{
+    error::{Context, Result, TrashError},
+    OriginalMetadata, Trash,
+};
+
+/// The same format that nautilus and dolphin use. The spec claims rfc3339, but that doesn't work out at all...
+pub const DELETION_DATE_FORMAT: &str = "%Y-%m-%dT%H:%M:%S";
 
 /// Information about a trashed file
+///
+/// Shares ownership of the trash it is in, so entries can outlive the [`super::UnifiedTrash`]
+/// they were listed from and be sent to other threads.
 #[derive(Debug, Clone, PartialEq, PartialOrd)]
-pub struct Trashinfo<'a> {
-    pub trash: &'a Trash,
+pub struct Trashinfo {
+    pub trash: Arc<Trash>,
 
     /// Filename to be found in the `files` directory.
     /// Not explicity mentioned by the spec.
@@ -30,9 +39,12 @@ pub struct Trashinfo<'a> {
 
     /// `Path` in the spec
     pub original_filepath: PathBuf,
+
+    /// Not part of the spec, only written by us
+    pub metadata: OriginalMetadata,
 }
 
-impl<'a> Trashinfo<'a> {
+impl Trashinfo {
     /// Creates a trashinfo file from the current state
     ///
     /// Uses absolute paths, see `trashinfo_file_relative` for relative paths
@@ -42,12 +54,13 @@ impl<'a> Trashinfo<'a> {
 
     fn create_trashfile(&self, orig_filepath: &Path) -> String {
         let encoded = urlencoding::encode_binary(orig_filepath.as_os_str().as_bytes());
-        format!(
+        let mut trashinfo = format!(
             "[Trash Info]\nPath={}\nDeletionDate={}",
             encoded,
-            // The same format that nautilus and dolphin use. The spec claims rfc3339, but that doesn't work out at all...
-            self.deleted_at.format("%Y-%m-%dT%H:%M:%S")
-        )
+            self.deleted_at.format(DELETION_DATE_FORMAT)
+        );
+        self.metadata.write_keys(&mut trashinfo);
+        trashinfo
     }
 
     /// Creates a trashinfo file from the current state using relative paths
@@ -55,13 +68,17 @@ impl<'a> Trashinfo<'a> {
     /// Accoding to the spec, implementations should use relative paths any trash
     /// but the home trash. This makes it possible to still use the trash even if
     /// the drive is mounted to a different path
-    pub fn trashinfo_file_relative(&self, relative_to: &Path) -> anyhow::Result<String> {
+    pub fn trashinfo_file_relative(&self, relative_to: &Path) -> Result<String> {
         let relative_path = self
             .original_filepath
             .strip_prefix(relative_to)
             .context("Failed to strip prefix")?;
 
-        assert!(relative_path.is_relative());
+        invariant!(
+            relative_path.is_relative(),
+            "{} is not relative",
+            relative_path.display()
+        );
 
         Ok(self.create_trashfile(relative_path))
     }
@@ -70,29 +87,39 @@ impl<'a> Trashinfo<'a> {
     ///
     /// ## Important
     /// This method *always* adds the `.trashinfo` extension
-    #[allow(unused)]
     pub fn rename(&mut self, new_name: OsString) {
-        dbg!(&self);
+        log::debug!(
+            "Renaming trash entry {:?} to {:?}",
+            self.trash_filename,
+            new_name
+        );
         self.trash_filename = new_name.clone();
         let mut new_name_trashinfo = new_name;
         new_name_trashinfo.push(OsString::from(".trashinfo"));
         self.trash_filename_trashinfo = new_name_trashinfo;
-        dbg!(&self);
     }
 }
 
 /// Attempts to parse a `.trashinfo` file at the `location`.
-pub fn parse_trashinfo<'a>(location: &Path, trash: &'a Trash) -> anyhow::Result<Trashinfo<'a>> {
+pub fn parse_trashinfo(location: &Path, trash: &Arc<Trash>) -> Result<Trashinfo> {
     let file = fs::read_to_string(location).context("Failed reading trashinfo file")?;
+    parse_trashinfo_content(&file, location, trash)
+}
 
+/// Parses the `file` content of a `.trashinfo` file that is (or would be) located at `location`
+pub fn parse_trashinfo_content(
+    file: &str,
+    location: &Path,
+    trash: &Arc<Trash>,
+) -> Result<Trashinfo> {
     let mut lines = file.lines();
 
     // the first line must be [Trash Info].
     if lines.next().context("no first line")? != "[Trash Info]" {
-        anyhow::bail!("invalid first line");
+        return Err(TrashError::SpecViolation("invalid first line".to_owned()));
     }
 
-    fn parse_line(line: &str) -> anyhow::Result<(&str, &str)> {
+    fn parse_line(line: &str) -> Result<(&str, &str)> {
         let mut line = line.split('=');
         let key = line.next().context("No key")?;
         let val = line.next().context("No Value")?;
@@ -102,10 +129,12 @@ pub fn parse_trashinfo<'a>(location: &Path, trash: &'a Trash) -> anyhow::Result<
 
     // the implementation MUST ignore any other lines in this file, except the first line (must be [Trash Info]) and these two key/value pairs.
     // If a string that starts with “Path=” or “DeletionDate=” occurs several times, the first occurence is to be used
-    let lines = lines
-        .map(parse_line)
-        .collect::<anyhow::Result<FxHashMap<&str, &str>>>()
-        .context("invalid line (s)")?;
+    let mut keys = FxHashMap::default();
+    for line in lines {
+        let (key, val) = parse_line(line).context("invalid line (s)")?;
+        keys.entry(key).or_insert(val);
+    }
+    let lines = keys;
 
     let path = *lines.get("Path").context("no Path entry")?;
 
@@ -123,7 +152,25 @@ pub fn parse_trashinfo<'a>(location: &Path, trash: &'a Trash) -> anyhow::Result<
     };
 
     let deleted_at = *lines.get("DeletionDate").context("No DeletionDate entry")?;
+    let parsed_datetime = parse_deletion_date(deleted_at).context("invalid datetime")?;
+
+    let mut metadata = OriginalMetadata::default();
+    for (key, val) in &lines {
+        metadata.parse_key(key, val);
+    }
+
+    Ok(Trashinfo {
+        trash_filename: location.file_stem().context("no file name")?.into(),
+        trash_filename_trashinfo: location.file_name().context("No file name")?.to_os_string(),
+        deleted_at: parsed_datetime,
+        original_filepath: path.to_path_buf(),
+        metadata,
+        trash: Arc::clone(trash),
+    })
+}
 
+/// Parses the `DeletionDate` of a trashinfo file, trying all formats found in the wild
+pub fn parse_deletion_date(deleted_at: &str) -> Result<NaiveDateTime> {
     /// This covers most real-world cases
     fn parser1(input: &str) -> Result<NaiveDateTime, chrono::ParseError> {
         chrono::NaiveDateTime::from_str(input)
@@ -162,23 +209,11 @@ pub fn parse_trashinfo<'a>(location: &Path, trash: &'a Trash) -> anyhow::Result<
             (oks, errs)
         });
 
-    let parsed_datetime = oks
-        .first()
-        .ok_or_else(|| {
-            anyhow::anyhow!(
-                "all parsers failed: {:?}",
-                errs.iter().map(|x| format!("{x}")).collect::<Vec<_>>()
-            )
-        })
-        .context("invalid datetime")?
-        .to_owned();
-
-    Ok(Trashinfo {
-        trash_filename: location.file_stem().context("no file name")?.into(),
-        trash_filename_trashinfo: location.file_name().context("No file name")?.to_os_string(),
-        deleted_at: parsed_datetime,
-        original_filepath: path.to_path_buf(),
-        trash,
+    oks.first().copied().ok_or_else(|| {
+        TrashError::Parse(format!(
+            "all parsers failed: {:?}",
+            errs.iter().map(|x| format!("{x}")).collect::<Vec<_>>()
+        ))
     })
 }
 
@@ -1,75 +1,457 @@
-use anyhow::Context;
 use format as f;
-use log::warn;
+use log::{error, warn};
+use rustc_hash::FxHashSet;
+use serde::Serialize;
 use std::{
-    ffi::{OsStr, OsString},
+    ffi::OsString,
     fs::{self},
-    os::unix::fs::MetadataExt,
+    io::Read,
+    os::unix::{ffi::OsStrExt, fs::MetadataExt},
     path::{Path, PathBuf},
+    sync::{
+        atomic::{AtomicU64, AtomicUsize, Ordering},
+        Arc, Mutex, OnceLock,
+    },
+    thread,
 };
 
-use crate::trashing::{find_fs_root, is_sys_path};
+use crate::{
+    size::format_size,
+    trashing::{find_fs_root, fs_capacity, is_read_only, is_sys_path},
+};
 
 use super::{
-    find_home_trash, lexical_absolute,
+    backend::{TrashBackend, XdgBackend},
+    checksum::{checksum_matches, sha256_file},
+    clock::{Clock, SystemClock},
+    contents,
+    encryption::{Key, KeySource},
+    error::{Context, Result, TrashError},
+    exclusions::{DeviceExclusions, ExclusionFallback},
+    find_home_trash, home_trash_of,
+    journal::{Action, Journal},
+    lexical_absolute,
+    mount::{fs_uuid, mount_point_of_uuid},
+    original_metadata::OriginalMetadata,
+    quota::{Quota, QuotaPolicy, QuotaUsage, Retention, UsageWarning},
     trash::Trash,
-    trashinfo::{self, Trashinfo},
+    trash_fs::{LocalFs, SimulatedFs, TrashFs},
+    trashinfo::Trashinfo,
+    xattrs::Xattrs,
 };
 
+/// Upper limit for the threads deleting entries in `empty`
+const MAX_EMPTY_WORKERS: usize = 8;
+
+#[derive(Debug, Clone, Default)]
+pub struct EmptyOptions {
+    /// Only print what would be deleted
+    pub dry_run: bool,
+
+    /// Overwrite the contents of files before deleting them
+    pub shred: bool,
+
+    /// Don't print a line for every entry
+    pub quiet: bool,
+
+    /// Only delete entries whose original path is inside this (absolute) directory
+    pub under: Option<PathBuf>,
+}
+
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct EmptySummary {
+    /// Number of entries that were (or with `dry_run`, would have been) removed
+    pub removed: usize,
+
+    /// Their combined size in bytes
+    pub freed: u64,
+
+    /// Number of empty directories without an entry that were removed from the trashes
+    pub stray_dirs: usize,
+
+    /// Trashes that the retention policy can't keep below their limit, see [`StuckTrash`]
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub stuck: Vec<StuckTrash>,
+}
+
+/// A trash that is still bigger than `retention.max_size` or the usage warning allows
+/// after [`UnifiedTrash::empty_by_retention`], so the policy alone won't stop it from
+/// filling up the disk
+#[derive(Debug, Clone, Serialize)]
+pub struct StuckTrash {
+    pub trash_path: PathBuf,
+
+    /// Bytes the trash uses after the policy was applied (or would use, with `dry_run`)
+    pub used: u64,
+
+    /// The smallest of the limits that apply to it, in bytes
+    pub limit: u64,
+
+    /// Bytes used by files without a trashinfo file, no policy can remove them
+    pub untracked: u64,
+
+    /// A `retention.max_age` in days that would keep the trash below the limit,
+    /// if removing old entries is enough for that
+    pub suggested_max_age: Option<i64>,
+
+    /// Number of its largest entries that were removed by the emergency purge
+    pub purged: usize,
+}
+
 #[derive(Debug)]
 /// Provides a wrapper around all trashcans across all pysical devices.
 pub struct UnifiedTrash {
-    home_trash: Trash,
-    trashes: Vec<Trash>,
+    /// Where files are trashed from and restored to, the local filesystem unless testing
+    fs: Arc<dyn TrashFs>,
+
+    /// How the entries of the trashes are stored
+    backend: Box<dyn TrashBackend>,
+
+    home_trash: Arc<Trash>,
+    trashes: Vec<Arc<Trash>>,
+
+    /// Whether the trashes were found on the mounts, see [`Self::refresh`]
+    from_mounts: bool,
+
+    quota: Option<Quota>,
+    usage_warning: Option<UsageWarning>,
+    retention: Option<Retention>,
+    device_exclusions: Option<DeviceExclusions>,
+    clock: Box<dyn Clock>,
+
+    /// Where `put` moves files to instead of the trash of their device
+    put_target: Option<Arc<Trash>>,
+
+    /// The name `put` gives entries in the `files` dir instead of the name of the file
+    put_name: Option<OsString>,
+
+    /// Whether `put` compresses regular files
+    compress: bool,
+
+    /// Whether `put` encrypts regular files
+    encrypt: bool,
+
+    /// Whether `put` records a checksum of regular files
+    checksum: bool,
+
+    /// Where the key for encrypted entries comes from, it's only loaded once it's needed
+    key_source: Option<KeySource>,
+    key: OnceLock<std::result::Result<Key, String>>,
+
+    /// Where operations are recorded
+    journal: Option<Journal>,
+
+    /// Whose trash this is
+    uid: u32,
+
+    /// Home dir of the user if it isn't the current one
+    home: Option<PathBuf>,
 }
 
 impl UnifiedTrash {
-    pub fn new() -> anyhow::Result<Self> {
-        let home_trash = find_home_trash().context("Failed to get home trash dir")?;
+    /// The trash of the effective user, who also owns everything created in it.
+    /// Under sudo or a setuid binary this isn't the user who started the program, see [`invoking_uid`](super::invoking_uid).
+    pub fn new() -> Result<Self> {
+        let uid = rustix::process::geteuid().as_raw();
+        let (home_trash, trashes) = Self::discover(uid, None)?;
 
-        let real_uid = unsafe { libc::getuid() };
-        let mut trashes =
-            Trash::get_trash_dirs_from_mounts(real_uid).context("Failed to get trash dirs")?;
-        trashes.insert(0, home_trash.clone());
+        Ok(Self::from_parts(
+            Arc::new(LocalFs),
+            home_trash,
+            trashes,
+            uid,
+            None,
+        ))
+    }
 
-        // ensure that admin created trash dirs take priority.
-        // yes a and b need to be swapped for this to be the proper way round
-        trashes.sort_by(|a, b| b.is_admin_trash.cmp(&a.is_admin_trash));
+    /// The trash of another user (only for root), with the home trash in `home`.
+    /// Their `$XDG_DATA_HOME` can't be known, so the default location is assumed.
+    /// A setuid binary only gets the trash of the user who started it.
+    pub fn for_user(uid: u32, home: PathBuf) -> Result<Self> {
+        let euid = rustix::process::geteuid();
+        let ruid = rustix::process::getuid();
+        let own = uid == euid.as_raw() || uid == ruid.as_raw();
+        let allowed = own || (euid.is_root() && ruid.is_root());
+        if !allowed {
+            return Err(TrashError::Unsupported(
+                "Only root can use the trash of other users".to_owned(),
+            ));
+        }
 
-        Ok(Self {
+        let (home_trash, trashes) = Self::discover(uid, Some(&home))?;
+
+        Ok(Self::from_parts(
+            Arc::new(LocalFs),
+            home_trash,
+            trashes,
+            uid,
+            Some(home),
+        ))
+    }
+
+    /// Only the trash at `path` (created if needed) for everything, the mounts aren't looked at.
+    /// `put` copies files on other devices into it, like with [`Self::with_trash_dir`].
+    pub fn in_dir(path: &Path) -> Result<Self> {
+        let fs: Arc<dyn TrashFs> = Arc::new(LocalFs);
+        let trash = Arc::new(explicit_trash(&*fs, path)?);
+        let uid = rustix::process::geteuid().as_raw();
+
+        let mut this =
+            Self::from_parts(fs, Arc::clone(&trash), vec![Arc::clone(&trash)], uid, None);
+        this.put_target = Some(trash);
+        this.from_mounts = false;
+        Ok(this)
+    }
+
+    /// A trash with only a home trash at `trash_path` on `fs`, the mounts aren't looked at
+    #[cfg(test)]
+    pub fn on_fs(fs: Arc<dyn TrashFs>, trash_path: PathBuf) -> Result<Self> {
+        let dev_root = trash_path.parent().context("No data dir")?.to_path_buf();
+        fs.create_dir_all(&dev_root)
+            .context("Failed to create data dir")?;
+        let device = fs
+            .stat(&dev_root, true)
+            .context("Failed to stat data dir")?
+            .dev;
+        let home_trash = Arc::new(Trash::new_with_ensure(
+            &*fs, trash_path, dev_root, device, true, false,
+        )?);
+        let trashes = vec![Arc::clone(&home_trash)];
+
+        let mut this = Self::from_parts(fs, home_trash, trashes, 0, None);
+        this.from_mounts = false;
+        Ok(this)
+    }
+
+    fn from_parts(
+        fs: Arc<dyn TrashFs>,
+        home_trash: Arc<Trash>,
+        trashes: Vec<Arc<Trash>>,
+        uid: u32,
+        home: Option<PathBuf>,
+    ) -> Self {
+        Self {
+            backend: Box::new(XdgBackend::new(Arc::clone(&fs))),
+            fs,
             trashes,
             home_trash,
-        })
+            from_mounts: true,
+            quota: None,
+            usage_warning: None,
+            retention: None,
+            device_exclusions: None,
+            clock: Box::new(SystemClock),
+            put_target: None,
+            put_name: None,
+            compress: false,
+            encrypt: false,
+            checksum: false,
+            key_source: None,
+            key: OnceLock::new(),
+            journal: None,
+            uid,
+            home,
+        }
     }
 
-    pub fn list_trashes(&self) -> &[Trash] {
-        &self.trashes
+    /// The user whose trash this is
+    pub fn uid(&self) -> u32 {
+        self.uid
     }
 
-    /// Removes any orphaned trashinfo files, i.e `.trashinfo` files that don't have a
-    /// matching file actually *in* the trash
-    pub fn remove_orphaned(&self) -> anyhow::Result<()> {
-        for trash in &self.trashes {
-            for info in fs::read_dir(trash.info_dir()).context("Failed to read info dir")? {
-                let info = info.context("Failed to get dir entry")?;
-                let info = trashinfo::parse_trashinfo(&info.path(), trash)
-                    .context("Failed to parse dir entry")?;
+    /// Rescans the mounts, picking up trashes on drives that were plugged in and
+    /// dropping the ones on drives that are gone.
+    ///
+    /// Only useful for long running frontends, everything else sees the mounts at startup.
+    /// A trash from [`Self::in_dir`] doesn't look at the mounts, so it stays as it is.
+    pub fn refresh(&mut self) -> Result<()> {
+        if !self.from_mounts {
+            return Ok(());
+        }
+        let (home_trash, trashes) = Self::discover(self.uid, self.home.as_deref())?;
+        self.home_trash = home_trash;
+        self.trashes = trashes;
+        Ok(())
+    }
+
+    /// Finds the home trash and every trash on the currently mounted devices
+    fn discover(uid: u32, home: Option<&Path>) -> Result<(Arc<Trash>, Vec<Arc<Trash>>)> {
+        let home_trash = match home {
+            Some(home) => home_trash_of(home),
+            None => find_home_trash(),
+        };
+        let home_trash = Arc::new(home_trash.context("Failed to get home trash dir")?);
+
+        let mut trashes = Trash::get_trash_dirs_from_mounts(uid)
+            .context("Failed to get trash dirs")?
+            .into_iter()
+            .map(Arc::new)
+            .collect::<Vec<_>>();
+        trashes.insert(0, Arc::clone(&home_trash));
 
-                if !trash.files_dir().join(&info.trash_filename).exists() {
-                    let info_file = trash
-                        .info_dir()
-                        .join(&info.trash_filename_trashinfo)
-                        .with_extension("trashinfo");
+        // ensure that admin created trash dirs take priority.
+        trashes.sort_by_key(|x| std::cmp::Reverse(x.is_admin_trash));
 
-                    log::info!("Removing orphaned trashinfo file: {}", info_file.display());
+        Ok((home_trash, trashes))
+    }
 
-                    fs::remove_file(&info_file).context("Failed to remove info file")?;
-                    continue;
-                }
-            }
+    /// Only prints the changes to the trashes and the filesystem instead of making them.
+    /// Replaces the backend, so it has to come before [`Self::with_backend`]
+    pub fn with_simulation(mut self) -> Self {
+        self.fs = Arc::new(SimulatedFs);
+        self.backend = Box::new(XdgBackend::new(Arc::clone(&self.fs)));
+        self
+    }
+
+    /// Stores the entries with `backend` instead of in the `files` and `info` dirs of the spec
+    /// Compression and encryption write to the `files` dir directly, so they only work with
+    /// backends that use it.
+    pub fn with_backend(mut self, backend: impl TrashBackend + 'static) -> Self {
+        self.backend = Box::new(backend);
+        self
+    }
+
+    /// Uses the `clock` instead of the system time (for example for the `DeletionDate`)
+    pub fn with_clock(mut self, clock: impl Clock + 'static) -> Self {
+        self.clock = Box::new(clock);
+        self
+    }
+
+    /// The current time according to the clock of this trash
+    pub fn now(&self) -> chrono::NaiveDateTime {
+        self.clock.now()
+    }
+
+    /// Enforces the `quota` on every trash when putting files
+    pub fn with_quota(mut self, quota: Option<Quota>) -> Self {
+        self.quota = quota;
+        self
+    }
+
+    /// The policy [`Self::empty_by_retention`] applies
+    pub fn with_retention(mut self, retention: Option<Retention>) -> Self {
+        self.retention = retention;
+        self
+    }
+
+    /// Makes [`Self::warn_on_usage`] warn about trashes that exceed the `usage_warning`
+    pub fn with_usage_warning(mut self, usage_warning: Option<UsageWarning>) -> Self {
+        self.usage_warning = usage_warning;
+        self
+    }
+
+    /// Keeps `put` from creating trashes on the excluded devices
+    pub fn with_device_exclusions(mut self, exclusions: Option<DeviceExclusions>) -> Self {
+        self.device_exclusions = exclusions;
+        self
+    }
+
+    /// Makes `put` use the home trash for every file, files on other devices are copied into it
+    pub fn with_home_only(mut self) -> Self {
+        self.put_target = Some(Arc::clone(&self.home_trash));
+        self
+    }
+
+    /// Makes `put` use the trash at `path` for every file (creating it if needed),
+    /// files on other devices are copied into it
+    pub fn with_trash_dir(mut self, path: &Path) -> Result<Self> {
+        self.put_target = Some(Arc::new(explicit_trash(&*self.fs, path)?));
+        Ok(self)
+    }
+
+    /// Makes `put` name the entries `name` inside the trash instead of using the file name.
+    /// The name is still made unique, so it can be used for many files.
+    pub fn with_put_name(mut self, name: OsString) -> Result<Self> {
+        let valid =
+            !name.is_empty() && name != "." && name != ".." && !name.as_bytes().contains(&b'/');
+        if !valid {
+            return Err(TrashError::Unsupported(f!(
+                "{:?} can't be used as a name in the trash",
+                name
+            )));
         }
 
-        Ok(())
+        self.put_name = Some(name);
+        Ok(self)
+    }
+
+    /// Makes `put` store regular files zstd compressed, `restore` decompresses them again.
+    /// Other implementations can't restore these entries properly.
+    pub fn with_compression(mut self, compress: bool) -> Self {
+        self.compress = compress;
+        self
+    }
+
+    /// Makes `put` encrypt regular files with the key from the key source
+    /// (see [`UnifiedTrash::with_key_source`]), `restore` decrypts them again.
+    pub fn with_encryption(mut self, encrypt: bool) -> Self {
+        self.encrypt = encrypt;
+        self
+    }
+
+    /// Makes `put` record a SHA-256 of regular files, `restore` and `remove` warn when the
+    /// trashed file doesn't match it anymore. Hashing reads the whole file, so it's off by default.
+    pub fn with_checksums(mut self, checksum: bool) -> Self {
+        self.checksum = checksum;
+        self
+    }
+
+    /// Where the key for encrypting and decrypting entries is loaded from
+    pub fn with_key_source(mut self, key_source: KeySource) -> Self {
+        self.key_source = Some(key_source);
+        self.key = OnceLock::new();
+        self
+    }
+
+    /// Records every put, restore, remove and empty in the `journal`
+    pub fn with_journal(mut self, journal: Journal) -> Self {
+        self.journal = Some(journal);
+        self
+    }
+
+    pub fn journal(&self) -> Option<&Journal> {
+        self.journal.as_ref()
+    }
+
+    /// Loads the key on first use, a key that failed to load isn't tried again
+    fn key(&self) -> Result<&Key> {
+        self.key
+            .get_or_init(|| match &self.key_source {
+                Some(source) => source.load().map_err(|e| f!("{:#}", e)),
+                None => Err("No key source is configured".to_owned()),
+            })
+            .as_ref()
+            .map_err(|e| TrashError::NotFound(f!("No key for encrypted files: {}", e)))
+    }
+
+    /// The key needed to read the trashed file of `info`, if it is encrypted
+    fn key_for(&self, info: &Trashinfo) -> Result<Option<&Key>> {
+        match info.metadata.encrypted {
+            true => self.key().map(Some),
+            false => Ok(None),
+        }
+    }
+
+    /// Opens the trashed file of `info` for reading its original contents,
+    /// decrypting and decompressing them if needed
+    pub fn open_contents(&self, info: &Trashinfo) -> Result<Box<dyn Read>> {
+        Ok(contents::open_contents(info, self.key_for(info)?)?)
+    }
+
+    /// Compares the trashed file of `info` with the checksum recorded when it was trashed,
+    /// see [`checksum_matches`]
+    pub fn checksum_matches(&self, info: &Trashinfo) -> Option<bool> {
+        checksum_matches(info, self.key_for(info).ok()?)
+    }
+
+    pub fn list_trashes(&self) -> &[Arc<Trash>] {
+        &self.trashes
+    }
+
+    /// Removes any orphaned trashinfo files, i.e `.trashinfo` files that don't have a
+    /// matching file actually *in* the trash
+    pub fn remove_orphaned(&self) -> Result<()> {
+        self.backend.remove_orphaned(&self.trashes)
     }
 
     /// List all currently trashed files.
@@ -77,189 +459,468 @@ impl UnifiedTrash {
     /// Note that is is according to the `.trashinfo` files, i.e a file without the
     /// matching `.trashinfo` file is *not* listed, as not enough information
     /// can be gathered to fully construct a `Trashinfo` object.
-    pub fn list(&self) -> anyhow::Result<Vec<Trashinfo>> {
+    pub fn list(&self) -> Result<Vec<Trashinfo>> {
         let mut parsed = vec![];
-        for trash in &self.trashes {
-            for info in fs::read_dir(trash.info_dir()).context("Failed to read info dir")? {
-                let info = info.context("Failed to get dir entry")?;
-                log::trace!("Parsing {}", info.path().display());
-                let info = trashinfo::parse_trashinfo(&info.path(), trash)
-                    .context("Failed to parse dir entry")?;
-
-                let files_path = trash.files_dir().join(&info.trash_filename);
-
-                match fs::symlink_metadata(&files_path) {
-                    Ok(v) => v,
-                    Err(e) => match e.kind() {
-                        std::io::ErrorKind::NotFound => {
-                            warn!(
-                                "Orphaned trashinfo file: {}",
-                                trash
-                                    .info_dir()
-                                    .join(&info.trash_filename_trashinfo)
-                                    .display()
-                            );
-                            continue;
-                        }
-                        _ => anyhow::bail!("Failed to stat {}", files_path.display()),
-                    },
-                };
-
-                parsed.push(info);
-            }
-        }
-
+        self.for_each_entry(|info| {
+            parsed.push(info);
+            Ok(())
+        })?;
         Ok(parsed)
     }
 
+    /// Like `list`, but hands every entry to `f` as soon as it is parsed instead of collecting them.
+    /// Stops at the first error returned by `f`.
+    pub fn for_each_entry(&self, mut f: impl FnMut(Trashinfo) -> Result<()>) -> Result<()> {
+        self.backend.for_each_entry(&self.trashes, &mut f)
+    }
+
     /// Attempts to trash the `input_file`, creating a new trashcan on the device if needed.
-    pub fn put(&self, input_file: &Path, follow_links: bool) -> anyhow::Result<()> {
-        let deleted_at = chrono::Local::now().naive_local();
+    /// Returns the entry as it was written to the trash.
+    ///
+    /// `exceeds_quota` is only called if the quota policy is `Prompt` and trashing the file
+    /// would exceed the quota of the trash, the file is only trashed if it returns `true`.
+    pub fn put(
+        &self,
+        input_file: &Path,
+        follow_links: bool,
+        exceeds_quota: impl Fn(&Trash, QuotaUsage) -> bool,
+    ) -> Result<Trashinfo> {
+        let deleted_at = self.clock.now();
+
+        let local = self.fs.is_local();
 
-        let (original_filepath, input_file_meta) = if follow_links {
-            let p = input_file
+        let original_filepath = if follow_links && local {
+            input_file
                 .canonicalize()
-                .context("Failed to resolve path path")?;
+                .context("Failed to resolve path path")?
+        } else {
+            lexical_absolute(input_file).context("Failed to build lexical absolute path")?
+        };
+        let input_file_meta = self
+            .fs
+            .stat(input_file, follow_links)
+            .context(format!("Failed stat file: {}", input_file.display()))?;
+
+        if is_sys_path(input_file) {
+            return Err(TrashError::Unsupported(f!(
+                "Trashing in system path {} is not supported",
+                input_file.display()
+            )));
+        }
+
+        // a trash would end up inside itself, or its entries in the trash twice
+        if let Some(trash) = self.overlapping_trash(&original_filepath) {
+            return Err(TrashError::Unsupported(f!(
+                "Refusing to trash {}, it is, contains or is inside the trash {}",
+                original_filepath.display(),
+                trash.display()
+            )));
+        }
+        if Trash::is_trash_dir_name(original_filepath.file_name().unwrap_or_default()) {
+            return Err(TrashError::Unsupported(f!(
+                "Refusing to trash {}, it looks like a trash directory",
+                original_filepath.display()
+            )));
+        }
 
-            let m = fs::metadata(input_file)
-                .context(format!("Failed stat file: {}", input_file.display()))?;
+        // the name is only a starting point, write_trashinfo makes it unique within the trash
+        let new_file_name = match &self.put_name {
+            Some(v) => v.clone(),
+            None => input_file
+                .file_name()
+                .context("File has no filename")?
+                .to_os_string(),
+        };
 
-            (p, m)
-        } else {
-            let p =
-                lexical_absolute(input_file).context("Failed to build lexical absolute path")?;
+        let mut trash_filename_trashinfo = new_file_name.clone();
+        trash_filename_trashinfo.push(OsString::from(".trashinfo"));
+
+        // nothing can be moved off a read-only filesystem, so fail early with a clear
+        // error instead of a failed mkdir or rename further down
+        let parent = original_filepath.parent().unwrap_or(&original_filepath);
+        if local && is_read_only(parent).unwrap_or(false) {
+            return Err(TrashError::Unsupported(f!(
+                "{} is on a read-only filesystem and can't be trashed",
+                input_file.display()
+            )));
+        }
+
+        let trash = match &self.put_target {
+            Some(v) => Arc::clone(v),
+            None => self
+                .trash_on_device(input_file, input_file_meta.dev)
+                .context(f!(
+                    "No trash for {}, use --home-only to copy it into the home trash instead",
+                    input_file.display()
+                ))?,
+        };
 
-            let m = fs::symlink_metadata(input_file)
-                .context(format!("Failed stat file: {}", input_file.display()))?;
+        self.enforce_quota(&trash, &original_filepath, exceeds_quota)
+            .context("Quota exceeded")?;
 
-            (p, m)
+        let mut trashinfo = Trashinfo {
+            trash: Arc::clone(&trash),
+            trash_filename: new_file_name,
+            trash_filename_trashinfo,
+            deleted_at,
+            original_filepath,
+            metadata: OriginalMetadata::capture(&input_file_meta),
         };
+        #[cfg(feature = "selinux")]
+        if local {
+            trashinfo
+                .metadata
+                .capture_security_context(&trashinfo.original_filepath);
+        }
 
-        if is_sys_path(input_file) {
-            anyhow::bail!(
-                "Trashing in system path {} is not supported",
+        // the paths in it are relative to the mount point, which can change
+        if local && !trash.is_home_trash {
+            trashinfo.metadata.fs_uuid = fs_uuid(trash.device);
+        }
+
+        // hashed before it's moved, so it describes the file as it was when it was trashed
+        if self.checksum && local && input_file_meta.is_file() {
+            match sha256_file(input_file) {
+                Ok(v) => trashinfo.metadata.sha256 = Some(v),
+                Err(e) => log::debug!("Failed to hash {}: {}", input_file.display(), e),
+            }
+        }
+
+        // renaming keeps the xattrs, but they are stored anyways in case the file gets copied later on
+        let xattrs = match local {
+            true => Xattrs::capture(&trashinfo.original_filepath).unwrap_or_else(|e| {
+                log::debug!("Failed to read xattrs: {}", e);
+                Xattrs::default()
+            }),
+            false => Xattrs::default(),
+        };
+
+        let store = (self.compress || self.encrypt) && input_file_meta.is_file();
+        if store && !local {
+            log::info!(
+                "Not compressing or encrypting {}, that needs the local filesystem",
                 input_file.display()
             );
         }
 
-        let mut new_file_name = input_file
-            .file_name()
-            .context("File has no filename")?
-            .to_os_string();
+        if store && local {
+            trashinfo.metadata.compressed = self.compress;
+            trashinfo.metadata.encrypted = self.encrypt;
+            let key = match self.encrypt {
+                true => Some(self.key()?),
+                false => None,
+            };
+            put_stored(&*self.backend, &mut trashinfo, input_file, key)?;
+        } else {
+            self.backend
+                .put(&mut trashinfo)
+                .context(format!("Failed to write to {}", trash.trash_path.display()))?;
+        }
 
-        // by listing all trashes, we ensure that the filename is unique system wide,
-        // as far as i can tell, this is what nautilus does as well and genereally seems like a good idea
-        let trashed_files = self.list().context("Failed to list trash")?;
+        if let Err(e) = xattrs.save(&trashinfo) {
+            warn!("Failed to store xattrs: {:#}", e);
+        }
+        record_or_warn(self.journal.as_ref(), Action::Put, &trashinfo, self.now());
 
-        {
-            let orig_filename = new_file_name.clone();
+        Ok(trashinfo)
+    }
 
-            for iterations in 1.. {
-                if trashed_files
-                    .iter()
-                    .any(|x| x.trash_filename == new_file_name)
-                {
-                    // If we get here, a file with the current name already exists in one of the trashes,
-                    // so we append the current iteration number to it and check again
-                    // we try to preserve the extension in case a user wants to manually recover a file
-                    // (so it still has the proper extension)
-
-                    // somefile.txt
-                    let old_name = PathBuf::from(&orig_filename);
-
-                    // somefile
-                    let mut stem = old_name
-                        .file_stem()
-                        .unwrap_or(&orig_filename)
-                        .to_os_string();
-
-                    // txt
-                    let ext = old_name.extension();
-
-                    // somefile1
-                    stem.push(OsStr::new(&iterations.to_string()));
-
-                    if let Some(ext) = ext {
-                        // somefile1.txt
-                        stem.push(OsStr::new("."));
-                        stem.push(ext);
-                    }
+    /// The known trash that `path` is, contains or is inside of
+    fn overlapping_trash(&self, path: &Path) -> Option<&Path> {
+        let overlaps = |a: &Path, b: &Path| a.starts_with(b) || b.starts_with(a);
+        // symlinks can hide an overlap from the lexical paths
+        let resolved = match self.fs.is_local() {
+            true => path.canonicalize().ok(),
+            false => None,
+        };
 
-                    new_file_name = stem;
+        self.trashes
+            .iter()
+            .chain([&self.home_trash])
+            .chain(&self.put_target)
+            .map(|x| x.trash_path.as_path())
+            .find(|trash| {
+                overlaps(path, trash)
+                    || resolved.as_ref().is_some_and(|resolved| {
+                        trash
+                            .canonicalize()
+                            .is_ok_and(|trash| overlaps(resolved, &trash))
+                    })
+            })
+    }
 
-                    continue;
-                } else {
-                    // we have a unique filename
-                    break;
+    /// Trashes either all of the `files` or none of them. If one of them fails, the ones
+    /// that were already trashed are moved back and their trashinfo files removed.
+    /// Returns the entries as they were written to the trash.
+    ///
+    /// Entries evicted to stay within a quota are gone for good, even if the batch is rolled back.
+    pub fn put_many(
+        &self,
+        files: &[PathBuf],
+        follow_links: bool,
+        exceeds_quota: impl Fn(&Trash, QuotaUsage) -> bool,
+    ) -> Result<Vec<Trashinfo>> {
+        let mut trashed = Vec::with_capacity(files.len());
+        for file in files {
+            match self.put(file, follow_links, &exceeds_quota) {
+                Ok(info) => trashed.push(info),
+                Err(e) => {
+                    for info in trashed.iter().rev() {
+                        if let Err(e) = self.unput(info) {
+                            error!(
+                                "Failed to move {} back: {:#}",
+                                info.original_filepath.display(),
+                                e
+                            );
+                        }
+                    }
+                    return Err(e).context(f!("Failed to trash {}", file.display()));
                 }
             }
         }
 
-        // At this point we have a unique name, so we create the corresponding trashinfo name
-        let mut trash_filename_trashinfo = new_file_name.clone();
-        trash_filename_trashinfo.push(OsString::from(".trashinfo"));
+        Ok(trashed)
+    }
+
+    /// The trash directory `put` would newly create for `file`, if its device has none yet
+    pub fn new_trash_for(&self, file: &Path, follow_links: bool) -> Option<PathBuf> {
+        if self.put_target.is_some() {
+            return None;
+        }
+
+        let dev = self.fs.stat(file, follow_links).ok()?.dev;
+        if dev == self.home_trash.device || self.trashes.iter().any(|x| x.device == dev) {
+            return None;
+        }
+
+        let device_root = find_fs_root(file).ok()?;
+        let excluded = self
+            .device_exclusions
+            .as_ref()
+            .is_some_and(|x| x.excludes(&device_root, || fs_uuid(dev)));
+        let trash_path = device_root.join(f!(".Trash-{}", self.uid));
+        // it may have been created since the trashes were discovered
+        (!excluded && self.fs.stat(&trash_path, false).is_err()).then_some(trash_path)
+    }
 
-        if input_file_meta.dev() == self.home_trash.device {
+    /// The trash for files on the device `dev`, `path` is any path on that device.
+    /// Creates a new trash in the root of the device if there is none yet,
+    /// unless the device is excluded from that.
+    fn trash_on_device(&self, path: &Path, dev: u64) -> Result<Arc<Trash>> {
+        if dev == self.home_trash.device {
             // input is on the same device as the home trash, so we use that.
-            let trashinfo = Trashinfo {
-                trash: &self.home_trash,
-                trash_filename: new_file_name,
-                trash_filename_trashinfo,
-                deleted_at,
-                original_filepath,
-            };
+            return Ok(Arc::clone(&self.home_trash));
+        }
 
-            self.home_trash
-                .write_trashinfo(&trashinfo)
-                .context("Failed to write to home trash")?;
-        } else {
-            let existing_trash = self
-                .trashes
-                .iter()
-                .find(|x| x.device == input_file_meta.dev());
-
-            if let Some(existing_trash) = existing_trash {
-                // We already have a trash on the device, so we use it
-                let trashinfo = Trashinfo {
-                    trash: existing_trash,
-                    trash_filename: new_file_name,
-                    trash_filename_trashinfo,
-                    deleted_at,
-                    original_filepath,
+        if let Some(existing_trash) = self.trashes.iter().find(|x| x.device == dev) {
+            // We already have a trash on the device, so we use it
+            return Ok(Arc::clone(existing_trash));
+        }
+
+        let device_root = find_fs_root(path).context("Failed to find mount point")?;
+
+        if let Some(exclusions) = &self.device_exclusions {
+            if exclusions.excludes(&device_root, || fs_uuid(dev)) {
+                return match exclusions.fallback {
+                    ExclusionFallback::Home => {
+                        log::info!(
+                            "Not creating a trash on the excluded device at {}, using the home trash",
+                            device_root.display()
+                        );
+                        Ok(Arc::clone(&self.home_trash))
+                    }
+                    ExclusionFallback::Skip => Err(TrashError::Unsupported(f!(
+                        "The device at {} is excluded from getting a trash",
+                        device_root.display()
+                    ))),
                 };
+            }
+        }
+
+        let fs_root_meta = self
+            .fs
+            .stat(&device_root, true)
+            .context("Failed to stat mount")?;
+        let trash_name = format!(".Trash-{}", self.uid);
+        let new_trash = Trash::new_with_ensure(
+            &*self.fs,
+            device_root.join(trash_name),
+            device_root.clone(),
+            fs_root_meta.dev,
+            false,
+            false,
+        )
+        .context(format!(
+            "Failed to create trash dir on mount: {}",
+            &device_root.display()
+        ))?;
+
+        Ok(Arc::new(new_trash))
+    }
+
+    /// The trash a file that was located at `original_filepath` would have been trashed to.
+    /// Falls back to the home trash if that location doesn't exist (anymore) or has no usable trash.
+    pub fn trash_for(&self, original_filepath: &Path) -> Arc<Trash> {
+        let existing = original_filepath
+            .ancestors()
+            .skip(1)
+            .find_map(|x| self.fs.stat(x, false).ok().map(|meta| (x, meta.dev)));
+
+        match existing.map(|(path, dev)| self.trash_on_device(path, dev)) {
+            Some(Ok(v)) => v,
+            Some(Err(e)) => {
+                warn!(
+                    "No trash for {} ({:#}), using the home trash",
+                    original_filepath.display(),
+                    e
+                );
+                Arc::clone(&self.home_trash)
+            }
+            None => Arc::clone(&self.home_trash),
+        }
+    }
+
+    /// Warns about each of the `trashes` that is bigger than the configured usage warning allows.
+    /// Every trash is walked to get its size, so this is meant to be called once after putting
+    /// all files rather than after every one of them.
+    pub fn warn_on_usage(&self, trashes: &[Arc<Trash>]) {
+        let Some(usage_warning) = &self.usage_warning else {
+            return;
+        };
 
-                existing_trash
-                    .write_trashinfo(&trashinfo)
-                    .context("Failed to write to trash")?;
-            } else {
-                let device_root = find_fs_root(input_file).context("Failed to find mount point")?;
-
-                let fs_root_meta = fs::metadata(&device_root).context("Failed to stat mount")?;
-                let uid = unsafe { libc::getuid() };
-                let trash_name = format!(".Trash-{}", uid);
-                let trash = Trash::new_with_ensure(
-                    device_root.join(trash_name),
-                    device_root.clone(),
-                    fs_root_meta.dev(),
-                    false,
-                    false,
-                )
-                .context(format!(
-                    "Failed to create trash dir on mount: {}",
-                    &device_root.display()
-                ))?;
-
-                let trashinfo = Trashinfo {
-                    trash: &trash,
-                    trash_filename: new_file_name,
-                    trash_filename_trashinfo,
-                    deleted_at,
-                    original_filepath,
+        for trash in trashes {
+            let used = match self.backend.trash_size(trash) {
+                Ok(v) => v,
+                Err(e) => {
+                    log::debug!(
+                        "Failed to get size of {}: {:#}",
+                        trash.trash_path.display(),
+                        e
+                    );
+                    continue;
+                }
+            };
+            let capacity = match self.fs.is_local() {
+                true => fs_capacity(&trash.trash_path).unwrap_or(0),
+                false => 0,
+            };
+
+            if usage_warning.exceeded(used, capacity) {
+                let share = match capacity {
+                    0 => String::new(),
+                    _ => f!(
+                        " ({:.1}% of its filesystem)",
+                        used as f64 * 100.0 / capacity as f64
+                    ),
                 };
+                warn!(
+                    "{} takes up {}{}, consider running `trash empty`",
+                    trash.trash_path.display(),
+                    format_size(used),
+                    share
+                );
+            }
+        }
+    }
+
+    /// Makes sure that the `incoming_file` fits into the `trash` without exceeding its quota,
+    /// evicting the oldest entries or asking the user, depending on the policy.
+    fn enforce_quota(
+        &self,
+        trash: &Trash,
+        incoming_file: &Path,
+        exceeds_quota: impl Fn(&Trash, QuotaUsage) -> bool,
+    ) -> Result<()> {
+        let Some(quota) = &self.quota else {
+            return Ok(());
+        };
+
+        let usage = QuotaUsage {
+            used: self
+                .backend
+                .trash_size(trash)
+                .context("Failed to get size of trash")?,
+            incoming: self
+                .fs
+                .size(incoming_file)
+                .context("Failed to get size of file")?,
+            limit: quota.limit_for(trash),
+        };
+
+        if usage.used + usage.incoming <= usage.limit {
+            return Ok(());
+        }
+
+        match quota.policy {
+            QuotaPolicy::Refuse => {
+                return Err(TrashError::QuotaExceeded(f!(
+                    "Trashing {} would exceed the quota of {}",
+                    incoming_file.display(),
+                    trash.trash_path.display()
+                )))
+            }
+            QuotaPolicy::Prompt => {
+                if !exceeds_quota(trash, usage) {
+                    return Err(TrashError::Aborted);
+                }
+            }
+            QuotaPolicy::Evict => {
+                if usage.incoming > usage.limit {
+                    return Err(TrashError::QuotaExceeded(f!(
+                        "{} is bigger than the quota of {}",
+                        incoming_file.display(),
+                        trash.trash_path.display()
+                    )));
+                }
+
+                let mut entries = self
+                    .list()
+                    .context("Failed to list trash")?
+                    .into_iter()
+                    .filter(|x| *x.trash == *trash)
+                    .map(|x| {
+                        let size = self.backend.entry_size(&x);
+                        (x, size.unwrap_or(0))
+                    })
+                    .collect::<Vec<_>>();
+                entries.sort_by_key(|(x, _)| x.deleted_at);
+
+                // Growth guard: space used by files without a trashinfo (put there by other
+                // programs or interrupted operations) can never be freed by evicting entries.
+                // If that alone leaves no room, evicting would just destroy every entry
+                // on each put while the trash keeps growing, so we refuse instead.
+                let tracked = entries.iter().map(|(_, size)| size).sum::<u64>();
+                let untracked = usage.used.saturating_sub(tracked);
+                if untracked + usage.incoming > usage.limit {
+                    warn!(
+                        "{} of {} are not tracked by any trashinfo file and can't be evicted. \
+                        Run `trash doctor` to find them, or raise quota.max_size (currently {})",
+                        format_size(untracked),
+                        trash.trash_path.display(),
+                        format_size(usage.limit)
+                    );
+                    return Err(TrashError::QuotaExceeded(f!(
+                        "Evicting entries from {} can not free enough space",
+                        trash.trash_path.display()
+                    )));
+                }
+
+                let mut used = usage.used;
+                for (entry, size) in entries {
+                    if used + usage.incoming <= usage.limit {
+                        break;
+                    }
 
-                trash
-                    .write_trashinfo(&trashinfo)
-                    .context("Failed writing to trash")?;
+                    log::info!(
+                        "Quota of {} exceeded, permanently removing {}",
+                        trash.trash_path.display(),
+                        entry.original_filepath.display()
+                    );
+                    self.backend
+                        .remove(&entry, false)
+                        .context("Failed to evict trash entry")?;
+                    used = used.saturating_sub(size);
+                }
             }
         }
 
@@ -267,55 +928,255 @@ impl UnifiedTrash {
     }
 
     /// Empty the trash based on the `.trashinfo` files, meaning that files for which no
-    /// `.trashinfo` file exists will be ignored
-    pub fn empty(&self, before: chrono::NaiveDateTime, dry_run: bool) -> anyhow::Result<()> {
+    /// `.trashinfo` file exists will be ignored. Only entries trashed `before` that match
+    /// the `filter` are deleted. With `shred`, the contents of files are overwritten
+    /// before they are deleted (see [`shred`]).
+    ///
+    /// The entries of each trash are deleted by several threads at once. A failed entry
+    /// doesn't stop the others, all failures are reported together at the end.
+    pub fn empty(
+        &self,
+        before: chrono::NaiveDateTime,
+        filter: impl Fn(&Trashinfo) -> bool,
+        opts: &EmptyOptions,
+    ) -> Result<EmptySummary> {
+        let mut summary = EmptySummary::default();
+        let mut by_trash: Vec<Vec<Trashinfo>> = vec![];
         for info in self.list().context("Failed to list trash files")? {
-            if info.deleted_at < before {
-                let files_file = info.trash.files_dir().join(info.trash_filename);
-                let info_file = info.trash.info_dir().join(info.trash_filename_trashinfo);
+            let under = opts
+                .under
+                .as_ref()
+                .is_none_or(|x| info.original_filepath.starts_with(x));
+            if info.deleted_at >= before || !under || !filter(&info) {
+                continue;
+            }
 
-                if dry_run {
+            if opts.dry_run {
+                if !opts.quiet {
                     println!("Would delete {}", info.original_filepath.display());
-                    continue;
                 }
+                // orphaned trashinfo files simply don't free anything
+                summary.removed += 1;
+                summary.freed += self.backend.entry_size(&info).unwrap_or(0);
+                continue;
+            }
 
-                println!("Removing {}", files_file.display());
-                let remove_result = if files_file.is_file() {
-                    fs::remove_file(&files_file)
-                } else {
-                    fs::remove_dir_all(&files_file)
-                };
+            match by_trash.iter_mut().find(|x| x[0].trash == info.trash) {
+                Some(entries) => entries.push(info),
+                None => by_trash.push(vec![info]),
+            }
+        }
 
-                if let Err(e) = remove_result {
-                    match e.kind() {
-                        std::io::ErrorKind::NotFound => {
-                            log::info!("Removing orphaned trashinfo file {}", info_file.display());
-                            // This falls through to the remove_file call below
-                        }
-                        _ => {
-                            anyhow::bail!(f!(
-                                "Failed to remove file {}: {}",
-                                files_file.display(),
-                                e
-                            ));
-                        }
-                    }
+        let mut errors = vec![];
+        for entries in by_trash {
+            let (removed, freed, failed) =
+                remove_parallel(&*self.backend, &entries, opts, |info| {
+                    record_or_warn(self.journal.as_ref(), Action::Empty, info, self.now())
+                });
+            summary.removed += removed;
+            summary.freed += freed;
+            errors.extend(failed);
+        }
+
+        // they are invisible to `list`, so this is the only place they would ever go away
+        if !opts.dry_run {
+            match self
+                .backend
+                .remove_stray_dirs(&self.trashes)
+                .context("Failed to remove stray directories")
+            {
+                Ok(n) => summary.stray_dirs = n,
+                Err(e) => errors.push(e),
+            }
+        }
+
+        match errors.len() {
+            0 => Ok(summary),
+            1 => Err(errors.remove(0)),
+            n => {
+                for e in &errors {
+                    error!("{:#}", e);
+                }
+                Err(TrashError::Other(f!("Failed to remove {} entries", n)))
+            }
+        }
+    }
+
+    /// Empties the entries that are older than the retention policy allows, then the oldest
+    /// entries of every trash that is still bigger than it allows. `opts` apply as for `empty`.
+    ///
+    /// Trashes that stay bigger than `retention.max_size` or the usage warning allow are
+    /// reported in [`EmptySummary::stuck`]. With `emergency_purge`, that many of their
+    /// largest remaining entries are removed as well.
+    pub fn empty_by_retention(
+        &self,
+        opts: &EmptyOptions,
+        emergency_purge: Option<usize>,
+    ) -> Result<EmptySummary> {
+        let Some(retention) = &self.retention else {
+            return Err(TrashError::NotFound(
+                "No retention policy configured".to_owned(),
+            ));
+        };
+
+        let now = self.now();
+        let files_path = |x: &Trashinfo| x.trash.files_dir().join(&x.trash_filename);
+        let needs_sizes = retention.max_size.is_some() || self.usage_warning.is_some();
+        let mut expired = FxHashSet::default();
+        let mut by_trash: Vec<Vec<(Trashinfo, u64)>> = vec![];
+        for info in self.list().context("Failed to list trash files")? {
+            if retention.max_age.is_some_and(|x| now - info.deleted_at > x) {
+                expired.insert(files_path(&info));
+            }
+            if needs_sizes {
+                // orphaned trashinfo files simply don't take up any space
+                let size = self.backend.entry_size(&info).unwrap_or(0);
+                match by_trash.iter_mut().find(|x| x[0].0.trash == info.trash) {
+                    Some(entries) => entries.push((info, size)),
+                    None => by_trash.push(vec![(info, size)]),
                 }
+            }
+        }
 
-                fs::remove_file(&info_file)
-                    .context(f!("Failed to remove info file {}", info_file.display()))?;
+        // keeps the newest entries that fit
+        for entries in &mut by_trash {
+            entries.sort_by_key(|(x, _)| std::cmp::Reverse(x.deleted_at));
+            let mut kept = 0u64;
+            for (info, size) in entries.iter() {
+                if expired.contains(&files_path(info)) {
+                    continue;
+                }
+                kept += size;
+                if retention.max_size.is_some_and(|x| kept > x) {
+                    expired.insert(files_path(info));
+                }
             }
         }
 
-        Ok(())
+        let mut summary = self.empty(
+            chrono::NaiveDateTime::MAX,
+            |x| expired.contains(&files_path(x)),
+            opts,
+        )?;
+
+        for trash in &self.trashes {
+            let entries = by_trash
+                .iter()
+                .find(|x| x[0].0.trash == *trash)
+                .map(|x| x.as_slice())
+                .unwrap_or_default();
+            let (removed, remaining): (Vec<_>, Vec<_>) = entries
+                .iter()
+                .partition(|(x, _)| expired.contains(&files_path(x)));
+            let freed = removed.iter().map(|(_, size)| size).sum();
+            let Some(mut stuck) = self.stuck_trash(trash, &remaining, freed, opts.dry_run) else {
+                continue;
+            };
+
+            if let Some(n) = emergency_purge {
+                let mut largest = remaining;
+                largest.sort_by_key(|(_, size)| std::cmp::Reverse(*size));
+                let purge = largest
+                    .iter()
+                    .take(n)
+                    .map(|(x, _)| files_path(x))
+                    .collect::<FxHashSet<_>>();
+                warn!(
+                    "Emergency purge: removing the {} largest entries of {}",
+                    purge.len(),
+                    trash.trash_path.display()
+                );
+                let purged = self.empty(
+                    chrono::NaiveDateTime::MAX,
+                    |x| purge.contains(&files_path(x)),
+                    opts,
+                )?;
+                summary.removed += purged.removed;
+                summary.freed += purged.freed;
+                summary.stray_dirs += purged.stray_dirs;
+                stuck.purged = purged.removed;
+                stuck.used = stuck.used.saturating_sub(purged.freed);
+            }
+            summary.stuck.push(stuck);
+        }
+
+        Ok(summary)
+    }
+
+    /// Checks whether `trash` is still too big after the retention policy freed `freed` bytes
+    /// and left only the `remaining` entries (sorted newest first) in it
+    fn stuck_trash(
+        &self,
+        trash: &Trash,
+        remaining: &[&(Trashinfo, u64)],
+        freed: u64,
+        dry_run: bool,
+    ) -> Option<StuckTrash> {
+        let capacity = match self.fs.is_local() {
+            true => fs_capacity(&trash.trash_path).unwrap_or(0),
+            false => 0,
+        };
+        let limit = [
+            self.retention.as_ref().and_then(|x| x.max_size),
+            self.usage_warning.as_ref().and_then(|x| x.limit(capacity)),
+        ]
+        .into_iter()
+        .flatten()
+        .min()?;
+
+        let used = match self.backend.trash_size(trash) {
+            Ok(v) => v,
+            Err(e) => {
+                log::debug!(
+                    "Failed to get size of {}: {:#}",
+                    trash.trash_path.display(),
+                    e
+                );
+                return None;
+            }
+        };
+        // a dry run didn't remove anything yet
+        let used = match dry_run {
+            true => used.saturating_sub(freed),
+            false => used,
+        };
+        if used <= limit {
+            return None;
+        }
+
+        let untracked = used.saturating_sub(remaining.iter().map(|(_, size)| size).sum());
+
+        // the age of the newest entry that no longer fits
+        let mut kept = untracked;
+        let suggested_max_age = remaining
+            .iter()
+            .find(|(_, size)| {
+                kept += size;
+                kept > limit
+            })
+            .map(|(info, _)| (self.now() - info.deleted_at).num_days())
+            .filter(|x| *x > 0 && untracked <= limit);
+
+        Some(StuckTrash {
+            trash_path: trash.trash_path.clone(),
+            used,
+            limit,
+            untracked,
+            suggested_max_age,
+            purged: 0,
+        })
     }
 
-    /// Permanently removes a file from the trash, returning the original path of the removed file
+    /// Permanently removes a file from the trash, returning the entry that was removed.
+    /// With `shred`, the contents are overwritten before deleting it.
+    /// With `dry_run`, the entry is only looked up and nothing is deleted.
     pub fn remove(
         &self,
-        filter_predicate: impl for<'a> Fn(&Trashinfo<'a>) -> bool,
-        matched_callback: impl for<'a> Fn(&'a [Trashinfo<'a>]) -> &'a Trashinfo,
-    ) -> anyhow::Result<PathBuf> {
+        filter_predicate: impl Fn(&Trashinfo) -> bool,
+        matched_callback: impl Fn(&[Trashinfo]) -> &Trashinfo,
+        shred: bool,
+        dry_run: bool,
+    ) -> Result<Trashinfo> {
         let trashed_files = self.list().context("Failed to list trashed files")?;
         let matching = trashed_files
             .into_iter()
@@ -323,73 +1184,438 @@ impl UnifiedTrash {
             .collect::<Vec<_>>();
 
         let del = match matching.len() {
-            0 => anyhow::bail!("No files match"),
+            0 => return Err(TrashError::NotFound("No files match".to_owned())),
             1 => &matching[0],
             // we only call the matched callback if more than one file matched
             _ => matched_callback(&matching),
         };
 
-        let info_path = del.trash.info_dir().join(&del.trash_filename_trashinfo);
-        let files_path = del.trash.files_dir().join(&del.trash_filename);
+        self.remove_entry(del, shred, dry_run)?;
+        Ok(del.clone())
+    }
 
-        if files_path.is_file() {
-            fs::remove_file(&files_path).context("Failed to remove file")?;
-        } else {
-            fs::remove_dir_all(&files_path).context("Failed to remove directory")?;
+    /// Like `remove`, for an entry that was already looked up
+    pub fn remove_entry(&self, del: &Trashinfo, shred: bool, dry_run: bool) -> Result<()> {
+        self.warn_on_checksum_mismatch(del);
+        if !dry_run {
+            self.backend.remove(del, shred)?;
+            record_or_warn(self.journal.as_ref(), Action::Remove, del, self.now());
         }
-
-        fs::remove_file(info_path).context("Failed to remove trashinfo file")?;
-
-        Ok(del.original_filepath.clone())
+        Ok(())
     }
 
     /// Restores a file to it's original location, returning the original path of the restored file
     pub fn restore(
         &self,
-        filter_predicate: impl for<'a> Fn(&Trashinfo<'a>) -> bool,
-        matched_callback: impl for<'a> Fn(&'a [Trashinfo<'a>]) -> &'a Trashinfo,
-        exists_callback: impl for<'a> Fn(&Trashinfo<'a>) -> bool,
-    ) -> anyhow::Result<PathBuf> {
+        filter_predicate: impl Fn(&Trashinfo) -> bool,
+        matched_callback: impl Fn(&[Trashinfo]) -> &Trashinfo,
+        exists_callback: impl Fn(&Trashinfo) -> bool,
+    ) -> Result<PathBuf> {
         let trashed_files = self.list().context("Failed to list trashed files")?;
         let matching = trashed_files
             .into_iter()
             .filter(filter_predicate)
             .collect::<Vec<_>>();
 
-        let restore = match matching.len() {
-            0 => anyhow::bail!("No files match"),
-            1 => {
-                let del = &matching[0];
-                if del.original_filepath.exists() && !exists_callback(del) {
-                    anyhow::bail!("Aborted by user");
-                }
-                &matching[0]
-            }
+        let selected = match matching.len() {
+            0 => return Err(TrashError::NotFound("No files match".to_owned())),
+            1 => &matching[0],
             // we only call the matched callback if more than one file matched
-            _ => {
-                let del = matched_callback(&matching);
-                if del.original_filepath.exists() && !exists_callback(del) {
-                    anyhow::bail!("Aborted by user");
-                }
-                del
-            }
+            _ => matched_callback(&matching),
         };
 
+        self.restore_entry(selected, exists_callback)
+    }
+
+    /// Like `restore`, for an entry that was already looked up
+    pub fn restore_entry(
+        &self,
+        selected: &Trashinfo,
+        exists_callback: impl Fn(&Trashinfo) -> bool,
+    ) -> Result<PathBuf> {
+        let relocated = self.relocated(selected);
+        let restore = relocated.as_ref().unwrap_or(selected);
+        if self.exists(&restore.original_filepath) && !exists_callback(restore) {
+            return Err(TrashError::Aborted);
+        }
+
         let files_path = restore.trash.files_dir().join(&restore.trash_filename);
-        let info_path = restore
-            .trash
-            .info_dir()
-            .join(&restore.trash_filename_trashinfo);
 
-        fs::rename(&files_path, &restore.original_filepath)
+        self.warn_on_checksum_mismatch(restore);
+        self.move_back(restore)
             .context(f!("Failed to restore {}", files_path.display()))?;
 
+        if self.fs.is_local() {
+            if let Err(e) = restore.metadata.apply(&restore.original_filepath, self.uid) {
+                warn!(
+                    "Failed to restore the metadata of {}: {}",
+                    restore.original_filepath.display(),
+                    e
+                );
+            }
+
+            match Xattrs::load(restore) {
+                Ok(Some(xattrs)) => {
+                    if let Err(e) = xattrs.apply(&restore.original_filepath, self.uid) {
+                        warn!(
+                            "Failed to restore the xattrs of {}: {}",
+                            restore.original_filepath.display(),
+                            e
+                        );
+                    }
+                }
+                Ok(None) => {}
+                Err(e) => warn!("{:#}", e),
+            }
+        }
+
         // We don't move the file back if this fails, as that might cause some unexpected troubles.
-        fs::remove_file(&info_path).context(f!(
-            "Failed to remove trashinfo file: {}",
-            info_path.display()
-        ))?;
+        self.backend
+            .forget(restore)
+            .context("Failed to remove the entry")?;
+        record_or_warn(self.journal.as_ref(), Action::Restore, restore, self.now());
 
         Ok(restore.original_filepath.clone())
     }
+
+    /// `info` with its original path on the mount point its filesystem has now,
+    /// if that is known and not where the trash was found
+    fn relocated(&self, info: &Trashinfo) -> Option<Trashinfo> {
+        if !self.fs.is_local() || info.trash.is_home_trash {
+            return None;
+        }
+
+        let mount_point = mount_point_of_uuid(info.metadata.fs_uuid.as_ref()?)?;
+        if mount_point == info.trash.dev_root {
+            return None;
+        }
+        let relative = info
+            .original_filepath
+            .strip_prefix(&info.trash.dev_root)
+            .ok()?;
+
+        let mut relocated = info.clone();
+        relocated.original_filepath = mount_point.join(relative);
+        log::info!(
+            "The filesystem of {} is mounted at {} now, restoring to {}",
+            info.original_filepath.display(),
+            mount_point.display(),
+            relocated.original_filepath.display()
+        );
+        Some(relocated)
+    }
+
+    /// Undoes a `put` of `info` that just happened
+    fn unput(&self, info: &Trashinfo) -> Result<()> {
+        // something else took the place of the file in the meantime, leave both alone
+        if self.exists(&info.original_filepath) {
+            return Err(TrashError::Conflict(f!(
+                "{} exists again",
+                info.original_filepath.display()
+            )));
+        }
+
+        let files_path = info.trash.files_dir().join(&info.trash_filename);
+        self.move_back(info)
+            .context(f!("Failed to move back {}", files_path.display()))?;
+        if info.metadata.compressed || info.metadata.encrypted {
+            // the decompressed file is a new one
+            if let Err(e) = info.metadata.apply(&info.original_filepath, self.uid) {
+                warn!("Failed to restore the metadata: {}", e);
+            }
+        }
+
+        self.backend
+            .forget(info)
+            .context("Failed to remove the entry")?;
+        record_or_warn(self.journal.as_ref(), Action::Restore, info, self.now());
+
+        Ok(())
+    }
+
+    /// Moves (or decrypts and decompresses) the trashed file of `info` back to its original path.
+    /// The trashinfo file is left alone.
+    fn move_back(&self, info: &Trashinfo) -> Result<()> {
+        let files_path = info.trash.files_dir().join(&info.trash_filename);
+        if !info.metadata.compressed && !info.metadata.encrypted {
+            return self.backend.move_back(info);
+        }
+
+        let key = self.key_for(info)?;
+        self.fs
+            .write_from(&info.original_filepath, &mut || {
+                contents::open_contents(info, key)
+            })
+            .context("Failed to read the stored contents")?;
+        Ok(self.fs.remove_file(&files_path)?)
+    }
+
+    /// Whether there is anything at `path`, without following symlinks
+    fn exists(&self, path: &Path) -> bool {
+        self.fs.stat(path, false).is_ok()
+    }
+
+    fn warn_on_checksum_mismatch(&self, info: &Trashinfo) {
+        if self.fs.is_local() && self.checksum_matches(info) == Some(false) {
+            warn!(
+                "The contents of {} changed since it was trashed, the trashed file may be corrupted",
+                info.original_filepath.display()
+            );
+        }
+    }
+}
+
+/// The trash at `path` a user asked for, created if needed
+fn explicit_trash(fs: &dyn TrashFs, path: &Path) -> Result<Trash> {
+    fs.create_dir_all(path)
+        .context(f!("Failed to create {}", path.display()))?;
+    let path = path.canonicalize().context("Failed to resolve trash dir")?;
+    let dev_root = find_fs_root(&path).context("Failed to find mount point")?;
+    let device = fs::metadata(&path)
+        .context("Failed to stat trash dir")?
+        .dev();
+
+    // other tools can't know what the paths would be relative to, so it stores
+    // absolute paths just like the home trash
+    Trash::new_with_ensure(fs, path, dev_root, device, true, false)
+        .context("Failed to create trash dir")
+}
+
+/// Removes all `entries` (of the same trash) using a few threads,
+/// returning how many were removed, the bytes freed and the errors
+/// `removed_cb` is called (from the worker threads) for every entry that was removed
+fn remove_parallel(
+    backend: &dyn TrashBackend,
+    entries: &[Trashinfo],
+    opts: &EmptyOptions,
+    removed_cb: impl Fn(&Trashinfo) + Sync,
+) -> (usize, u64, Vec<TrashError>) {
+    // deleting is mostly waiting on the filesystem, but too many threads just fight over the same disk
+    let workers = thread::available_parallelism()
+        .map_or(1, |x| x.get())
+        .clamp(1, MAX_EMPTY_WORKERS)
+        .min(entries.len());
+
+    let next = AtomicUsize::new(0);
+    let removed = AtomicUsize::new(0);
+    let freed = AtomicU64::new(0);
+    let errors = Mutex::new(vec![]);
+
+    thread::scope(|scope| {
+        for _ in 0..workers {
+            scope.spawn(|| {
+                while let Some(info) = entries.get(next.fetch_add(1, Ordering::Relaxed)) {
+                    match empty_entry(backend, info, opts) {
+                        Ok(size) => {
+                            removed_cb(info);
+                            removed.fetch_add(1, Ordering::Relaxed);
+                            freed.fetch_add(size, Ordering::Relaxed);
+                        }
+                        Err(e) => errors.lock().unwrap_or_else(|x| x.into_inner()).push(e),
+                    }
+                }
+            });
+        }
+    });
+
+    (
+        removed.into_inner(),
+        freed.into_inner(),
+        errors.into_inner().unwrap_or_else(|x| x.into_inner()),
+    )
+}
+
+/// Deletes a single entry for `empty`, returning the bytes freed
+fn empty_entry(backend: &dyn TrashBackend, info: &Trashinfo, opts: &EmptyOptions) -> Result<u64> {
+    if !opts.quiet {
+        println!(
+            "Removing {}",
+            info.trash.files_dir().join(&info.trash_filename).display()
+        );
+    }
+
+    backend
+        .remove(info, opts.shred)
+        .context(f!("Failed to remove {}", info.original_filepath.display()))
+}
+
+/// Records the entry and writes a compressed and/or encrypted copy of `input_file`
+/// (as set in the metadata of `info`) into its trash, removing the original once the copy is complete
+fn put_stored(
+    backend: &dyn TrashBackend,
+    info: &mut Trashinfo,
+    input_file: &Path,
+    key: Option<&Key>,
+) -> Result<()> {
+    backend.add_entry(info).context(format!(
+        "Failed to write to {}",
+        info.trash.trash_path.display()
+    ))?;
+
+    let files_path = info.trash.files_dir().join(&info.trash_filename);
+    if let Err(e) = contents::store_file(input_file, &files_path, info.metadata.compressed, key) {
+        let _ = fs::remove_file(&files_path);
+        let _ = backend.forget(info);
+        return Err(e).context(f!("Failed to store {}", input_file.display()));
+    }
+
+    if let Err(e) = fs::remove_file(input_file) {
+        // the file wasn't trashed after all, so the entry has to go
+        let _ = fs::remove_file(&files_path);
+        let _ = backend.forget(info);
+        return Err(e).context(f!("Failed to remove {}", input_file.display()));
+    }
+
+    Ok(())
+}
+
+/// The operation already happened, so failing to record it is not an error
+fn record_or_warn(
+    journal: Option<&Journal>,
+    action: Action,
+    info: &Trashinfo,
+    at: chrono::NaiveDateTime,
+) {
+    if let Some(Err(e)) = journal.map(|x| x.record(action, info, at)) {
+        warn!("Failed to record {} in the journal: {:#}", action, e);
+    }
+}
+
+#[test]
+fn test_put_restore_empty() {
+    use super::{clock::FixedClock, trash_fs::MemoryFs};
+
+    let fs = Arc::new(MemoryFs::new());
+    let now = chrono::NaiveDate::from_ymd_opt(2024, 1, 22)
+        .unwrap()
+        .and_hms_opt(12, 0, 0)
+        .unwrap();
+    let trash = UnifiedTrash::on_fs(fs.clone(), PathBuf::from("/home/user/.local/share/Trash"))
+        .unwrap()
+        .with_clock(FixedClock(now));
+
+    let file = Path::new("/home/user/notes.txt");
+    fs.write(file, b"first");
+    let first = trash.put(file, false, |_, _| false).unwrap();
+    fs.write(file, b"second");
+    let second = trash.put(file, false, |_, _| false).unwrap();
+
+    assert!(!fs.exists(file));
+    assert_eq!(first.trash_filename, "notes.txt");
+    assert_eq!(second.trash_filename, "notes1.txt");
+    assert_eq!(trash.list().unwrap().len(), 2);
+
+    for path in [
+        "/home/user/.local",
+        "/home/user/.local/share/Trash/files/notes.txt",
+    ] {
+        let refused = trash.put(Path::new(path), false, |_, _| false);
+        assert!(
+            matches!(refused, Err(TrashError::Unsupported(_))),
+            "{}",
+            path
+        );
+    }
+
+    let restored = trash
+        .restore(
+            |x| x.trash_filename == first.trash_filename,
+            |_| unreachable!(),
+            |_| false,
+        )
+        .unwrap();
+    assert_eq!(restored, file);
+    assert_eq!(fs.read(file).unwrap(), b"first");
+
+    // the restored file is in the way now
+    let conflict = trash.restore(|_| true, |_| unreachable!(), |_| false);
+    assert!(matches!(conflict, Err(TrashError::Aborted)));
+
+    // left behind by an interrupted operation
+    fs.create_dir_all(Path::new("/home/user/.local/share/Trash/files/stray"))
+        .unwrap();
+
+    let opts = EmptyOptions {
+        quiet: true,
+        ..Default::default()
+    };
+    let summary = trash
+        .empty(now + chrono::Duration::seconds(1), |_| true, &opts)
+        .unwrap();
+    assert_eq!(
+        (summary.removed, summary.freed, summary.stray_dirs),
+        (1, 6, 1)
+    );
+    assert!(trash.list().unwrap().is_empty());
+    assert!(fs
+        .read_dir(Path::new("/home/user/.local/share/Trash/files"))
+        .unwrap()
+        .is_empty());
+}
+
+#[test]
+fn test_simulated_restore_of_compressed() {
+    let dir = crate::test::TempDir::new("simulate");
+    let file = dir.join("a.txt");
+    fs::write(&file, "contents").unwrap();
+
+    let trash = UnifiedTrash::in_dir(&dir.join("trash"))
+        .unwrap()
+        .with_compression(true);
+    let info = trash.put(&file, false, |_, _| false).unwrap();
+    assert!(info.metadata.compressed);
+
+    let simulated = UnifiedTrash::in_dir(&dir.join("trash"))
+        .unwrap()
+        .with_simulation();
+    simulated
+        .restore(|_| true, |_| unreachable!(), |_| false)
+        .unwrap();
+
+    assert!(!file.exists());
+    assert!(info.trash.files_dir().join(&info.trash_filename).exists());
+    assert!(info
+        .trash
+        .info_dir()
+        .join(&info.trash_filename_trashinfo)
+        .exists());
+}
+
+#[test]
+fn test_retention_escalation() {
+    let dir = crate::test::TempDir::new("escalate");
+
+    let trash = UnifiedTrash::in_dir(&dir.join("trash"))
+        .unwrap()
+        .with_retention(Some(Retention {
+            max_age: Some(chrono::Duration::days(30)),
+            max_size: None,
+        }))
+        .with_usage_warning(Some(UsageWarning {
+            max_percent: None,
+            max_size: Some(150),
+        }));
+    for (name, size) in [("small", 50), ("big", 100), ("medium", 70)] {
+        let file = dir.join(name);
+        fs::write(&file, vec![0u8; size]).unwrap();
+        trash.put(&file, false, |_, _| false).unwrap();
+    }
+
+    // nothing is old enough for the policy to remove
+    let opts = EmptyOptions {
+        quiet: true,
+        ..Default::default()
+    };
+    let summary = trash.empty_by_retention(&opts, None).unwrap();
+    assert_eq!(summary.removed, 0);
+    assert_eq!(summary.stuck.len(), 1);
+    assert_eq!(summary.stuck[0].used, 220);
+    assert_eq!(summary.stuck[0].untracked, 0);
+
+    let summary = trash.empty_by_retention(&opts, Some(1)).unwrap();
+    assert_eq!(summary.removed, 1);
+    assert_eq!(summary.stuck[0].purged, 1);
+    let left = trash.list().unwrap();
+    assert_eq!(left.len(), 2);
+    assert!(left.iter().all(|x| !x.original_filepath.ends_with("big")));
 }
@@ -0,0 +1,167 @@
+use std::{
+    ffi::OsStr,
+    fmt::Display,
+    fs,
+    os::unix::ffi::OsStrExt,
+    path::{Path, PathBuf},
+};
+
+use rustc_hash::{FxHashMap, FxHashSet};
+
+use super::{
+    error::{Context, Result},
+    trashinfo, UnifiedTrash,
+};
+
+/// An integrity problem found by [`verify`]
+#[derive(Debug, Clone)]
+pub struct Problem {
+    /// The file in the trash the problem is about
+    pub path: PathBuf,
+    pub kind: ProblemKind,
+}
+
+#[derive(Debug, Clone)]
+pub enum ProblemKind {
+    /// The trashinfo file could not be parsed
+    Unparseable(String),
+
+    /// The trashinfo file contains the same key multiple times
+    DuplicateKey(String),
+
+    /// The trashinfo file has no matching file in the `files` dir
+    MissingFile,
+
+    /// The file in the `files` dir has no matching trashinfo file
+    MissingInfo,
+
+    /// The file (or something inside it, if it's a directory) can't be read
+    Unreadable(PathBuf, String),
+
+    /// The contents of the file don't match the checksum recorded when it was trashed
+    ChecksumMismatch,
+}
+
+impl Display for ProblemKind {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ProblemKind::Unparseable(e) => write!(f, "can not be parsed: {}", e),
+            ProblemKind::DuplicateKey(k) => write!(f, "contains {} more than once", k),
+            ProblemKind::MissingFile => write!(f, "has no matching file in the trash"),
+            ProblemKind::MissingInfo => write!(f, "has no matching trashinfo file"),
+            ProblemKind::Unreadable(p, e) => write!(f, "{} can not be read: {}", p.display(), e),
+            ProblemKind::ChecksumMismatch => {
+                write!(f, "has contents that changed since it was trashed")
+            }
+        }
+    }
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct Verification {
+    /// Number of trashinfo files that were checked
+    pub checked: usize,
+    pub problems: Vec<Problem>,
+}
+
+/// Checks the integrity of every trash, making sure that every entry can actually be
+/// listed and restored.
+pub fn verify(trash: &UnifiedTrash) -> Result<Verification> {
+    let mut verification = Verification::default();
+
+    let unified = trash;
+    for trash in trash.list_trashes() {
+        let mut problem = |path: &Path, kind| {
+            verification.problems.push(Problem {
+                path: path.to_path_buf(),
+                kind,
+            })
+        };
+
+        let mut infos = FxHashSet::default();
+        for entry in fs::read_dir(trash.info_dir()).context("Failed to read info dir")? {
+            let path = entry.context("Failed to read info dir")?.path();
+            verification.checked += 1;
+
+            if let Some(name) = path
+                .file_name()
+                .and_then(|x| x.as_bytes().strip_suffix(b".trashinfo"))
+            {
+                infos.insert(OsStr::from_bytes(name).to_os_string());
+            }
+
+            for key in duplicate_keys(&path) {
+                problem(&path, ProblemKind::DuplicateKey(key));
+            }
+
+            let info = match trashinfo::parse_trashinfo(&path, trash) {
+                Ok(v) => v,
+                Err(e) => {
+                    problem(&path, ProblemKind::Unparseable(format!("{:#}", e)));
+                    continue;
+                }
+            };
+
+            let files_path = trash.files_dir().join(&info.trash_filename);
+            if fs::symlink_metadata(&files_path).is_err() {
+                problem(&path, ProblemKind::MissingFile);
+            } else if unified.checksum_matches(&info) == Some(false) {
+                problem(&files_path, ProblemKind::ChecksumMismatch);
+            }
+        }
+
+        for entry in fs::read_dir(trash.files_dir()).context("Failed to read files dir")? {
+            let path = entry.context("Failed to read files dir")?.path();
+
+            if !path.file_name().is_some_and(|x| infos.contains(x)) {
+                problem(&path, ProblemKind::MissingInfo);
+            }
+
+            if let Err((unreadable, e)) = check_readable(&path) {
+                problem(&path, ProblemKind::Unreadable(unreadable, e.to_string()));
+            }
+        }
+    }
+
+    Ok(verification)
+}
+
+/// The keys that are contained more than once in the trashinfo file at `path`
+fn duplicate_keys(path: &Path) -> Vec<String> {
+    let Ok(content) = fs::read_to_string(path) else {
+        return vec![];
+    };
+
+    let mut counts = FxHashMap::default();
+    for key in content
+        .lines()
+        .filter_map(|x| x.split_once('='))
+        .map(|x| x.0)
+    {
+        *counts.entry(key).or_insert(0) += 1;
+    }
+
+    let mut duplicates = counts
+        .into_iter()
+        .filter(|(_, count)| *count > 1)
+        .map(|(key, _)| key.to_owned())
+        .collect::<Vec<_>>();
+    duplicates.sort();
+    duplicates
+}
+
+/// Makes sure every file at (or below) `path` can be opened, symlinks are not followed
+fn check_readable(path: &Path) -> Result<(), (PathBuf, std::io::Error)> {
+    let meta = fs::symlink_metadata(path).map_err(|e| (path.to_path_buf(), e))?;
+
+    if meta.is_dir() {
+        for entry in fs::read_dir(path).map_err(|e| (path.to_path_buf(), e))? {
+            let entry = entry.map_err(|e| (path.to_path_buf(), e))?;
+            check_readable(&entry.path())?;
+        }
+    } else if meta.is_file() {
+        fs::File::open(path).map_err(|e| (path.to_path_buf(), e))?;
+    }
+
+    Ok(())
+}
@@ -0,0 +1,199 @@
+use std::{
+    ffi::{CString, OsStr, OsString},
+    fs::File,
+    io::{self, Read, Seek},
+    mem,
+    os::{
+        fd::{AsFd, AsRawFd, FromRawFd, OwnedFd},
+        unix::ffi::OsStrExt,
+    },
+    path::PathBuf,
+    ptr,
+    sync::Arc,
+};
+
+use rustc_hash::FxHashMap;
+
+use super::{
+    error::{Context, Result, TrashError},
+    trashinfo::parse_trashinfo,
+    Trash, Trashinfo, UnifiedTrash,
+};
+
+/// Something that happened in the `info` dir of a trash
+#[derive(Debug, Clone)]
+pub enum WatchEvent {
+    /// A trashinfo file was written, by trashing a file or by another tool rewriting it
+    Added(Trashinfo),
+
+    /// A trashinfo file is gone, because its entry was restored or removed
+    Removed {
+        trash: Arc<Trash>,
+        trash_filename: OsString,
+
+        /// The entry as it was last seen, only known for entries that existed
+        /// when watching started or were added since
+        info: Option<Trashinfo>,
+    },
+}
+
+/// Watches the `info` dirs of every trash with inotify and calls `f` for every entry that is
+/// added or removed, by this tool or any other. Only returns if `f` (or reading the events) fails.
+///
+/// When something is mounted or unmounted, the `trash` is [refreshed](UnifiedTrash::refresh),
+/// so trashes on drives that are plugged in later on are watched as well.
+pub fn watch(trash: &mut UnifiedTrash, mut f: impl FnMut(WatchEvent) -> Result<()>) -> Result<()> {
+    // SAFETY: no pointers involved, the fd is checked before use
+    let fd = unsafe { libc::inotify_init1(libc::IN_CLOEXEC) };
+    if fd < 0 {
+        return Err(io::Error::last_os_error()).context("Failed to start inotify");
+    }
+    // SAFETY: the fd was just opened and isn't owned by anything else
+    let fd = unsafe { OwnedFd::from_raw_fd(fd) };
+
+    let mut watches = FxHashMap::default();
+    for target in trash.list_trashes() {
+        if let Some(wd) = add_watch(&fd, target)? {
+            watches.insert(wd, Arc::clone(target));
+        }
+    }
+
+    if watches.is_empty() {
+        return Err(TrashError::NotFound("No trash could be watched".to_owned()));
+    }
+
+    // the trashinfo file is gone when it's removed, so the entries have to be remembered
+    let mut known = remember(trash)?;
+
+    // the kernel flags it with POLLPRI whenever something is mounted or unmounted
+    let mut mountinfo = File::open("/proc/self/mountinfo")
+        .inspect_err(|e| log::debug!("Not watching for new mounts: {}", e))
+        .ok();
+
+    let mut events = File::from(fd);
+    let mut buf = vec![0u8; 64 * 1024];
+    loop {
+        if let Some(mounts) = &mut mountinfo {
+            let mut fds = [
+                libc::pollfd {
+                    fd: events.as_raw_fd(),
+                    events: libc::POLLIN,
+                    revents: 0,
+                },
+                libc::pollfd {
+                    fd: mounts.as_raw_fd(),
+                    events: libc::POLLPRI,
+                    revents: 0,
+                },
+            ];
+            // SAFETY: fds is an array of 2 pollfds with open fds
+            if unsafe { libc::poll(fds.as_mut_ptr(), 2, -1) } < 0 {
+                let e = io::Error::last_os_error();
+                if e.kind() == io::ErrorKind::Interrupted {
+                    continue;
+                }
+                return Err(e).context("Failed to wait for events");
+            }
+
+            if fds[1].revents & (libc::POLLPRI | libc::POLLERR) != 0 {
+                // the flag is only cleared by reading it again
+                mounts.rewind()?;
+                io::copy(mounts, &mut io::sink())?;
+
+                trash.refresh().context("Failed to rescan mounts")?;
+                // the kernel drops the watches of unmounted filesystems on its own
+                watches.retain(|_, x| trash.list_trashes().contains(x));
+                let fd = events.as_fd();
+                for target in trash.list_trashes() {
+                    if !watches.values().any(|x| x == target) {
+                        if let Some(wd) = add_watch(fd, target)? {
+                            log::debug!("Watching {}", target.trash_path.display());
+                            watches.insert(wd, Arc::clone(target));
+                        }
+                    }
+                }
+                known = remember(trash)?;
+            }
+            if fds[0].revents & libc::POLLIN == 0 {
+                continue;
+            }
+        }
+
+        let n = events
+            .read(&mut buf)
+            .context("Failed to read inotify events")?;
+
+        let mut offset = 0;
+        while offset + mem::size_of::<libc::inotify_event>() <= n {
+            // SAFETY: the kernel only writes complete events, which are followed by `len` bytes
+            // for the name. The buffer isn't aligned for the struct, so it's read unaligned
+            let event = unsafe {
+                ptr::read_unaligned(buf.as_ptr().add(offset) as *const libc::inotify_event)
+            };
+            let name_start = offset + mem::size_of::<libc::inotify_event>();
+            offset = name_start + event.len as usize;
+
+            // the name is padded with NUL bytes
+            let name = buf[name_start..offset]
+                .split(|x| *x == 0)
+                .next()
+                .unwrap_or_default();
+            let (Some(target), Some(trash_filename)) =
+                (watches.get(&event.wd), name.strip_suffix(b".trashinfo"))
+            else {
+                continue;
+            };
+            let trash_filename = OsStr::from_bytes(trash_filename).to_os_string();
+            let key = (target.trash_path.clone(), trash_filename.clone());
+
+            if event.mask & (libc::IN_CLOSE_WRITE | libc::IN_MOVED_TO) != 0 {
+                let path = target.info_dir().join(OsStr::from_bytes(name));
+                match parse_trashinfo(&path, target) {
+                    Ok(info) => {
+                        known.insert(key, info.clone());
+                        f(WatchEvent::Added(info))?;
+                    }
+                    // most likely gone again already
+                    Err(e) => log::debug!("Failed to parse {}: {:#}", path.display(), e),
+                }
+            } else {
+                f(WatchEvent::Removed {
+                    trash: Arc::clone(target),
+                    trash_filename,
+                    info: known.remove(&key),
+                })?;
+            }
+        }
+    }
+}
+
+/// Watches the `info` dir of `target`, `None` if that's not possible
+fn add_watch(fd: impl AsFd, target: &Trash) -> Result<Option<i32>> {
+    let mask = libc::IN_CLOSE_WRITE | libc::IN_MOVED_TO | libc::IN_DELETE | libc::IN_MOVED_FROM;
+    let dir = CString::new(target.info_dir().as_os_str().as_bytes())
+        .map_err(io::Error::from)
+        .context("Invalid trash path")?;
+    // SAFETY: dir is nul terminated
+    let wd = unsafe { libc::inotify_add_watch(fd.as_fd().as_raw_fd(), dir.as_ptr(), mask) };
+    if wd < 0 {
+        log::warn!(
+            "Failed to watch {}: {}",
+            target.trash_path.display(),
+            io::Error::last_os_error()
+        );
+        return Ok(None);
+    }
+    Ok(Some(wd))
+}
+
+/// The current entries of the `trash`, by trash and name in it
+fn remember(trash: &UnifiedTrash) -> Result<FxHashMap<(PathBuf, OsString), Trashinfo>> {
+    let mut known = FxHashMap::default();
+    for info in trash.list().context("Failed to list trash")? {
+        known.insert(
+            (info.trash.trash_path.clone(), info.trash_filename.clone()),
+            info,
+        );
+    }
+    Ok(known)
+}
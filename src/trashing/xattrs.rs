@@ -0,0 +1,102 @@
+use std::{
+    ffi::OsString,
+    fs, io,
+    os::unix::ffi::OsStrExt,
+    path::{Path, PathBuf},
+};
+
+use serde::{Deserialize, Serialize};
+
+use super::{
+    error::{Context, Result},
+    original_metadata::is_privileged_for,
+    Trashinfo,
+};
+
+/// Extended attributes of a trashed file (this includes ACLs and security labels,
+/// which are stored as `system.*` and `security.*` attributes).
+///
+/// They are kept in a sidecar file at `$trash/xattrs/<name>.json`, outside of the
+/// directories the spec defines, so other implementations don't trip over them.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct Xattrs {
+    attrs: Vec<(OsString, Vec<u8>)>,
+}
+
+impl Xattrs {
+    /// Reads all extended attributes of `path` (not following symlinks).
+    /// Only the top level file is looked at, not the contents of directories.
+    pub fn capture(path: &Path) -> io::Result<Self> {
+        let mut attrs = vec![];
+        for name in xattr::list(path)? {
+            if let Some(value) = xattr::get(path, &name)? {
+                attrs.push((name, value));
+            }
+        }
+        Ok(Self { attrs })
+    }
+
+    /// Stores the attributes next to the trashed file of `info`, nothing is written if there are none
+    pub fn save(&self, info: &Trashinfo) -> Result<()> {
+        if self.attrs.is_empty() {
+            return Ok(());
+        }
+
+        let path = sidecar_path(info);
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent).context("Failed to create xattrs dir")?;
+        }
+
+        let serialized = serde_json::to_vec(self).context("Failed to serialize xattrs")?;
+        fs::write(&path, serialized).context(format!("Failed to write {}", path.display()))
+    }
+
+    /// Loads the attributes stored for `info`, `None` if there are none
+    pub fn load(info: &Trashinfo) -> Result<Option<Self>> {
+        let path = sidecar_path(info);
+        let raw = match fs::read(&path) {
+            Ok(v) => v,
+            Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(None),
+            Err(e) => return Err(e).context(format!("Failed to read {}", path.display())),
+        };
+
+        Ok(Some(
+            serde_json::from_slice(&raw).context("Failed to parse xattrs")?,
+        ))
+    }
+
+    /// Sets every attribute that is missing or differs on `path`.
+    /// Running as root for another user than `owner`, only `user.*` attributes are set,
+    /// the others (capabilities, labels, ACLs) could grant more than the user has
+    pub fn apply(&self, path: &Path, owner: u32) -> io::Result<()> {
+        let privileged = is_privileged_for(owner);
+        for (name, value) in &self.attrs {
+            if privileged && !name.as_bytes().starts_with(b"user.") {
+                log::info!(
+                    "Not restoring {} of {} for another user",
+                    name.to_string_lossy(),
+                    path.display()
+                );
+                continue;
+            }
+            if xattr::get(path, name)?.as_ref() != Some(value) {
+                xattr::set(path, name, value)?;
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Removes the sidecar file of `info`, if there is one
+pub fn remove_sidecar(info: &Trashinfo) -> io::Result<()> {
+    match fs::remove_file(sidecar_path(info)) {
+        Err(e) if e.kind() != io::ErrorKind::NotFound => Err(e),
+        _ => Ok(()),
+    }
+}
+
+fn sidecar_path(info: &Trashinfo) -> PathBuf {
+    let mut name = info.trash_filename.clone();
+    name.push(".json");
+    info.trash.trash_path.join("xattrs").join(name)
+}